@@ -1,13 +1,15 @@
 fn main() {
+    // libheif は heif feature が有効な場合のみ必要
     #[cfg(target_os = "macos")]
+    if std::env::var_os("CARGO_FEATURE_HEIF").is_some()
+        && pkg_config::probe_library("libheif").is_err()
     {
-        // Homebrew で libheif / libaom をインストール済みを想定
-        if pkg_config::probe_library("libheif").is_err() {
-            panic!("libheif not found. Install via Homebrew: brew install libheif");
-        }
-        if pkg_config::probe_library("aom").is_err() {
-            panic!("libaom not found. Install via Homebrew: brew install libaom");
-        }
+        // Homebrew で libheif をインストールするか、heif feature を無効にしてビルドする
+        panic!(
+            "libheif not found. Install via Homebrew (brew install libheif) or build without the `heif` feature (see [features] in Cargo.toml)"
+        );
     }
-    tauri_build::build()
+    // デスクトップアプリ (gui feature) をビルドする場合のみ Tauri の設定を読み込む
+    #[cfg(feature = "gui")]
+    tauri_build::build();
 }
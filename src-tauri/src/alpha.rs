@@ -0,0 +1,130 @@
+use crate::options::AlphaOptions;
+use image::{DynamicImage, RgbImage};
+
+/// アルファチャンネルの処理を適用する
+/// # 引数
+/// - `img`: 処理対象の画像
+/// - `options`: アルファのオプション
+/// # 戻り値
+/// - 処理後の `DynamicImage` を返します。
+/// # 注意
+/// - `background` が指定された場合は背景色に合成し、アルファの無い RGB 画像を返します。
+/// - `drop_opaque` が有効で全ての画素が不透明な場合は RGB 画像として返します (エンコード時のアルファ分の容量を削減)。
+/// - `clean_transparent` が有効な場合は完全に透明な画素の色を黒に揃えます (圧縮率の向上)。
+/// - アルファを持つ画像は RGBA8 に変換してから処理します。
+pub fn apply_alpha(img: DynamicImage, options: &AlphaOptions) -> DynamicImage {
+    if !img.color().has_alpha() {
+        return img;
+    }
+
+    if let Some(background) = options.background {
+        eprintln!("Alpha: Flattening onto background...");
+        return DynamicImage::ImageRgb8(flatten(&img, background));
+    }
+
+    let mut rgba = img.into_rgba8();
+
+    if options.drop_opaque && rgba.pixels().all(|p| p[3] == 255) {
+        eprintln!("Alpha: Image is fully opaque, dropping alpha channel...");
+        return DynamicImage::ImageRgb8(DynamicImage::ImageRgba8(rgba).into_rgb8());
+    }
+
+    if options.clean_transparent {
+        eprintln!("Alpha: Cleaning color of transparent pixels...");
+        for pixel in rgba.pixels_mut().filter(|p| p[3] == 0) {
+            pixel.0 = [0, 0, 0, 0];
+        }
+    }
+
+    DynamicImage::ImageRgba8(rgba)
+}
+
+/// 背景色に合成してアルファを取り除く
+fn flatten(img: &DynamicImage, background: [u8; 3]) -> RgbImage {
+    let rgba = img.to_rgba8();
+    RgbImage::from_fn(rgba.width(), rgba.height(), |x, y| {
+        let p = rgba.get_pixel(x, y);
+        let a = p[3] as u32;
+        let mix =
+            |c: usize| ((p[c] as u32 * a + background[c] as u32 * (255 - a) + 127) / 255) as u8;
+        image::Rgb([mix(0), mix(1), mix(2)])
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{Rgb, Rgba, RgbaImage};
+
+    /// 1 行に並べた画素の RGBA 画像
+    fn row(pixels: &[[u8; 4]]) -> DynamicImage {
+        DynamicImage::ImageRgba8(RgbaImage::from_fn(pixels.len() as u32, 1, |x, _| {
+            Rgba(pixels[x as usize])
+        }))
+    }
+
+    #[test]
+    fn background_is_mixed_by_alpha() {
+        let img = row(&[
+            [255, 0, 0, 255],
+            [255, 0, 0, 0],
+            [255, 0, 0, 128],
+            [0, 0, 255, 51],
+        ]);
+        let options = AlphaOptions {
+            background: Some([0, 255, 0]),
+            ..Default::default()
+        };
+        let result = apply_alpha(img, &options);
+        let rgb = result.as_rgb8().unwrap();
+        assert_eq!(rgb.get_pixel(0, 0), &Rgb([255, 0, 0]));
+        assert_eq!(rgb.get_pixel(1, 0), &Rgb([0, 255, 0]));
+        assert_eq!(rgb.get_pixel(2, 0), &Rgb([128, 127, 0]));
+        assert_eq!(rgb.get_pixel(3, 0), &Rgb([0, 204, 51]));
+    }
+
+    #[test]
+    fn drop_opaque_only_when_every_pixel_is_opaque() {
+        let options = AlphaOptions::default();
+        let opaque = apply_alpha(row(&[[1, 2, 3, 255], [4, 5, 6, 255]]), &options);
+        assert_eq!(opaque.as_rgb8().unwrap().as_raw(), &vec![1, 2, 3, 4, 5, 6]);
+
+        let translucent = apply_alpha(row(&[[1, 2, 3, 255], [4, 5, 6, 254]]), &options);
+        assert!(translucent.as_rgba8().is_some());
+
+        let disabled = AlphaOptions {
+            drop_opaque: false,
+            ..Default::default()
+        };
+        let kept = apply_alpha(row(&[[1, 2, 3, 255]]), &disabled);
+        assert!(kept.as_rgba8().is_some());
+    }
+
+    #[test]
+    fn clean_transparent_zeroes_only_fully_transparent_pixels() {
+        let img = row(&[[10, 20, 30, 0], [10, 20, 30, 1], [10, 20, 30, 255]]);
+        let options = AlphaOptions {
+            clean_transparent: true,
+            ..Default::default()
+        };
+        let result = apply_alpha(img.clone(), &options);
+        assert_eq!(
+            result.as_rgba8().unwrap().as_raw(),
+            &vec![0, 0, 0, 0, 10, 20, 30, 1, 10, 20, 30, 255]
+        );
+        // 既定では色情報を残す
+        let result = apply_alpha(img.clone(), &AlphaOptions::default());
+        assert_eq!(result.to_rgba8(), img.to_rgba8());
+    }
+
+    #[test]
+    fn images_without_alpha_are_unchanged() {
+        let img = DynamicImage::ImageRgb8(RgbImage::from_pixel(2, 2, Rgb([9, 8, 7])));
+        let options = AlphaOptions {
+            background: Some([0, 0, 0]),
+            clean_transparent: true,
+            ..Default::default()
+        };
+        assert_eq!(apply_alpha(img.clone(), &options), img);
+    }
+}
@@ -0,0 +1,262 @@
+use crate::color::apply_color_management;
+use crate::decoder::{ColorProfile, check_decodable, decode};
+use crate::encoder::{Metadata, encode};
+use crate::error::AppError;
+use crate::metrics::ssimulacra2;
+use crate::options::{
+    AlphaColorMode, AutoCandidate, AutoOptions, AvifOptions, BitDepth, CandidateReport, ColorModel,
+    ConvertResult, EncodeOptions, WebpOptions,
+};
+use image::DynamicImage;
+use std::borrow::Cow;
+use std::thread;
+
+/// 候補のエンコード結果 (出力のバイト列と SSIMULACRA2 のスコア)
+type CandidateOutput = Result<(Vec<u8>, Option<f64>), AppError>;
+
+/// 候補を並列にエンコードし、最も小さい出力を選ぶ
+/// # 引数
+/// - `img`: エンコード前の処理を適用済みの画像
+/// - `original`: 元のファイルのバイト列 (Original 候補に使用、None の場合は Original を除外)
+/// - `options`: エンコードオプション (webp / avif のオプションがあれば候補の設定に使う)
+/// - `metadata`: 出力に引き継ぐ ICC プロファイルや EXIF
+/// - `auto`: 自動選択のオプション
+/// # 戻り値
+/// - 成功した場合は選ばれた候補の結果と、全候補のサイズを返します。
+/// - 失敗した場合 (選択できる候補が無い場合) は `AppError` を返します。
+/// # 注意
+/// - `min_score` を指定した場合、非可逆圧縮の候補はデコードして SSIMULACRA2 を計算し、閾値を下回るものを除外します。
+/// - 可逆圧縮と Original の候補は常に選択の対象になります。
+/// - ICC プロファイルを埋め込めない候補 (AVIF) は、sRGB に変換してからエンコードします。
+/// - スコアを計算する候補の形式をデコードできない場合、その候補はエンコードせずに失敗として扱います。
+pub fn encode_auto(
+    img: &DynamicImage,
+    original: Option<&[u8]>,
+    options: &EncodeOptions,
+    metadata: &Metadata,
+    auto: &AutoOptions,
+) -> Result<ConvertResult, AppError> {
+    eprintln!("Auto: Encoding {} candidates...", auto.candidates.len());
+
+    let results: Vec<(AutoCandidate, CandidateOutput)> = thread::scope(|scope| {
+        let handles: Vec<_> = auto
+            .candidates
+            .iter()
+            .map(|&candidate| {
+                let handle = scope.spawn(move || {
+                    encode_candidate(img, original, options, metadata, auto, candidate)
+                });
+                (candidate, handle)
+            })
+            .collect();
+        handles
+            .into_iter()
+            .map(|(candidate, handle)| {
+                let result = handle.join().unwrap_or_else(|_| {
+                    Err(AppError::Encode("Candidate encoder panicked".to_string()))
+                });
+                (candidate, result)
+            })
+            .collect()
+    });
+
+    let mut best: Option<(AutoCandidate, Vec<u8>, Option<f64>)> = None;
+    let mut reports = Vec::with_capacity(results.len());
+    for (candidate, result) in results {
+        match result {
+            Ok((data, score)) => {
+                let accepted = match (auto.min_score, score) {
+                    (Some(min), Some(score)) => score >= min,
+                    _ => true,
+                };
+                eprintln!(
+                    "Auto: {:?} -> {} bytes{}",
+                    candidate,
+                    data.len(),
+                    if accepted { "" } else { " (below threshold)" }
+                );
+                reports.push(CandidateReport {
+                    candidate,
+                    size: Some(data.len() as u64),
+                    score,
+                    accepted,
+                    error: None,
+                });
+                if accepted && best.as_ref().is_none_or(|b| data.len() < b.1.len()) {
+                    best = Some((candidate, data, score));
+                }
+            }
+            Err(e) => {
+                eprintln!("Auto: {:?} failed: {}", candidate, e);
+                reports.push(CandidateReport {
+                    candidate,
+                    size: None,
+                    score: None,
+                    accepted: false,
+                    error: Some(e.to_string()),
+                });
+            }
+        }
+    }
+
+    let (chosen, data, score) =
+        best.ok_or(AppError::Encode("No acceptable auto candidate".to_string()))?;
+    eprintln!("Auto: Chose {:?}.", chosen);
+    Ok(ConvertResult {
+        width: img.width(),
+        height: img.height(),
+        size: data.len() as u64,
+        data,
+        quality: chosen
+            .is_lossy()
+            .then_some(candidate_quality(options, auto, chosen)),
+        attempts: reports.len() as u32,
+        score,
+        chosen: Some(chosen),
+        candidates: reports,
+    })
+}
+
+/// 1 つの候補をエンコードする (閾値がある非可逆圧縮の候補はスコアも計算する)
+fn encode_candidate(
+    img: &DynamicImage,
+    original: Option<&[u8]>,
+    options: &EncodeOptions,
+    metadata: &Metadata,
+    auto: &AutoOptions,
+    candidate: AutoCandidate,
+) -> CandidateOutput {
+    let mut encode_options = EncodeOptions::default();
+    match candidate {
+        AutoCandidate::Original => {
+            let data = original.ok_or(AppError::Encode(
+                "Original is not available because the image was modified".to_string(),
+            ))?;
+            return Ok((data.to_vec(), None));
+        }
+        AutoCandidate::WebpLossy => {
+            encode_options.webp = Some(WebpOptions {
+                quality: candidate_quality(options, auto, candidate),
+                lossless: false,
+            });
+        }
+        AutoCandidate::WebpLossless => {
+            encode_options.webp = Some(WebpOptions {
+                quality: 100.0,
+                lossless: true,
+            });
+        }
+        AutoCandidate::Avif => {
+            let quality = candidate_quality(options, auto, candidate);
+            encode_options.avif = Some(match &options.avif {
+                Some(avif) => AvifOptions {
+                    quality,
+                    ..avif.clone()
+                },
+                None => AvifOptions {
+                    lossless: false,
+                    quality,
+                    bit_depth: BitDepth::Eight,
+                    alpha_quality: quality,
+                    speed: 6,
+                    color_model: ColorModel::YCbCr,
+                    threads: None,
+                    alpha_color_mode: AlphaColorMode::UnassociatedClean,
+                },
+            });
+        }
+    }
+
+    let needs_score = auto.min_score.is_some() && candidate.is_lossy();
+    if needs_score && let Some(format) = candidate.format() {
+        check_decodable(format)?;
+    }
+
+    // ICC プロファイルを埋め込めない形式は sRGB に変換する (埋め込める形式はそのまま引き継ぐ)
+    let (img, metadata) = match &metadata.icc_profile {
+        Some(icc) if !encode_options.supports_icc_profile() => {
+            let (srgb, _) = apply_color_management(
+                img.clone(),
+                Some(ColorProfile::Icc(icc.clone())),
+                &options.color.unwrap_or_default(),
+                false,
+            )?;
+            let metadata = Metadata {
+                icc_profile: None,
+                exif: metadata.exif.clone(),
+            };
+            (Cow::Owned(srgb), Cow::Owned(metadata))
+        }
+        _ => (Cow::Borrowed(img), Cow::Borrowed(metadata)),
+    };
+
+    let data = encode(&img, encode_options, &metadata)?;
+    let score = if needs_score {
+        Some(ssimulacra2(&img, &decode(&data)?)?)
+    } else {
+        None
+    };
+    Ok((data, score))
+}
+
+/// 非可逆圧縮の候補の品質 (形式ごとのオプションが指定されていればその品質を使う)
+fn candidate_quality(options: &EncodeOptions, auto: &AutoOptions, candidate: AutoCandidate) -> f32 {
+    match candidate {
+        AutoCandidate::Avif => options.avif.as_ref().map(|a| a.quality),
+        AutoCandidate::WebpLossy => options
+            .webp
+            .as_ref()
+            .filter(|w| !w.lossless)
+            .map(|w| w.quality),
+        _ => None,
+    }
+    .unwrap_or(auto.quality)
+}
+
+#[cfg(all(test, feature = "webp"))]
+mod tests {
+    use super::*;
+    use image::RgbImage;
+
+    #[test]
+    fn candidates_keep_icc_profile() {
+        let img = DynamicImage::ImageRgb8(RgbImage::new(16, 16));
+        let metadata = Metadata {
+            icc_profile: Some(b"test icc profile".to_vec()),
+            exif: None,
+        };
+        let auto = AutoOptions {
+            candidates: vec![AutoCandidate::WebpLossless],
+            ..Default::default()
+        };
+        let result = encode_auto(&img, None, &EncodeOptions::default(), &metadata, &auto).unwrap();
+        assert_eq!(result.chosen, Some(AutoCandidate::WebpLossless));
+        assert!(result.data.windows(4).any(|chunk| chunk == b"ICCP"));
+    }
+
+    #[test]
+    fn scored_candidates_are_decodable() {
+        let img = DynamicImage::ImageRgb8(RgbImage::from_fn(32, 32, |x, y| {
+            image::Rgb([(x * 8) as u8, (y * 8) as u8, 64])
+        }));
+        let auto = AutoOptions {
+            candidates: vec![AutoCandidate::WebpLossy, AutoCandidate::Avif],
+            min_score: Some(0.0),
+            ..Default::default()
+        };
+        let result = encode_auto(
+            &img,
+            None,
+            &EncodeOptions::default(),
+            &Metadata::default(),
+            &auto,
+        )
+        .unwrap();
+        for report in &result.candidates {
+            let expected = report.candidate == AutoCandidate::WebpLossy
+                || (cfg!(feature = "avif")
+                    && crate::codec::registry().can_decode(crate::options::OutputFormat::Avif));
+            assert_eq!(report.error.is_none(), expected, "{:?}", report);
+        }
+    }
+}
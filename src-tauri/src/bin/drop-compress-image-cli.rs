@@ -0,0 +1,522 @@
+//! DropCompressImage のコマンドライン版
+//!
+//! デスクトップアプリと同じ変換処理 (デコード → 変形 → エンコード) を GUI なしで実行します。
+//! 進捗のログは標準エラー出力に、結果の JSON は標準出力に出力します
+//! (変換した画像を標準出力に書き出す場合は、JSON も標準エラー出力に出力します)。
+
+use clap::Parser;
+use drop_compress_image_lib::options::{
+    ConvertResult, EncodeOptions, FileConvertResult, FileOutcome, IsolationOptions, JpegOptions,
+    LargerOutputPolicy, OutputFormat, PngOptions, SizePolicyOptions, TargetSizeOptions,
+};
+use drop_compress_image_lib::{AppError, codec, convert_file, convert_with_report, isolation};
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+use std::fs;
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+
+/// 入出力や引数のエラー (引数の解析エラーは clap が 2 で終了する)
+const EXIT_FAILURE: u8 = 1;
+/// デコードに失敗した
+const EXIT_DECODE: u8 = 3;
+/// エンコード (変形を含む) に失敗した
+const EXIT_ENCODE: u8 = 4;
+/// 一部の入力の変換に失敗した
+const EXIT_PARTIAL: u8 = 5;
+
+/// 標準入出力を表す引数
+const STDIO: &str = "-";
+
+/// Convert images with the same pipeline as the DropCompressImage desktop app.
+#[derive(Parser)]
+#[command(name = "drop-compress-image-cli", version, about)]
+struct Cli {
+    /// Input files, directories or glob patterns ("-" reads from stdin)
+    #[arg(required = true)]
+    inputs: Vec<String>,
+    /// Output file or directory ("-" writes to stdout). Defaults to next to each input
+    #[arg(short, long)]
+    output: Option<String>,
+    /// Output format: avif, webp, jpeg or png (defaults to the output extension, then webp)
+    #[arg(short, long, value_parser = parse_enum::<OutputFormat>)]
+    format: Option<OutputFormat>,
+    /// Quality (0-100)
+    #[arg(short, long)]
+    quality: Option<f32>,
+    /// Use lossless compression (WebP)
+    #[arg(long)]
+    lossless: bool,
+    /// Encoding speed (AVIF, 0-10)
+    #[arg(long)]
+    speed: Option<u8>,
+    /// Output width in pixels
+    #[arg(long)]
+    width: Option<u32>,
+    /// Output height in pixels
+    #[arg(long)]
+    height: Option<u32>,
+    /// Shrink to fit within this width
+    #[arg(long)]
+    max_width: Option<u32>,
+    /// Shrink to fit within this height
+    #[arg(long)]
+    max_height: Option<u32>,
+    /// Search the quality so that the output fits within this many bytes
+    #[arg(long, value_parser = clap::value_parser!(u64).range(1..))]
+    max_bytes: Option<u64>,
+    /// Full EncodeOptions as JSON, or @path to a JSON file. Other flags override it
+    #[arg(long)]
+    options: Option<String>,
+    /// Search directories recursively
+    #[arg(short, long)]
+    recursive: bool,
+    /// Overwrite existing output files
+    #[arg(long)]
+    overwrite: bool,
+    /// What to do when the output is not smaller: Skip, KeepOriginal or WriteAnyway
+    #[arg(long, value_parser = parse_enum::<LargerOutputPolicy>, default_value = "WriteAnyway")]
+    on_larger: LargerOutputPolicy,
+    /// Minimum savings in percent required to write the output
+    #[arg(long, default_value_t = 0.0)]
+    min_savings: f64,
+    /// Decode and encode each input in a child process, so that a crash fails only that input
+    #[arg(long)]
+    isolate: bool,
+    /// Time limit in seconds for each input in isolation mode (0 disables it)
+    #[arg(long, default_value_t = 120, requires = "isolate")]
+    timeout: u64,
+}
+
+/// 変換対象の入力
+enum Input {
+    Stdin,
+    File(PathBuf),
+}
+
+impl Input {
+    fn name(&self) -> String {
+        match self {
+            Input::Stdin => STDIO.to_string(),
+            Input::File(path) => path.to_string_lossy().to_string(),
+        }
+    }
+}
+
+/// 出力先
+/// Beside: 入力ファイルと同じディレクトリ (標準入力の場合は標準出力)
+enum Output {
+    Stdout,
+    File(PathBuf),
+    Dir(PathBuf),
+    Beside,
+}
+
+/// 失敗の種類 (終了コードを決める)
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+enum Failure {
+    Decode,
+    Encode,
+    Io,
+}
+
+impl Failure {
+    /// 分離モードの子プロセスのエラーも同じく分類できるよう、コードで判定する
+    fn from_error(error: &AppError) -> Self {
+        match error.code() {
+            "decode_failed" | "unsupported_format" | "limit_exceeded" => Failure::Decode,
+            "file_not_found" | "permission_denied" | "file_exists" | "disk_full" | "io_error" => {
+                Failure::Io
+            }
+            _ => Failure::Encode,
+        }
+    }
+
+    fn exit_code(self) -> u8 {
+        match self {
+            Failure::Decode => EXIT_DECODE,
+            Failure::Encode => EXIT_ENCODE,
+            Failure::Io => EXIT_FAILURE,
+        }
+    }
+}
+
+/// 1 つの入力の結果 (JSON で出力する)
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Report {
+    input: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    result: Option<FileConvertResult>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<ReportError>,
+}
+
+/// 失敗した入力のエラー
+/// code: `AppError::code` の安定したコード
+#[derive(Serialize)]
+struct ReportError {
+    kind: Failure,
+    code: &'static str,
+    message: String,
+}
+
+fn main() -> ExitCode {
+    isolation::run_worker_if_requested();
+    let cli = Cli::parse();
+    match run(&cli) {
+        Ok(code) => ExitCode::from(code),
+        Err(message) => {
+            eprintln!("error: {}", message);
+            ExitCode::from(EXIT_FAILURE)
+        }
+    }
+}
+
+/// 入力を順に変換し、結果の JSON を出力して終了コードを返す
+fn run(cli: &Cli) -> Result<u8, String> {
+    let options = build_options(cli)?;
+    let inputs = collect_inputs(&cli.inputs, cli.recursive)?;
+    if inputs.is_empty() {
+        return Err("No input files".to_string());
+    }
+    let output = resolve_output(cli.output.as_deref(), inputs.len())?;
+    let policy = SizePolicyOptions {
+        on_larger: cli.on_larger,
+        min_savings_percent: cli.min_savings,
+    };
+    let isolation = cli.isolate.then(|| IsolationOptions {
+        timeout_seconds: (cli.timeout > 0).then_some(cli.timeout),
+    });
+
+    let reports: Vec<Report> = inputs
+        .iter()
+        .map(|input| {
+            let result = convert_input(
+                input,
+                &output,
+                &options,
+                &policy,
+                isolation.as_ref(),
+                cli.overwrite,
+            );
+            if let Err(e) = &result {
+                eprintln!("Failed: {}: {}", input.name(), e);
+            }
+            Report {
+                input: input.name(),
+                error: result.as_ref().err().map(|e| ReportError {
+                    kind: Failure::from_error(e),
+                    code: e.code(),
+                    message: e.to_string(),
+                }),
+                result: result.ok(),
+            }
+        })
+        .collect();
+
+    // 画像を標準出力に書き出した場合、JSON は標準エラー出力に回す
+    let json = serde_json::to_string_pretty(&reports).map_err(|e| e.to_string())?;
+    let image_on_stdout = match output {
+        Output::Stdout => true,
+        Output::Beside => inputs.iter().any(|input| matches!(input, Input::Stdin)),
+        _ => false,
+    };
+    if image_on_stdout {
+        eprintln!("{}", json);
+    } else {
+        println!("{}", json);
+    }
+    Ok(exit_code(&reports))
+}
+
+/// 全て成功なら 0、一部が失敗なら EXIT_PARTIAL、全て同じ理由で失敗ならその理由の終了コード
+fn exit_code(reports: &[Report]) -> u8 {
+    let failures: Vec<Failure> = reports
+        .iter()
+        .filter_map(|r| r.error.as_ref().map(|e| e.kind))
+        .collect();
+    match failures.first() {
+        None => 0,
+        Some(_) if failures.len() < reports.len() => EXIT_PARTIAL,
+        Some(&first) if failures.iter().all(|&f| f == first) => first.exit_code(),
+        Some(_) => EXIT_PARTIAL,
+    }
+}
+
+/// コマンドライン引数から EncodeOptions を組み立てる
+fn build_options(cli: &Cli) -> Result<EncodeOptions, String> {
+    let mut options: EncodeOptions = match &cli.options {
+        Some(arg) => {
+            let json = match arg.strip_prefix('@') {
+                Some(path) => fs::read_to_string(path)
+                    .map_err(|e| format!("Failed to read {}: {}", path, e))?,
+                None => arg.clone(),
+            };
+            serde_json::from_str(&json).map_err(|e| format!("Invalid options: {}", e))?
+        }
+        None => EncodeOptions::default(),
+    };
+
+    // 出力形式: --format → JSON のオプション → 出力ファイルの拡張子 → WebP
+    let format = cli
+        .format
+        .or(options.format())
+        .or_else(|| cli.output.as_deref().and_then(format_from_extension))
+        .unwrap_or(OutputFormat::Webp);
+    match format {
+        OutputFormat::Avif => {
+            let avif = options.avif.get_or_insert_default();
+            if let Some(speed) = cli.speed {
+                avif.speed = speed;
+            }
+        }
+        OutputFormat::Webp => {
+            let webp = options.webp.get_or_insert_default();
+            webp.lossless |= cli.lossless;
+        }
+        OutputFormat::Jpeg => {
+            options.jpeg.get_or_insert_with(JpegOptions::default);
+        }
+        OutputFormat::Png => {
+            options.png.get_or_insert_with(PngOptions::default);
+        }
+    }
+    let mut options = options
+        .select(format)
+        .ok_or(format!("Missing {} options", format.extension()))?;
+    if let Some(quality) = cli.quality {
+        options = options.with_quality(quality);
+    }
+
+    if cli.width.is_some()
+        || cli.height.is_some()
+        || cli.max_width.is_some()
+        || cli.max_height.is_some()
+    {
+        let transform = options.transform.get_or_insert_with(Default::default);
+        transform.width = cli.width.or(transform.width);
+        transform.height = cli.height.or(transform.height);
+        transform.max_width = cli.max_width.or(transform.max_width);
+        transform.max_height = cli.max_height.or(transform.max_height);
+    }
+    if let Some(max_bytes) = cli.max_bytes {
+        options.target_size = Some(TargetSizeOptions {
+            max_bytes,
+            ..options.target_size.unwrap_or_default()
+        });
+    }
+    Ok(options)
+}
+
+/// 出力ファイルの拡張子から出力形式を推測する
+fn format_from_extension(path: &str) -> Option<OutputFormat> {
+    let extension = Path::new(path).extension()?.to_str()?.to_ascii_lowercase();
+    match extension.as_str() {
+        "avif" => Some(OutputFormat::Avif),
+        "webp" => Some(OutputFormat::Webp),
+        "jpg" | "jpeg" => Some(OutputFormat::Jpeg),
+        "png" => Some(OutputFormat::Png),
+        _ => None,
+    }
+}
+
+/// serde の名前で列挙型を解析する (clap の value_parser 用)
+fn parse_enum<T: DeserializeOwned>(value: &str) -> Result<T, String> {
+    serde_json::from_value(serde_json::Value::String(value.to_string())).map_err(|e| e.to_string())
+}
+
+/// 引数のファイル・ディレクトリ・glob パターンを入力の一覧に展開する
+fn collect_inputs(args: &[String], recursive: bool) -> Result<Vec<Input>, String> {
+    let mut inputs = Vec::new();
+    for arg in args {
+        let path = Path::new(arg);
+        if arg == STDIO {
+            inputs.push(Input::Stdin);
+        } else if path.is_dir() {
+            walk_dir(path, recursive, &mut inputs)
+                .map_err(|e| format!("Failed to read {}: {}", arg, e))?;
+        } else if path.is_file() {
+            inputs.push(Input::File(path.to_path_buf()));
+        } else if arg.contains(['*', '?', '[']) {
+            let paths = glob::glob(arg).map_err(|e| format!("Invalid pattern {}: {}", arg, e))?;
+            inputs.extend(
+                paths
+                    .filter_map(Result::ok)
+                    .filter(|p| p.is_file())
+                    .map(Input::File),
+            );
+        } else {
+            return Err(format!("No such file or directory: {}", arg));
+        }
+    }
+    Ok(inputs)
+}
+
+/// ディレクトリ内の対応する拡張子のファイルを名前順に追加する
+fn walk_dir(dir: &Path, recursive: bool, inputs: &mut Vec<Input>) -> io::Result<()> {
+    let mut entries: Vec<PathBuf> = fs::read_dir(dir)?
+        .map(|entry| entry.map(|e| e.path()))
+        .collect::<io::Result<_>>()?;
+    entries.sort();
+    for path in entries {
+        if path.is_dir() {
+            if recursive {
+                walk_dir(&path, recursive, inputs)?;
+            }
+        } else if path
+            .extension()
+            .and_then(|e| e.to_str())
+            .is_some_and(|e| codec::registry().can_decode_extension(e))
+        {
+            inputs.push(Input::File(path));
+        }
+    }
+    Ok(())
+}
+
+/// 出力先を決める (複数の入力がある場合はディレクトリとして扱う)
+fn resolve_output(output: Option<&str>, input_count: usize) -> Result<Output, String> {
+    let Some(output) = output else {
+        return Ok(Output::Beside);
+    };
+    if output == STDIO {
+        if input_count > 1 {
+            return Err("Only one input can be written to stdout".to_string());
+        }
+        return Ok(Output::Stdout);
+    }
+    let path = PathBuf::from(output);
+    if path.is_dir() || input_count > 1 || output.ends_with(['/', '\\']) {
+        fs::create_dir_all(&path).map_err(|e| format!("Failed to create {}: {}", output, e))?;
+        Ok(Output::Dir(path))
+    } else {
+        Ok(Output::File(path))
+    }
+}
+
+/// 1 つの入力を変換して出力する
+fn convert_input(
+    input: &Input,
+    output: &Output,
+    options: &EncodeOptions,
+    policy: &SizePolicyOptions,
+    isolation: Option<&IsolationOptions>,
+    overwrite: bool,
+) -> Result<FileConvertResult, AppError> {
+    let extension = options.format().map_or("webp", |f| f.extension());
+    let (input_path, output_path) = match (input, output) {
+        (Input::Stdin, Output::Stdout | Output::Beside) => {
+            return convert_stdio(None, options, isolation);
+        }
+        (Input::File(path), Output::Stdout) => {
+            return convert_stdio(Some(path), options, isolation);
+        }
+        (Input::Stdin, Output::Dir(_)) => {
+            return Err(AppError::Io(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "Reading from stdin needs an output file or -",
+            )));
+        }
+        (Input::Stdin, Output::File(out)) => {
+            check_overwrite(out, overwrite)?;
+            let mut data = Vec::new();
+            io::stdin().read_to_end(&mut data)?;
+            let result = convert_data(&data, options, isolation)?;
+            fs::write(out, &result.data).map_err(|e| AppError::file("write", out, e))?;
+            return Ok(stdio_result(&data, &result, out.to_string_lossy().as_ref()));
+        }
+        (Input::File(path), Output::File(out)) => (path, out.clone()),
+        (Input::File(path), Output::Dir(dir)) => {
+            let stem = path
+                .file_stem()
+                .unwrap_or(path.as_os_str())
+                .to_string_lossy();
+            (path, dir.join(format!("{}.{}", stem, extension)))
+        }
+        (Input::File(path), Output::Beside) => (path, path.with_extension(extension)),
+    };
+    check_overwrite(&output_path, overwrite)?;
+    let (input_path, output_path) = (input_path.to_string_lossy(), output_path.to_string_lossy());
+    match isolation {
+        Some(isolation) => isolation::convert_file(
+            &input_path,
+            &output_path,
+            options.clone(),
+            policy,
+            isolation,
+        ),
+        None => convert_file(&input_path, &output_path, options.clone(), policy),
+    }
+}
+
+/// バイト列を変換する (分離モードの場合は子プロセスで)
+fn convert_data(
+    data: &[u8],
+    options: &EncodeOptions,
+    isolation: Option<&IsolationOptions>,
+) -> Result<ConvertResult, AppError> {
+    match isolation {
+        Some(isolation) => isolation::convert_with_report(data, options.clone(), isolation),
+        None => convert_with_report(data, options.clone()),
+    }
+}
+
+/// 上書きが許可されていない場合、既存の出力ファイルをエラーにする
+fn check_overwrite(path: &Path, overwrite: bool) -> Result<(), AppError> {
+    if path.exists() && !overwrite {
+        return Err(AppError::Io(io::Error::new(
+            io::ErrorKind::AlreadyExists,
+            format!(
+                "Output already exists: {} (use --overwrite)",
+                path.display()
+            ),
+        )));
+    }
+    Ok(())
+}
+
+/// 標準入力またはファイルから読み込み、変換結果を標準出力に書き出す
+fn convert_stdio(
+    path: Option<&Path>,
+    options: &EncodeOptions,
+    isolation: Option<&IsolationOptions>,
+) -> Result<FileConvertResult, AppError> {
+    let data = match path {
+        Some(path) => fs::read(path).map_err(|e| AppError::file("read", path, e))?,
+        None => {
+            let mut data = Vec::new();
+            io::stdin().read_to_end(&mut data)?;
+            data
+        }
+    };
+    let result = convert_data(&data, options, isolation)?;
+    let mut stdout = io::stdout().lock();
+    stdout.write_all(&result.data)?;
+    stdout.flush()?;
+    let mut report = stdio_result(&data, &result, STDIO);
+    if let Some(path) = path {
+        report.input_path = path.to_string_lossy().to_string();
+    }
+    Ok(report)
+}
+
+/// 標準入出力を使った変換の結果 (サイズのポリシーは適用しない)
+fn stdio_result(input: &[u8], result: &ConvertResult, output_path: &str) -> FileConvertResult {
+    let (input_size, converted_size) = (input.len() as u64, result.data.len() as u64);
+    FileConvertResult {
+        input_path: STDIO.to_string(),
+        output_path: Some(output_path.to_string()),
+        outcome: FileOutcome::Written,
+        input_size,
+        converted_size,
+        savings_percent: if input_size == 0 {
+            0.0
+        } else {
+            (1.0 - converted_size as f64 / input_size as f64) * 100.0
+        },
+        chosen: result.chosen,
+    }
+}
@@ -0,0 +1,31 @@
+use crate::error::AppError;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static CANCELLED: AtomicBool = AtomicBool::new(false);
+
+/// 一括変換を始める (前回の一括変換の中止の要求を取り消す)
+pub fn start_batch() {
+    CANCELLED.store(false, Ordering::SeqCst);
+}
+
+/// 一括変換を中止する
+/// # 注意
+/// - 以降の `convert_file` は、`start_batch` が呼ばれるまで `AppError::Cancelled` を返します。
+/// - 変換中のファイルは、書き込む前に中止します (分離モードの場合はワーカープロセスを終了させます)。
+/// - すべてのスレッドの変換に適用されます。
+pub fn cancel_batch() {
+    CANCELLED.store(true, Ordering::SeqCst);
+}
+
+/// 一括変換の中止が要求されているか
+pub fn is_cancelled() -> bool {
+    CANCELLED.load(Ordering::SeqCst)
+}
+
+/// 中止が要求されている場合は `AppError::Cancelled` を返す
+pub(crate) fn check_cancelled() -> Result<(), AppError> {
+    if is_cancelled() {
+        return Err(AppError::Cancelled);
+    }
+    Ok(())
+}
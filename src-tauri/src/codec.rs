@@ -0,0 +1,404 @@
+use crate::decoder::{
+    AvifDecoder, CmykJpegDecoder, ColorProfile, ExrDecoder, HeicDecoder, Jpeg2000Decoder,
+    StandardDecoder,
+};
+use crate::encoder::{AvifEncoder, JpegEncoder, Metadata, PngEncoder, WebpEncoder};
+use crate::error::AppError;
+use crate::header::exif_thumbnail;
+use crate::limits::image_limits;
+use crate::options::{EncodeOptions, ImageInfo, OutputFormat};
+use image::{DynamicImage, ImageFormat, ImageReader};
+use serde::Serialize;
+use std::io::Cursor;
+use std::sync::OnceLock;
+
+/// 入力形式のデコーダー
+/// 形式の判別 (sniff)・デコード・ヘッダーのみの読み取り (probe) を行う
+pub trait Decoder: Send + Sync {
+    /// 形式の名前 ("jpeg", "png", "heic" など)
+    fn name(&self) -> &str;
+
+    /// この形式のファイルの拡張子 (ドット無し)
+    fn extensions(&self) -> &[&str];
+
+    /// マジックナンバーから、このデコーダーで扱える形式かを判定する
+    fn sniff(&self, bytes: &[u8]) -> bool;
+
+    /// デコードできるか (cargo の feature で無効化されている場合は false)
+    /// 無効な場合も判別は行い、`decode` は `AppError::UnsupportedFormat` を返す
+    fn enabled(&self) -> bool {
+        true
+    }
+
+    /// 画像全体をデコードする
+    fn decode(&self, bytes: &[u8]) -> Result<DynamicImage, AppError>;
+
+    /// 画像全体をデコードせずに、ヘッダーから形式・サイズ・色の種類などを読み取る
+    /// ファイルサイズは `decoder::probe` が設定し、ICC の説明と EXIF は未設定なら `color_profile` / `exif` から補う
+    fn probe(&self, bytes: &[u8]) -> Result<ImageInfo, AppError>;
+
+    /// 埋め込まれた色情報 (見つからない場合は sRGB とみなす)
+    fn color_profile(&self, _bytes: &[u8]) -> Option<ColorProfile> {
+        None
+    }
+
+    /// 埋め込まれた EXIF (TIFF 形式の生データ)
+    fn exif(&self, _bytes: &[u8]) -> Option<Vec<u8>> {
+        None
+    }
+
+    /// 埋め込まれたサムネイル (プレビューの高速化に使う)
+    /// 既定では EXIF の IFD1 に格納された JPEG をデコードする (Orientation は適用しない)
+    fn thumbnail(&self, bytes: &[u8]) -> Option<DynamicImage> {
+        let exif = self.exif(bytes)?;
+        let jpeg = exif_thumbnail(&exif)?;
+        let mut reader = ImageReader::with_format(Cursor::new(jpeg), ImageFormat::Jpeg);
+        reader.limits(image_limits());
+        reader.decode().ok()
+    }
+}
+
+/// 出力形式のエンコーダー
+/// `EncodeOptions` のうち自身の形式のオプションを読み取り、エンコードを行う
+/// # 注意
+/// - 出力形式は閉じた列挙型 (`OutputFormat`) で、オプションは `EncodeOptions` の形式ごとのフィールドに持ちます。
+/// - そのため、このトレイトを実装するだけでは新しい出力形式は追加できません。
+///   `OutputFormat` の値、`EncodeOptions` のフィールドとそのオプション構造体を追加した上で、
+///   `CodecRegistry` に登録する必要があります (フロントエンドの型も合わせて更新します)。
+/// - 既存の形式のエンコーダーを差し替える場合は、このトレイトの実装を登録するだけで済みます。
+pub trait Encoder: Send + Sync {
+    /// 出力形式
+    fn format(&self) -> OutputFormat;
+
+    /// エンコードできるか (cargo の feature で無効化されている場合は false)
+    /// 無効な場合、`encode` は `AppError::UnsupportedFormat` を返す
+    fn enabled(&self) -> bool {
+        true
+    }
+
+    /// この形式のオプションの一覧 (フロントエンドで設定画面を組み立てるため)
+    fn options_schema(&self) -> Vec<OptionField>;
+
+    /// `options` にこの形式のオプションが指定されているか
+    fn is_selected(&self, options: &EncodeOptions) -> bool;
+
+    /// `options` からこの形式のオプションを取り除く
+    fn deselect(&self, options: &mut EncodeOptions);
+
+    /// 非可逆圧縮の品質 (品質を持たない形式やロスレスの場合は None)
+    fn quality(&self, _options: &EncodeOptions) -> Option<f32> {
+        None
+    }
+
+    /// 品質を変更する (品質を持たない形式では何もしない)
+    fn set_quality(&self, _options: &mut EncodeOptions, _quality: f32) {}
+
+    /// ICC プロファイルの埋め込みに対応しているか
+    fn supports_icc_profile(&self, _options: &EncodeOptions) -> bool {
+        false
+    }
+
+    /// 画像をエンコードする
+    fn encode(
+        &self,
+        img: &DynamicImage,
+        options: &EncodeOptions,
+        metadata: &Metadata,
+    ) -> Result<Vec<u8>, AppError>;
+}
+
+/// エンコードオプションの項目
+/// key: オプションのキー (camelCase、例: `{ webp: { quality } }` の "quality")
+/// kind: 値の種類と範囲
+/// default: 既定値
+#[derive(Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct OptionField {
+    pub key: &'static str,
+    #[serde(flatten)]
+    pub kind: OptionKind,
+    pub default: serde_json::Value,
+}
+
+impl OptionField {
+    /// 既定値を持たない項目を作る (`with_defaults` で既定値を埋める)
+    pub fn new(key: &'static str, kind: OptionKind) -> Self {
+        Self {
+            key,
+            kind,
+            default: serde_json::Value::Null,
+        }
+    }
+}
+
+/// オプションの値の種類
+/// Number: 数値 (min-max、step 刻み)
+/// Boolean: 真偽値
+/// Choice: 列挙値 (values のいずれか)
+/// Group: 省略可能な入れ子のオプション (None の場合は null)
+#[derive(Serialize, Debug, Clone)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum OptionKind {
+    Number { min: f64, max: f64, step: f64 },
+    Boolean,
+    Choice { values: Vec<&'static str> },
+    Group { fields: Vec<OptionField> },
+}
+
+/// `defaults` をシリアライズした値から、各項目の既定値を埋める
+pub fn with_defaults<T: Serialize>(mut fields: Vec<OptionField>, defaults: &T) -> Vec<OptionField> {
+    let defaults = serde_json::to_value(defaults).unwrap_or_default();
+    for field in &mut fields {
+        field.default = defaults
+            .get(field.key)
+            .cloned()
+            .unwrap_or(serde_json::Value::Null);
+    }
+    fields
+}
+
+/// 利用可能なデコーダーとエンコーダーの一覧
+#[derive(Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct CodecList {
+    pub decoders: Vec<DecoderInfo>,
+    pub encoders: Vec<EncoderInfo>,
+}
+
+/// デコーダーの情報
+/// name: 形式の名前
+/// extensions: 拡張子の一覧
+/// enabled: デコードできるか (feature で無効化されている場合は false)
+#[derive(Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct DecoderInfo {
+    pub name: String,
+    pub extensions: Vec<String>,
+    pub enabled: bool,
+}
+
+/// エンコーダーの情報
+/// format: 出力形式 (`EncodeOptions` のキーと同じ)
+/// extension: 出力ファイルの拡張子
+/// mime_type: MIME タイプ
+/// options: オプションの一覧
+/// enabled: エンコードできるか (feature で無効化されている場合は false)
+#[derive(Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct EncoderInfo {
+    pub format: OutputFormat,
+    pub extension: &'static str,
+    pub mime_type: &'static str,
+    pub options: Vec<OptionField>,
+    pub enabled: bool,
+}
+
+/// デコーダーとエンコーダーの登録先
+/// デコーダーは登録順に `sniff` を試し、最初に一致したものを使う
+/// エンコーダーは登録順が `EncodeOptions` で複数の形式が指定された場合の優先順位になる
+#[derive(Default)]
+pub struct CodecRegistry {
+    decoders: Vec<Box<dyn Decoder>>,
+    encoders: Vec<Box<dyn Encoder>>,
+}
+
+impl CodecRegistry {
+    /// 組み込みのデコーダーとエンコーダーを登録したレジストリを作る
+    /// 形式の追加・削除・並べ替えはここで行う
+    pub fn builtin() -> Self {
+        let mut registry = Self::default();
+        registry.register_decoder(HeicDecoder);
+        registry.register_decoder(AvifDecoder);
+        registry.register_decoder(ExrDecoder);
+        registry.register_decoder(Jpeg2000Decoder);
+        // CMYK の JPEG は通常の JPEG より先に判定する
+        registry.register_decoder(CmykJpegDecoder);
+        for decoder in StandardDecoder::all() {
+            registry.register_decoder(decoder);
+        }
+
+        registry.register_encoder(AvifEncoder);
+        registry.register_encoder(WebpEncoder);
+        registry.register_encoder(JpegEncoder);
+        registry.register_encoder(PngEncoder);
+        registry
+    }
+
+    /// デコーダーを末尾に登録する
+    pub fn register_decoder(&mut self, decoder: impl Decoder + 'static) {
+        self.decoders.push(Box::new(decoder));
+    }
+
+    /// エンコーダーを末尾に登録する
+    pub fn register_encoder(&mut self, encoder: impl Encoder + 'static) {
+        self.encoders.push(Box::new(encoder));
+    }
+
+    /// バイトデータを扱えるデコーダーを返す
+    pub fn decoder_for(&self, bytes: &[u8]) -> Option<&dyn Decoder> {
+        self.decoders
+            .iter()
+            .find(|decoder| decoder.sniff(bytes))
+            .map(|decoder| decoder.as_ref())
+    }
+
+    /// 指定した出力形式をデコードできるか (有効なデコーダーの拡張子に出力形式の拡張子が含まれるか)
+    /// エンコード結果をデコードして画質を評価する処理で、エンコードの前に確かめるために使う
+    pub fn can_decode(&self, format: OutputFormat) -> bool {
+        self.decoders
+            .iter()
+            .any(|decoder| decoder.enabled() && decoder.extensions().contains(&format.extension()))
+    }
+
+    /// 拡張子 (大文字小文字は区別しない) のファイルを有効なデコーダーで読み込めるか
+    /// ディレクトリから変換対象のファイルを拾う処理で使う
+    pub fn can_decode_extension(&self, extension: &str) -> bool {
+        self.decoders.iter().any(|decoder| {
+            decoder.enabled()
+                && decoder
+                    .extensions()
+                    .iter()
+                    .any(|e| e.eq_ignore_ascii_case(extension))
+        })
+    }
+
+    /// `options` で指定された形式のうち、最も優先順位の高いエンコーダーを返す
+    pub fn encoder_for(&self, options: &EncodeOptions) -> Option<&dyn Encoder> {
+        self.encoders
+            .iter()
+            .find(|encoder| encoder.is_selected(options))
+            .map(|encoder| encoder.as_ref())
+    }
+
+    /// 指定した出力形式のエンコーダーを返す
+    pub fn encoder(&self, format: OutputFormat) -> Option<&dyn Encoder> {
+        self.encoders
+            .iter()
+            .find(|encoder| encoder.format() == format)
+            .map(|encoder| encoder.as_ref())
+    }
+
+    /// 登録されているエンコーダーの一覧
+    pub fn encoders(&self) -> impl Iterator<Item = &dyn Encoder> {
+        self.encoders.iter().map(|encoder| encoder.as_ref())
+    }
+
+    /// フロントエンドに渡す形式とオプションの一覧
+    /// 同じ形式を扱うデコーダーが複数ある場合 (CMYK の JPEG など) は 1 つにまとめる
+    pub fn describe(&self) -> CodecList {
+        let mut decoders: Vec<DecoderInfo> = Vec::new();
+        for decoder in &self.decoders {
+            if decoders.iter().any(|info| info.name == decoder.name()) {
+                continue;
+            }
+            decoders.push(DecoderInfo {
+                name: decoder.name().to_string(),
+                extensions: decoder.extensions().iter().map(|e| e.to_string()).collect(),
+                enabled: decoder.enabled(),
+            });
+        }
+        CodecList {
+            decoders,
+            encoders: self
+                .encoders
+                .iter()
+                .map(|encoder| EncoderInfo {
+                    format: encoder.format(),
+                    extension: encoder.format().extension(),
+                    mime_type: encoder.format().mime_type(),
+                    options: encoder.options_schema(),
+                    enabled: encoder.enabled(),
+                })
+                .collect(),
+        }
+    }
+}
+
+static REGISTRY: OnceLock<CodecRegistry> = OnceLock::new();
+
+/// 変換処理で使われるレジストリ (未設定の場合は組み込みのもの)
+pub fn registry() -> &'static CodecRegistry {
+    REGISTRY.get_or_init(CodecRegistry::builtin)
+}
+
+/// 変換処理で使うレジストリを差し替える
+/// # 引数
+/// - `registry`: 使用するレジストリ (`CodecRegistry::builtin()` に追加したものなど)
+/// # 戻り値
+/// - 既にレジストリが使われている (または設定済みの) 場合は、渡したレジストリを `Err` で返します。
+/// # 注意
+/// - 変換処理を始める前に一度だけ呼び出してください。
+pub fn set_registry(registry: CodecRegistry) -> Result<(), CodecRegistry> {
+    REGISTRY.set(registry)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::decoder::decode;
+    use image::RgbImage;
+
+    /// 指定した形式のオプションのみを既定値で指定する
+    fn options_for(format: OutputFormat) -> EncodeOptions {
+        let mut options = EncodeOptions::default();
+        match format {
+            OutputFormat::Avif => options.avif = Some(Default::default()),
+            OutputFormat::Webp => options.webp = Some(Default::default()),
+            OutputFormat::Jpeg => options.jpeg = Some(Default::default()),
+            OutputFormat::Png => options.png = Some(Default::default()),
+        }
+        options
+    }
+
+    #[test]
+    fn avif_is_decodable_with_heif() {
+        let registry = CodecRegistry::builtin();
+        assert_eq!(
+            registry.can_decode(OutputFormat::Avif),
+            cfg!(feature = "heif")
+        );
+        for format in [OutputFormat::Webp, OutputFormat::Jpeg, OutputFormat::Png] {
+            assert!(registry.can_decode(format), "{:?}", format);
+        }
+    }
+
+    #[test]
+    fn decodable_extensions_follow_the_decoders() {
+        let registry = CodecRegistry::builtin();
+        for extension in ["png", "JPG", "jpeg", "Tiff", "webp"] {
+            assert!(registry.can_decode_extension(extension), "{}", extension);
+        }
+        assert!(!registry.can_decode_extension("txt"));
+        assert!(!registry.can_decode_extension(""));
+        assert_eq!(
+            registry.can_decode_extension("heic"),
+            cfg!(feature = "heif")
+        );
+    }
+
+    #[test]
+    fn encoders_round_trip_through_decode() {
+        let img = DynamicImage::ImageRgb8(RgbImage::from_fn(32, 24, |x, y| {
+            image::Rgb([(x * 8) as u8, (y * 10) as u8, 128])
+        }));
+        let registry = CodecRegistry::builtin();
+        for encoder in registry.encoders().filter(|encoder| encoder.enabled()) {
+            let format = encoder.format();
+            let options = options_for(format);
+            assert!(encoder.is_selected(&options), "{:?}", format);
+            let data = encoder
+                .encode(&img, &options, &Metadata::default())
+                .unwrap();
+            if !registry.can_decode(format) {
+                continue;
+            }
+            let decoded = decode(&data).unwrap_or_else(|e| panic!("{:?}: {}", format, e));
+            assert_eq!(
+                (decoded.width(), decoded.height()),
+                (32, 24),
+                "{:?}",
+                format
+            );
+        }
+    }
+}
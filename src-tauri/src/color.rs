@@ -0,0 +1,319 @@
+use crate::decoder::ColorProfile;
+use crate::error::AppError;
+use crate::options::{ColorMode, ColorOptions};
+use image::{DynamicImage, RgbImage, RgbaImage};
+use qcms::{CIE_xyY, CIE_xyYTRIPLE, DataType, Profile, Transform};
+
+/// カラーマネジメントを適用する
+/// # 引数
+/// - `img`: デコード済みの画像
+/// - `profile`: 入力画像の色情報 (None の場合は sRGB とみなす)
+/// - `options`: カラーマネジメントのオプション
+/// - `can_embed`: 出力形式が ICC プロファイルの埋め込みに対応しているか
+/// # 戻り値
+/// - 変換後の画像と、出力に埋め込むべき ICC プロファイルを返します。
+/// # 注意
+/// - 埋め込めない場合 (nclx のみ、または出力形式が非対応) は Preserve でも sRGB に変換します。
+/// - RGB/グレースケール以外のプロファイル (CMYK はデコーダーで変換済み) は無視します。
+/// - 壊れた ICC プロファイルや、変換を作成できないプロファイルは警告を出して sRGB とみなします (埋め込みもしません)。
+pub fn apply_color_management(
+    img: DynamicImage,
+    profile: Option<ColorProfile>,
+    options: &ColorOptions,
+    can_embed: bool,
+) -> Result<(DynamicImage, Option<Vec<u8>>), AppError> {
+    let Some(profile) = profile else {
+        return Ok((img, None));
+    };
+
+    if options.mode == ColorMode::Preserve {
+        if let ColorProfile::Icc(icc) = &profile
+            && can_embed
+        {
+            if Profile::new_from_slice(icc, false).is_none() {
+                eprintln!("Color: Warning: Invalid ICC profile, treating the image as sRGB");
+                return Ok((img, None));
+            }
+            eprintln!("Color: Preserving embedded ICC profile...");
+            return Ok((img, Some(icc.clone())));
+        }
+        eprintln!("Color: Profile cannot be embedded in the output, converting to sRGB...");
+    }
+
+    Ok((convert_to_srgb(img, &profile, options), None))
+}
+
+/// 埋め込みプロファイルの色空間から sRGB に変換する
+/// 変換できない場合は、画素をそのまま (sRGB とみなして) 返す
+fn convert_to_srgb(
+    img: DynamicImage,
+    profile: &ColorProfile,
+    options: &ColorOptions,
+) -> DynamicImage {
+    let source = match profile {
+        ColorProfile::Icc(icc) => {
+            // ICC ヘッダーの 16〜20 バイト目がデータ色空間のシグネチャ
+            let is_gray = match icc.get(16..20) {
+                Some(b"RGB ") => false,
+                Some(b"GRAY") => true,
+                _ => return img,
+            };
+            // グレースケールのプロファイルを RGB 画像に適用することはできない
+            let is_gray_image = matches!(
+                img,
+                DynamicImage::ImageLuma8(_) | DynamicImage::ImageLumaA8(_)
+            );
+            if is_gray != is_gray_image {
+                return img;
+            }
+            match Profile::new_from_slice(icc, false) {
+                Some(profile) => profile,
+                None => {
+                    eprintln!("Color: Warning: Invalid ICC profile, treating the image as sRGB");
+                    return img;
+                }
+            }
+        }
+        ColorProfile::Nclx {
+            color_primaries,
+            transfer_characteristics,
+        } => match nclx_profile(*color_primaries, *transfer_characteristics) {
+            Some(profile) => profile,
+            // sRGB または未対応の組み合わせは変換しない
+            None => return img,
+        },
+    };
+    if source.is_sRGB() {
+        return img;
+    }
+
+    eprintln!("Color: Converting to sRGB...");
+    let mut srgb = Profile::new_sRGB();
+    srgb.precache_output_transform();
+    // RGBA8 およびその他の形式 (16bit, float など) は RGBA8 で変換する
+    let (src_ty, dst_ty) = match &img {
+        DynamicImage::ImageRgb8(_) => (DataType::RGB8, DataType::RGB8),
+        DynamicImage::ImageLuma8(_) => (DataType::Gray8, DataType::RGB8),
+        DynamicImage::ImageLumaA8(_) => (DataType::GrayA8, DataType::RGBA8),
+        _ => (DataType::RGBA8, DataType::RGBA8),
+    };
+    let Some(transform) =
+        Transform::new_to(&source, &srgb, src_ty, dst_ty, options.intent.to_qcms())
+    else {
+        eprintln!("Color: Warning: Failed to create color transform, treating the image as sRGB");
+        return img;
+    };
+
+    let (width, height) = (img.width(), img.height());
+    match img {
+        DynamicImage::ImageRgb8(mut buf) => {
+            transform.apply(&mut buf);
+            DynamicImage::ImageRgb8(buf)
+        }
+        DynamicImage::ImageLuma8(buf) => {
+            let mut out = RgbImage::new(width, height);
+            transform.convert(&buf, &mut out);
+            DynamicImage::ImageRgb8(out)
+        }
+        DynamicImage::ImageLumaA8(buf) => {
+            let mut out = RgbaImage::new(width, height);
+            transform.convert(&buf, &mut out);
+            DynamicImage::ImageRgba8(out)
+        }
+        other => {
+            let mut buf = other.into_rgba8();
+            transform.apply(&mut buf);
+            DynamicImage::ImageRgba8(buf)
+        }
+    }
+}
+
+/// nclx (CICP) の色域・伝達特性から RGB プロファイルを作成する
+/// 対応していない組み合わせや sRGB と同等の場合は None を返す
+fn nclx_profile(color_primaries: u8, transfer_characteristics: u8) -> Option<Box<Profile>> {
+    let xy = |x: f64, y: f64| CIE_xyY { x, y, Y: 1.0 };
+    let d65 = xy(0.3127, 0.3290);
+    // Rec. ITU-T H.273 Table 2
+    let (white_point, primaries) = match color_primaries {
+        // BT.709 (sRGB)
+        1 => (
+            d65,
+            CIE_xyYTRIPLE {
+                red: xy(0.640, 0.330),
+                green: xy(0.300, 0.600),
+                blue: xy(0.150, 0.060),
+            },
+        ),
+        // BT.2020
+        9 => (
+            d65,
+            CIE_xyYTRIPLE {
+                red: xy(0.708, 0.292),
+                green: xy(0.170, 0.797),
+                blue: xy(0.131, 0.046),
+            },
+        ),
+        // DCI-P3 (SMPTE RP 431-2) / Display P3 (SMPTE EG 432-1)
+        11 | 12 => (
+            if color_primaries == 11 {
+                xy(0.314, 0.351)
+            } else {
+                d65
+            },
+            CIE_xyYTRIPLE {
+                red: xy(0.680, 0.320),
+                green: xy(0.265, 0.690),
+                blue: xy(0.150, 0.060),
+            },
+        ),
+        _ => return None,
+    };
+    // Rec. ITU-T H.273 Table 3 (符号値 → リニア)
+    let eotf: fn(f64) -> f64 = match transfer_characteristics {
+        // sRGB
+        13 if color_primaries == 1 => return None,
+        13 => |v| {
+            if v <= 0.04045 {
+                v / 12.92
+            } else {
+                ((v + 0.055) / 1.055).powf(2.4)
+            }
+        },
+        // BT.709 / BT.601 / BT.2020
+        1 | 6 | 14 | 15 => |v| {
+            if v < 0.081 {
+                v / 4.5
+            } else {
+                ((v + 0.099) / 1.099).powf(1.0 / 0.45)
+            }
+        },
+        // リニア
+        8 => |v| v,
+        _ => return None,
+    };
+    let table: Vec<u16> = (0..1024)
+        .map(|i| (eotf(i as f64 / 1023.0) * 65535.0).round() as u16)
+        .collect();
+    Profile::new_rgb_with_table(white_point, primaries, &table)
+}
+
+/// ICC プロファイルの説明 (desc タグ) を読み取る
+/// # 引数
+/// - `icc`: ICC プロファイルのバイト列
+/// # 戻り値
+/// - 説明が見つかった場合はその文字列を返します ("Display P3" など)。
+/// # 注意
+/// - v2 の textDescriptionType (ASCII) と v4 の multiLocalizedUnicodeType (最初のレコード) に対応します。
+pub fn icc_description(icc: &[u8]) -> Option<String> {
+    let read_u32 = |pos: usize| -> Option<usize> {
+        Some(u32::from_be_bytes(icc.get(pos..pos + 4)?.try_into().ok()?) as usize)
+    };
+
+    // ヘッダー (128 バイト) の後にタグテーブルが続く
+    let tag_count = read_u32(128)?;
+    let entry = (0..tag_count.min(1024))
+        .map(|i| 132 + i * 12)
+        .find(|&entry| icc.get(entry..entry + 4) == Some(b"desc"))?;
+    let (offset, size) = (read_u32(entry + 4)?, read_u32(entry + 8)?);
+    let data = icc.get(offset..offset.checked_add(size)?)?;
+
+    let text = match data.get(0..4)? {
+        b"desc" => {
+            let length = u32::from_be_bytes(data.get(8..12)?.try_into().ok()?) as usize;
+            let ascii = data.get(12..12 + length)?;
+            String::from_utf8_lossy(ascii)
+                .trim_end_matches('\0')
+                .to_string()
+        }
+        b"mluc" => {
+            let length = u32::from_be_bytes(data.get(20..24)?.try_into().ok()?) as usize;
+            let offset = u32::from_be_bytes(data.get(24..28)?.try_into().ok()?) as usize;
+            let utf16: Vec<u16> = data
+                .get(offset..offset + length)?
+                .chunks_exact(2)
+                .map(|pair| u16::from_be_bytes([pair[0], pair[1]]))
+                .collect();
+            String::from_utf16_lossy(&utf16)
+                .trim_end_matches('\0')
+                .to_string()
+        }
+        _ => return None,
+    };
+    let text = text.trim().to_string();
+    (!text.is_empty()).then_some(text)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::Rgb;
+
+    fn rgb(color: [u8; 3]) -> DynamicImage {
+        DynamicImage::ImageRgb8(RgbImage::from_pixel(4, 4, Rgb(color)))
+    }
+
+    /// ヘッダーの色空間だけが RGB の、壊れた ICC プロファイル
+    fn garbage_icc() -> Vec<u8> {
+        let mut icc = vec![0xAB; 200];
+        icc[16..20].copy_from_slice(b"RGB ");
+        icc
+    }
+
+    #[test]
+    fn invalid_icc_is_treated_as_srgb() {
+        let profile = || Some(ColorProfile::Icc(garbage_icc()));
+        for mode in [ColorMode::Srgb, ColorMode::Preserve] {
+            let options = ColorOptions {
+                mode,
+                ..Default::default()
+            };
+            for can_embed in [false, true] {
+                let (img, icc) =
+                    apply_color_management(rgb([200, 100, 50]), profile(), &options, can_embed)
+                        .unwrap();
+                assert_eq!(img, rgb([200, 100, 50]));
+                assert_eq!(icc, None);
+            }
+        }
+    }
+
+    #[test]
+    fn srgb_and_unknown_profiles_are_unchanged() {
+        let options = ColorOptions::default();
+        let unchanged = |profile| {
+            let (img, icc) =
+                apply_color_management(rgb([10, 20, 30]), profile, &options, true).unwrap();
+            img == rgb([10, 20, 30]) && icc.is_none()
+        };
+        assert!(unchanged(None));
+        // BT.709 + sRGB の伝達特性は sRGB
+        assert!(unchanged(Some(ColorProfile::Nclx {
+            color_primaries: 1,
+            transfer_characteristics: 13,
+        })));
+        // 未対応の色域
+        assert!(unchanged(Some(ColorProfile::Nclx {
+            color_primaries: 200,
+            transfer_characteristics: 13,
+        })));
+    }
+
+    #[test]
+    fn display_p3_is_converted() {
+        let profile = ColorProfile::Nclx {
+            color_primaries: 12,
+            transfer_characteristics: 13,
+        };
+        let (img, icc) = apply_color_management(
+            rgb([200, 100, 50]),
+            Some(profile),
+            &Default::default(),
+            true,
+        )
+        .unwrap();
+        assert_eq!(icc, None);
+        // Display P3 の色は sRGB ではより鮮やかな値になる
+        let [r, g, b] = img.to_rgb8().get_pixel(0, 0).0;
+        assert!(r > 200 && g < 100 && b < 50, "{:?}", (r, g, b));
+    }
+}
@@ -1,42 +1,267 @@
-use crate::decoder::decode;
-use crate::encoder::encode;
-use crate::options::EncodeOptions;
-use crate::options::PathInfo;
+use drop_compress_image_lib as pipeline;
+use drop_compress_image_lib::AppError;
+use drop_compress_image_lib::codec::{CodecList, registry};
+use drop_compress_image_lib::isolation;
+use drop_compress_image_lib::options::{
+    CompareOptions, CompareResult, ConvertResult, DecodeLimits, EncodeOptions, FileConvertResult,
+    ImageInfo, IsolationOptions, PathInfo, PreviewEncodeOptions, PreviewEncodeResult,
+    PreviewOptions, PreviewResult, SizePolicyOptions, VariantOutput, VariantPlan, VariantResult,
+};
+use std::fs;
 use std::path::Path;
+use tauri::Manager;
 
 /// Uint8Arrayバイナリデータを圧縮してUint8Arrayで返します。
 /// # 引数
 /// - `data`: 変換対象の画像データのバイト列
 /// - `options`: エンコードオプション
+/// - `isolation`: 指定した場合は子プロセスで変換する (分離モード)
 /// # 戻り値
 /// - 成功した場合は WebP のバイト列を `Vec<u8>` として返します。
-/// - 失敗した場合は `Box<dyn Error>` を返します。
+/// - 失敗した場合は `AppError` を返します。
 #[tauri::command]
-pub async fn convert(data: Vec<u8>, options: EncodeOptions) -> Result<Vec<u8>, String> {
+pub async fn convert(
+    data: Vec<u8>,
+    options: EncodeOptions,
+    isolation: Option<IsolationOptions>,
+) -> Result<Vec<u8>, AppError> {
     // spawn_blocking でUIをフリーズさせずに重い処理を実行
-    let converted_data = tauri::async_runtime::spawn_blocking(move || {
-        println!("Decoding...");
-        // 画像デコード
-        let img = decode(&data).map_err(|e| format!("Failed to decode image: {}", e))?;
-        println!("Encoding...");
-        // 画像エンコード
-        let data = encode(&img, options).map_err(|e| format!("Failed to encode image: {}", e))?;
-
-        Ok(data)
+    let converted_data = tauri::async_runtime::spawn_blocking(move || match isolation {
+        Some(isolation) => isolation::convert(&data, options, &isolation),
+        None => pipeline::convert(&data, options),
     })
     .await
-    .map_err(|e| e.to_string())?;
+    .map_err(|e| AppError::Task(e.to_string()))?;
     converted_data
 }
 
+/// 画像を変換し、出力サイズや使用した品質などの詳細とともに返します。
+/// # 引数
+/// - `data`: 変換対象の画像データのバイト列
+/// - `options`: エンコードオプション
+/// - `isolation`: 指定した場合は子プロセスで変換する (分離モード)
+/// # 戻り値
+/// - 成功した場合は `ConvertResult` を返します。
+/// - 失敗した場合は `AppError` を返します。
+/// # 注意
+/// - `options.target_size` を指定した場合は、選択された品質と試行回数が結果に含まれます。
+#[tauri::command]
+pub async fn convert_with_report(
+    data: Vec<u8>,
+    options: EncodeOptions,
+    isolation: Option<IsolationOptions>,
+) -> Result<ConvertResult, AppError> {
+    tauri::async_runtime::spawn_blocking(move || match isolation {
+        Some(isolation) => isolation::convert_with_report(&data, options, &isolation),
+        None => pipeline::convert_with_report(&data, options),
+    })
+    .await
+    .map_err(|e| AppError::Task(e.to_string()))?
+}
+
+/// 画像の一部 (または縮小したもの) を指定した設定でエンコードし、ファイルに書き込まずに結果を返します。
+/// # 引数
+/// - `data`: 変換対象の画像データのバイト列
+/// - `options`: エンコードオプション
+/// - `preview`: 切り抜く範囲と縮小のオプション (省略時は全体をそのままエンコード)
+/// # 戻り値
+/// - 成功した場合は、エンコードしたサイズ・全体の推定サイズ・エンコード時間と、比較表示用にデコードした RGBA の画素を返します。
+/// - 失敗した場合は `AppError` を返します。
+/// # 注意
+/// - 設定を決める前の確認用です。`target_size` は無視されます。
+#[tauri::command]
+pub async fn preview_encode(
+    data: Vec<u8>,
+    options: EncodeOptions,
+    preview: Option<PreviewEncodeOptions>,
+) -> Result<PreviewEncodeResult, AppError> {
+    tauri::async_runtime::spawn_blocking(move || {
+        pipeline::preview_encode(&data, options, &preview.unwrap_or_default())
+    })
+    .await
+    .map_err(|e| AppError::Task(e.to_string()))?
+}
+
+/// 画像ファイルを変換して保存します。変換結果が十分に小さくならない場合はポリシーに従います。
+/// # 引数
+/// - `input_path`: 変換対象の画像ファイルのパス
+/// - `output_path`: 保存先のファイルのパス
+/// - `options`: エンコードオプション
+/// - `policy`: サイズのポリシー (省略時はサイズに関わらず書き込む `WriteAnyway`)
+/// - `isolation`: 指定した場合は子プロセスで変換する (分離モード)
+/// # 戻り値
+/// - 成功した場合はファイルごとの結果 (書き込んだか、サイズ、削減率) を返します。
+/// - 失敗した場合は `AppError` を返します。
+/// # 注意
+/// - `KeepOriginal` の場合は、元のファイル名のまま出力先のディレクトリにコピーします (同じパスの場合はコピーしません)。
+/// - 分離モードで子プロセスがクラッシュ・タイムアウトした場合は、`worker_crashed` / `worker_timeout` のエラーを返します
+///   (アプリは終了しないため、一括変換は次のファイルに進めます)。
+#[tauri::command]
+pub async fn convert_file(
+    input_path: String,
+    output_path: String,
+    options: EncodeOptions,
+    policy: Option<SizePolicyOptions>,
+    isolation: Option<IsolationOptions>,
+) -> Result<FileConvertResult, AppError> {
+    tauri::async_runtime::spawn_blocking(move || {
+        let policy = policy.unwrap_or_default();
+        match isolation {
+            Some(isolation) => {
+                isolation::convert_file(&input_path, &output_path, options, &policy, &isolation)
+            }
+            None => pipeline::convert_file(&input_path, &output_path, options, &policy),
+        }
+    })
+    .await
+    .map_err(|e| AppError::Task(e.to_string()))?
+}
+
+/// 2 つの画像 (元画像と変換後の画像など) を比較し、画質の指標を返します。
+/// # 引数
+/// - `original`: 元の画像データのバイト列
+/// - `modified`: 比較対象の画像データのバイト列
+/// - `options`: 比較のオプション
+/// # 戻り値
+/// - 成功した場合は PSNR・SSIM・SSIMULACRA2・チャンネルごとの最大誤差と、指定されていれば差分画像を返します。
+/// - 失敗した場合は `AppError` を返します。
+#[tauri::command]
+pub async fn compare_images(
+    original: Vec<u8>,
+    modified: Vec<u8>,
+    options: Option<CompareOptions>,
+) -> Result<CompareResult, AppError> {
+    tauri::async_runtime::spawn_blocking(move || {
+        pipeline::compare_images(&original, &modified, options.unwrap_or_default())
+    })
+    .await
+    .map_err(|e| AppError::Task(e.to_string()))?
+}
+
+/// 画像ファイルから複数のサイズ・形式の画像 (レスポンシブ画像) を生成して保存します。
+/// # 引数
+/// - `path_str`: 変換対象の画像ファイルのパス
+/// - `output_dir`: 保存先のディレクトリ (省略時は入力ファイルと同じディレクトリ)
+/// - `plan`: 生成計画
+/// # 戻り値
+/// - 成功した場合は生成されたファイルの一覧と、指定されていればマニフェストを返します。
+/// - 失敗した場合は `AppError` を返します。
+/// # 注意
+/// - マニフェストを指定した場合は `{name}.json` を、HTML も指定した場合は `{name}.html` を同じディレクトリに保存します。
+#[tauri::command]
+pub async fn convert_variants(
+    path_str: String,
+    output_dir: Option<String>,
+    plan: VariantPlan,
+) -> Result<VariantResult, AppError> {
+    tauri::async_runtime::spawn_blocking(move || {
+        let path = Path::new(&path_str);
+        let name = path
+            .file_stem()
+            .and_then(|s| s.to_str())
+            .ok_or_else(|| AppError::InvalidInput(format!("Invalid file name: {}", path_str)))?;
+        let dir = match &output_dir {
+            Some(dir) => Path::new(dir),
+            None => path.parent().unwrap_or(Path::new("")),
+        };
+
+        let data = fs::read(path).map_err(|e| AppError::file("read", path, e))?;
+        let set = pipeline::generate_variants(&data, name, &plan)?;
+
+        let mut outputs = Vec::with_capacity(set.variants.len());
+        for variant in set.variants {
+            let out_path = dir.join(&variant.file_name);
+            fs::write(&out_path, &variant.data)
+                .map_err(|e| AppError::file("write", &out_path, e))?;
+            outputs.push(VariantOutput {
+                path: out_path.to_string_lossy().to_string(),
+                width: variant.width,
+                height: variant.height,
+                format: variant.format,
+                size: variant.data.len() as u64,
+            });
+        }
+
+        if let Some(manifest) = &set.manifest {
+            let json =
+                serde_json::to_vec_pretty(manifest).map_err(|e| AppError::Task(e.to_string()))?;
+            let json_path = dir.join(format!("{}.json", name));
+            fs::write(&json_path, json).map_err(|e| AppError::file("write", &json_path, e))?;
+            if plan.manifest.as_ref().is_some_and(|m| m.html) {
+                let html_path = dir.join(format!("{}.html", name));
+                fs::write(&html_path, &manifest.html)
+                    .map_err(|e| AppError::file("write", &html_path, e))?;
+            }
+        }
+
+        Ok(VariantResult {
+            outputs,
+            manifest: set.manifest,
+        })
+    })
+    .await
+    .map_err(|e| AppError::Task(e.to_string()))?
+}
+
+/// 画像ファイルをデコードせずに、形式・サイズ・色の種類・メタデータを読み取ります。
+/// # 引数
+/// - `path_str`: 対象の画像ファイルのパス
+/// # 戻り値
+/// - 成功した場合は `ImageInfo` (形式、サイズ、ビット数、色の種類、アルファの有無、フレーム数、ICC プロファイルの説明、Orientation、EXIF の主な項目) を返します。
+/// - 失敗した場合は `AppError` を返します。
+/// # 注意
+/// - 画素のデコードは行わないため、大きな画像でもすぐに結果を返します。
+#[tauri::command]
+pub async fn probe_image(path_str: String) -> Result<ImageInfo, AppError> {
+    tauri::async_runtime::spawn_blocking(move || {
+        let data = fs::read(&path_str).map_err(|e| AppError::file("read", &path_str, e))?;
+        pipeline::probe(&data)
+    })
+    .await
+    .map_err(|e| AppError::Task(e.to_string()))?
+}
+
+/// ファイル一覧に表示するプレビュー (サムネイル) を生成します。
+/// # 引数
+/// - `app`: アプリのハンドル (キャッシュのディレクトリの取得に使用)
+/// - `path_str`: 対象の画像ファイルのパス
+/// - `options`: プレビューのオプション (省略時は 256x256 に収めた WebP)
+/// # 戻り値
+/// - 成功した場合はエンコードされたプレビューとそのサイズを返します。
+/// - 失敗した場合は `AppError` を返します。
+/// # 注意
+/// - 生成結果はアプリのキャッシュディレクトリの `previews` に保存し、パス・更新日時・サイズが同じ間は再利用します。
+/// - HEIC や JPEG 2000 など、WebView で表示できない形式の表示に使います。
+#[tauri::command]
+pub async fn generate_preview(
+    app: tauri::AppHandle,
+    path_str: String,
+    options: Option<PreviewOptions>,
+) -> Result<PreviewResult, AppError> {
+    let cache_dir = app
+        .path()
+        .app_cache_dir()
+        .ok()
+        .map(|dir| dir.join("previews"));
+    tauri::async_runtime::spawn_blocking(move || {
+        pipeline::preview_file(
+            &path_str,
+            &options.unwrap_or_default(),
+            cache_dir.as_deref(),
+        )
+    })
+    .await
+    .map_err(|e| AppError::Task(e.to_string()))?
+}
+
 /// ファイルパスを解析して、ファイル名、拡張子、親ディレクトリを抽出します。
 /// # 引数
 /// - `path_str`: 解析対象のファイルパス文字列
 /// # 戻り値
 /// - 成功した場合は `PathInfo` 構造体を返します。
-/// - 失敗した場合はエラーメッセージを `String` として返します。
+/// - 失敗した場合は `AppError` を返します。
 #[tauri::command]
-pub fn parse_path(path_str: String) -> Result<PathInfo, String> {
+pub fn parse_path(path_str: String) -> Result<PathInfo, AppError> {
     let path = Path::new(&path_str);
 
     let info = PathInfo {
@@ -58,3 +283,44 @@ pub fn parse_path(path_str: String) -> Result<PathInfo, String> {
 
     Ok(info)
 }
+
+/// 利用可能な入力形式と、出力形式ごとのオプションの一覧を返します。
+/// # 戻り値
+/// - デコーダーの一覧 (名前と拡張子) と、エンコーダーの一覧 (形式・拡張子・MIME タイプ・オプション) を返します。
+/// # 注意
+/// - エンコーダーは `EncodeOptions` で複数の形式を指定した場合の優先順に並びます。
+#[tauri::command]
+pub fn list_codecs() -> CodecList {
+    registry().describe()
+}
+
+/// デコード時のサイズやメモリ量の上限を設定します。
+/// # 引数
+/// - `limits`: 以降のすべての変換・読み取りで使う上限 (各項目が null の場合は制限しない)
+#[tauri::command]
+pub fn set_decode_limits(limits: DecodeLimits) {
+    pipeline::set_decode_limits(limits);
+}
+
+/// 現在のデコード時の上限を返します。
+#[tauri::command]
+pub fn get_decode_limits() -> DecodeLimits {
+    pipeline::decode_limits()
+}
+
+/// 一括変換を始めます (前回の一括変換の中止を取り消します)。
+/// # 注意
+/// - 一括変換の最初のファイルを `convert_file` で変換する前に呼び出します。
+#[tauri::command]
+pub fn start_batch() {
+    pipeline::start_batch();
+}
+
+/// 一括変換を中止します。
+/// # 注意
+/// - 以降の `convert_file` は、`start_batch` が呼ばれるまで `cancelled` のエラーを返します。
+/// - 変換中のファイルは書き込まずに中止します (分離モードの場合はワーカープロセスを終了させます)。
+#[tauri::command]
+pub fn cancel_batch() {
+    pipeline::cancel_batch();
+}
@@ -1,6 +1,16 @@
+use crate::codec::{self, Decoder};
+use crate::color::icc_description;
 use crate::error::AppError;
-use image::{DynamicImage, ImageBuffer, ImageFormat, Rgba};
-use libheif_rs::{HeifContext, LibHeif};
+use crate::header::{exif_orientation, exif_summary, frame_count};
+use crate::limits::{check_info, check_size, image_error, image_limits};
+use crate::options::{ColorType, ImageInfo, OutputFormat};
+use image::{DynamicImage, ExtendedColorType, ImageDecoder, ImageFormat, ImageReader};
+#[cfg(feature = "heif")]
+use libheif_rs::{HeifContext, ImageHandle, LibHeif};
+use qcms::{DataType, Intent, Profile, Transform};
+use std::io::Cursor;
+use zune_jpeg::JpegDecoder;
+use zune_jpeg::zune_core::colorspace::ColorSpace as JpegColorSpace;
 
 /// バイトデータから画像をデコードし、DynamicImageとして返す
 /// サポートする形式: HEIC, JPEG 2000, そして imageクレートが対応する形式
@@ -8,121 +18,618 @@ use libheif_rs::{HeifContext, LibHeif};
 /// - `image_bytes`: 画像のバイトデータ
 /// # 戻り値
 /// - 成功した場合は `DynamicImage` を返します。
-/// - 失敗した場合は `Box<dyn Error>` を返します。
+/// - 失敗した場合は `AppError` を返します。
 /// # 注意
+/// - 形式の判別とデコードは `codec::registry()` に登録されたデコーダーで行います。
 /// - EXR形式はこのバージョンではサポートされていません
 /// - HEIC形式のデコードには `libheif-rs` クレートを使用しています。ビルド時に `libheif` ライブラリがシステムにインストールされている必要があります。
 /// - JPEG 2000形式のデコードには `jpeg2k` クレートを使用しています。
-///  ただし、このクレートはすべてのJPEG 2000ファイルに対応しているわけではないため、特定のファイルでエラーが発生する可能性があります。
-#[allow(dead_code)]
+///   ただし、このクレートはすべてのJPEG 2000ファイルに対応しているわけではないため、特定のファイルでエラーが発生する可能性があります。
+/// - CMYK/YCCK の JPEG は `zune-jpeg` で生のチャンネルを取り出し、埋め込み ICC プロファイル (無い場合は簡易式) で sRGB に変換します。
+/// - AVIF も libheif でデコードします (libheif が AV1 のデコーダーを含めてビルドされている必要があります)。
+/// - `heif` / `jpeg2000` feature を無効にしてビルドした場合、HEIC / AVIF / JPEG 2000 は `AppError::UnsupportedFormat` を返します。
+/// - デコードの前にヘッダーを読み取り、サイズやフレーム数が `set_decode_limits` の上限を超える場合は
+///   メモリを確保せずに `AppError::LimitExceeded` を返します。
 pub fn decode(image_bytes: &[u8]) -> Result<DynamicImage, AppError> {
-    // まず、バイトデータから画像形式を判別する
-    let format = detect_format(image_bytes)
-        .ok_or_else(|| AppError::Decode("Unsupported or unknown image format".to_string()))?;
-
-    // 判別した形式に応じて、適切なデコーダーを呼び出す
-    match format {
-        DetectedFormat::Heic => {
-            println!("Decoder: Using heif decoder...");
-            heif_to_dynamic_image(image_bytes)
-        }
-        DetectedFormat::Exr => Err(AppError::Decode(
-            "EXR format is not supported in this version".into(),
-        )),
-        DetectedFormat::Jpeg2000 => {
-            println!("Decoder: Using Jpeg2k decoder...");
-            jpeg2k_to_dynamic_image(image_bytes)
-        }
-        DetectedFormat::Standard(image_format) => {
-            println!("Decoder: Using image decoder...");
-            image::load_from_memory_with_format(image_bytes, image_format)
-                .map_err(|e| AppError::Decode(e.to_string()))
-        }
+    let decoder = find_decoder(image_bytes)?;
+    check_info(&decoder.probe(image_bytes)?)?;
+    eprintln!("Decoder: Using {} decoder...", decoder.name());
+    decoder.decode(image_bytes)
+}
+
+/// 画像全体をデコードせずに、ヘッダーから形式・サイズ・色の種類・メタデータを読み取る
+/// # 引数
+/// - `image_bytes`: 画像のバイトデータ
+/// # 戻り値
+/// - 成功した場合は `ImageInfo` を返します。
+/// - 失敗した場合は `AppError` を返します。
+/// # 注意
+/// - サイズは EXIF の回転を適用する前の値です。
+/// - ICC プロファイルの説明と EXIF は、デコーダーが読み取らなかった場合に `Decoder::color_profile` / `Decoder::exif` から補います。
+/// - HEIC / AVIF は libheif がデコード時に回転を適用するため、Orientation は `None` になります。
+pub fn probe(image_bytes: &[u8]) -> Result<ImageInfo, AppError> {
+    let decoder = find_decoder(image_bytes)?;
+    let mut info = decoder.probe(image_bytes)?;
+    info.file_size = image_bytes.len() as u64;
+    if info.icc_description.is_none()
+        && let Some(ColorProfile::Icc(icc)) = decoder.color_profile(image_bytes)
+    {
+        info.icc_description = icc_description(&icc);
+    }
+    if let Some(exif) = decoder.exif(image_bytes) {
+        info.orientation = exif_orientation(&exif);
+        info.exif = exif_summary(&exif);
     }
+    Ok(info)
 }
 
-// 独自の形式を定義するためのenum
-enum DetectedFormat {
-    Heic,
-    Exr,
-    Jpeg2000,
-    // imageクレートがサポートするその他の形式
-    Standard(ImageFormat),
+/// バイトデータから埋め込まれた色情報 (ICC プロファイルまたは nclx) を読み取る
+/// # 引数
+/// - `image_bytes`: 画像のバイトデータ
+/// # 戻り値
+/// - 色情報が見つかった場合は `ColorProfile` を返します。
+/// - 見つからない場合や読み取りに失敗した場合は `None` を返します (sRGB とみなす)。
+/// # 注意
+/// - CMYK の JPEG はデコード時に sRGB へ変換済みのため `None` を返します。
+/// - JPEG 2000 の色情報は現在読み取りません。
+pub fn read_color_profile(image_bytes: &[u8]) -> Option<ColorProfile> {
+    codec::registry()
+        .decoder_for(image_bytes)?
+        .color_profile(image_bytes)
 }
 
-/// バイトデータのマジックナンバーから画像形式を判別する
-fn detect_format(bytes: &[u8]) -> Option<DetectedFormat> {
-    // HEIC/AVIF (ISOBMFFコンテナ) のチェック
-    // ftyp ボックスが "heic", "heix", "avif" などを含むか
-    if bytes.len() > 12 && &bytes[4..8] == b"ftyp" {
-        let ftyp = &bytes[8..12];
-        if ftyp == b"heic" || ftyp == b"heix" || ftyp == b"hevc" || ftyp == b"heim" {
-            return Some(DetectedFormat::Heic);
-        }
-        // AVIFの判別もここに追加できる
-        if ftyp == b"avif" || ftyp == b"avis" {
-            // AVIFの場合はimageクレートが扱えるのでStandardに流す
-            if let Ok(format) = image::guess_format(bytes) {
-                return Some(DetectedFormat::Standard(format));
-            }
+/// バイトデータから EXIF (TIFF 形式の生データ) を読み取る
+/// # 引数
+/// - `image_bytes`: 画像のバイトデータ
+/// # 戻り値
+/// - EXIF が見つかった場合はそのバイト列を返します。
+/// - 見つからない場合や読み取りに失敗した場合は `None` を返します。
+/// # 注意
+/// - HEIC は libheif がデコード時に回転を適用するため、Orientation が二重に効かないよう `None` を返します。
+pub fn read_exif(image_bytes: &[u8]) -> Option<Vec<u8>> {
+    codec::registry()
+        .decoder_for(image_bytes)?
+        .exif(image_bytes)
+}
+
+/// バイトデータから埋め込まれたサムネイル (EXIF の IFD1 や HEIF の thmb) をデコードする
+/// # 引数
+/// - `image_bytes`: 画像のバイトデータ
+/// # 戻り値
+/// - サムネイルが見つかった場合はデコードした `DynamicImage` を返します。
+/// - 見つからない場合や読み取りに失敗した場合は `None` を返します。
+/// # 注意
+/// - EXIF の Orientation は適用しません (本体の画像と同じ向きで格納されています)。
+pub fn read_thumbnail(image_bytes: &[u8]) -> Option<DynamicImage> {
+    codec::registry()
+        .decoder_for(image_bytes)?
+        .thumbnail(image_bytes)
+}
+
+/// バイトデータを扱えるデコーダーをレジストリから探す
+fn find_decoder(image_bytes: &[u8]) -> Result<&'static dyn Decoder, AppError> {
+    codec::registry()
+        .decoder_for(image_bytes)
+        .ok_or_else(|| AppError::Decode("Unsupported or unknown image format".to_string()))
+}
+
+/// エンコード結果をデコードする (画質の評価やプレビュー) 前に、その形式をデコードできるかを確かめる
+/// デコードできない場合は、エンコードを始める前に `AppError::UnsupportedFeature` を返す
+pub(crate) fn check_decodable(format: OutputFormat) -> Result<(), AppError> {
+    if codec::registry().can_decode(format) {
+        return Ok(());
+    }
+    Err(AppError::UnsupportedFeature(format!(
+        "Decoding {} output requires a {} decoder (enable the heif feature)",
+        format.extension(),
+        format.extension()
+    )))
+}
+
+/// 入力画像に埋め込まれていた色情報
+#[derive(Clone)]
+pub enum ColorProfile {
+    /// ICC プロファイルのバイト列 (JPEG APP2, PNG iCCP, TIFF, HEIC prof, WebP ICCP)
+    Icc(Vec<u8>),
+    /// HEIC の nclx (CICP) 情報
+    Nclx {
+        color_primaries: u8,
+        transfer_characteristics: u8,
+    },
+}
+
+/// HEIC のデコーダー (libheif)
+pub struct HeicDecoder;
+
+impl Decoder for HeicDecoder {
+    fn name(&self) -> &str {
+        "heic"
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["heic", "heif"]
+    }
+
+    fn sniff(&self, bytes: &[u8]) -> bool {
+        // ISOBMFF コンテナの ftyp ボックスが "heic", "heix" などを含むか
+        bytes.len() > 12
+            && &bytes[4..8] == b"ftyp"
+            && matches!(&bytes[8..12], b"heic" | b"heix" | b"hevc" | b"heim")
+    }
+
+    fn enabled(&self) -> bool {
+        cfg!(feature = "heif")
+    }
+
+    fn decode(&self, bytes: &[u8]) -> Result<DynamicImage, AppError> {
+        heif_to_dynamic_image(bytes, self.name())
+    }
+
+    fn probe(&self, bytes: &[u8]) -> Result<ImageInfo, AppError> {
+        heif_probe(bytes, self.name())
+    }
+
+    fn color_profile(&self, bytes: &[u8]) -> Option<ColorProfile> {
+        heif_color_profile(bytes)
+    }
+
+    fn thumbnail(&self, bytes: &[u8]) -> Option<DynamicImage> {
+        heif_thumbnail(bytes)
+    }
+}
+
+/// AVIF のデコーダー (libheif)
+/// image クレートは AVIF の読み込み (dav1d) を有効にしていないため、HEIC と同じく libheif でデコードする
+/// libheif が AV1 のデコーダー (dav1d または libaom) を含めてビルドされている必要がある
+pub struct AvifDecoder;
+
+impl Decoder for AvifDecoder {
+    fn name(&self) -> &str {
+        "avif"
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["avif"]
+    }
+
+    fn sniff(&self, bytes: &[u8]) -> bool {
+        // ISOBMFF コンテナの ftyp ボックスが "avif" (静止画) または "avis" (連番) か
+        bytes.len() > 12 && &bytes[4..8] == b"ftyp" && matches!(&bytes[8..12], b"avif" | b"avis")
+    }
+
+    fn enabled(&self) -> bool {
+        cfg!(feature = "heif")
+    }
+
+    fn decode(&self, bytes: &[u8]) -> Result<DynamicImage, AppError> {
+        heif_to_dynamic_image(bytes, self.name())
+    }
+
+    fn probe(&self, bytes: &[u8]) -> Result<ImageInfo, AppError> {
+        heif_probe(bytes, self.name())
+    }
+
+    fn color_profile(&self, bytes: &[u8]) -> Option<ColorProfile> {
+        heif_color_profile(bytes)
+    }
+
+    fn thumbnail(&self, bytes: &[u8]) -> Option<DynamicImage> {
+        heif_thumbnail(bytes)
+    }
+}
+
+/// EXR (このバージョンではサポートしていないため、判別のみ行う)
+pub struct ExrDecoder;
+
+impl Decoder for ExrDecoder {
+    fn name(&self) -> &str {
+        "exr"
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["exr"]
+    }
+
+    fn sniff(&self, bytes: &[u8]) -> bool {
+        bytes.starts_with(&[0x76, 0x2f, 0x31, 0x01])
+    }
+
+    fn enabled(&self) -> bool {
+        false
+    }
+
+    fn decode(&self, _bytes: &[u8]) -> Result<DynamicImage, AppError> {
+        Err(exr_unsupported())
+    }
+
+    fn probe(&self, _bytes: &[u8]) -> Result<ImageInfo, AppError> {
+        Err(exr_unsupported())
+    }
+}
+
+/// EXR はこのバージョンではサポートしていない
+fn exr_unsupported() -> AppError {
+    AppError::UnsupportedFormat {
+        format: "exr".to_string(),
+        feature: None,
+    }
+}
+
+/// JPEG 2000 (JP2) のデコーダー (jpeg2k)
+pub struct Jpeg2000Decoder;
+
+impl Decoder for Jpeg2000Decoder {
+    fn name(&self) -> &str {
+        "jpeg2000"
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["jp2"]
+    }
+
+    fn sniff(&self, bytes: &[u8]) -> bool {
+        bytes.starts_with(b"\x00\x00\x00\x0CjP  \r\n\x87\n")
+    }
+
+    fn enabled(&self) -> bool {
+        cfg!(feature = "jpeg2000")
+    }
+
+    fn decode(&self, bytes: &[u8]) -> Result<DynamicImage, AppError> {
+        jpeg2k_to_dynamic_image(bytes)
+    }
+
+    fn probe(&self, bytes: &[u8]) -> Result<ImageInfo, AppError> {
+        jp2_probe(bytes).ok_or(AppError::Decode(
+            "JPEG 2000 image header not found".to_string(),
+        ))
+    }
+}
+
+/// CMYK / YCCK の JPEG のデコーダー (zune-jpeg + qcms)
+pub struct CmykJpegDecoder;
+
+impl Decoder for CmykJpegDecoder {
+    fn name(&self) -> &str {
+        "jpeg"
+    }
+
+    fn extensions(&self) -> &[&str] {
+        ImageFormat::Jpeg.extensions_str()
+    }
+
+    fn sniff(&self, bytes: &[u8]) -> bool {
+        image::guess_format(bytes).is_ok_and(|format| format == ImageFormat::Jpeg)
+            && is_cmyk_jpeg(bytes)
+    }
+
+    fn decode(&self, bytes: &[u8]) -> Result<DynamicImage, AppError> {
+        cmyk_jpeg_to_dynamic_image(bytes)
+    }
+
+    fn probe(&self, bytes: &[u8]) -> Result<ImageInfo, AppError> {
+        let mut decoder = JpegDecoder::new(bytes);
+        decoder.decode_headers().map_err(AppError::decode)?;
+        let info = decoder
+            .info()
+            .ok_or(AppError::Decode("JPEG dimensions not found".to_string()))?;
+        // 色情報はデコード時に sRGB へ変換するが、元のプロファイルの説明は返す
+        let icc_description = ImageReader::with_format(Cursor::new(bytes), ImageFormat::Jpeg)
+            .into_decoder()
+            .ok()
+            .and_then(|mut decoder| decoder.icc_profile().ok()?)
+            .and_then(|icc| icc_description(&icc));
+        Ok(ImageInfo {
+            format: self.name().to_string(),
+            width: info.width as u32,
+            height: info.height as u32,
+            bit_depth: 8,
+            color_type: ColorType::Cmyk,
+            frame_count: 1,
+            icc_description,
+            ..Default::default()
+        })
+    }
+
+    // 色情報はデコード時に sRGB へ変換済みのため返さない
+
+    fn exif(&self, bytes: &[u8]) -> Option<Vec<u8>> {
+        image_crate_exif(bytes, ImageFormat::Jpeg)
+    }
+}
+
+/// image クレートが対応する形式のデコーダー
+pub struct StandardDecoder {
+    format: ImageFormat,
+    name: String,
+}
+
+impl StandardDecoder {
+    pub fn new(format: ImageFormat) -> Self {
+        Self {
+            format,
+            name: format!("{:?}", format).to_lowercase(),
         }
     }
-    // EXRのチェック
-    if bytes.starts_with(&[0x76, 0x2f, 0x31, 0x01]) {
-        return Some(DetectedFormat::Exr);
+
+    /// image クレートで読み込みが有効な形式のデコーダーの一覧
+    /// EXR は専用の `ExrDecoder` で扱うため除く
+    pub fn all() -> impl Iterator<Item = StandardDecoder> {
+        ImageFormat::all()
+            .filter(|format| format.reading_enabled() && *format != ImageFormat::OpenExr)
+            .map(StandardDecoder::new)
+    }
+}
+
+impl Decoder for StandardDecoder {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn extensions(&self) -> &[&str] {
+        self.format.extensions_str()
+    }
+
+    fn sniff(&self, bytes: &[u8]) -> bool {
+        image::guess_format(bytes).is_ok_and(|format| format == self.format)
+    }
+
+    fn decode(&self, bytes: &[u8]) -> Result<DynamicImage, AppError> {
+        let mut reader = ImageReader::with_format(Cursor::new(bytes), self.format);
+        reader.limits(image_limits());
+        reader.decode().map_err(image_error)
+    }
+
+    fn probe(&self, bytes: &[u8]) -> Result<ImageInfo, AppError> {
+        // 上限の確認は読み取った情報で行うため、ヘッダーの読み取りでは制限しない
+        let mut reader = ImageReader::with_format(Cursor::new(bytes), self.format);
+        reader.no_limits();
+        let decoder = reader.into_decoder().map_err(AppError::decode)?;
+        let (width, height) = decoder.dimensions();
+        let color = decoder.color_type();
+        let original = decoder.original_color_type();
+        Ok(ImageInfo {
+            format: self.name.clone(),
+            width,
+            height,
+            bit_depth: bits_per_channel(original, color),
+            color_type: color_type_of(original, color),
+            has_alpha: color.has_alpha(),
+            frame_count: frame_count(self.format, bytes),
+            ..Default::default()
+        })
     }
 
-    // JPEG 2000のチェック
-    if bytes.starts_with(b"\x00\x00\x00\x0CjP  \r\n\x87\n") {
-        return Some(DetectedFormat::Jpeg2000);
+    fn color_profile(&self, bytes: &[u8]) -> Option<ColorProfile> {
+        let reader = ImageReader::with_format(Cursor::new(bytes), self.format);
+        let mut decoder = reader.into_decoder().ok()?;
+        decoder.icc_profile().ok()?.map(ColorProfile::Icc)
     }
 
-    // 上記のいずれでもない場合、imageクレートの形式推測に任せる
-    if let Ok(format) = image::guess_format(bytes) {
-        return Some(DetectedFormat::Standard(format));
+    fn exif(&self, bytes: &[u8]) -> Option<Vec<u8>> {
+        image_crate_exif(bytes, self.format)
     }
+}
+
+/// image クレートのデコーダーで EXIF を読み取る
+fn image_crate_exif(bytes: &[u8], format: ImageFormat) -> Option<Vec<u8>> {
+    let reader = ImageReader::with_format(Cursor::new(bytes), format);
+    let mut decoder = reader.into_decoder().ok()?;
+    decoder.exif_metadata().ok()?
+}
+
+/// ファイル内の元の色の種類から、1 チャンネルあたりのビット数を求める
+fn bits_per_channel(original: ExtendedColorType, color: image::ColorType) -> u8 {
+    match original.channel_count() {
+        // 元の色の種類が不明な場合はデコード後の値を使う
+        0 => color.bits_per_pixel() as u8 / color.channel_count(),
+        channels => (original.bits_per_pixel() / channels as u16) as u8,
+    }
+}
+
+/// ファイル内の元の色の種類とデコード後の色の種類から `ColorType` を求める
+fn color_type_of(original: ExtendedColorType, color: image::ColorType) -> ColorType {
+    // アルファの無い 4 チャンネルは CMYK (デコード時に RGB へ変換される)
+    if original.channel_count() == 4 && !color.has_alpha() {
+        return ColorType::Cmyk;
+    }
+    match (color.has_color(), color.has_alpha()) {
+        (false, false) => ColorType::Gray,
+        (false, true) => ColorType::GrayAlpha,
+        (true, false) => ColorType::Rgb,
+        (true, true) => ColorType::Rgba,
+    }
+}
+
+/// JP2 のボックスを走査し、jp2h 内の ihdr / colr ボックスからサイズと色の情報を読み取る
+/// ihdr: 高さ (4) 幅 (4) コンポーネント数 (2) ビット数 (1、下位 7 ビット + 1、255 は可変)
+/// colr: METH (1) PREC (1) APPROX (1) の後に、METH が 1 なら列挙された色空間、2 なら ICC プロファイル
+fn jp2_probe(bytes: &[u8]) -> Option<ImageInfo> {
+    let jp2h = find_box(bytes, b"jp2h")?;
+    let ihdr = find_box(jp2h, b"ihdr")?;
+    let height = u32::from_be_bytes(ihdr.get(0..4)?.try_into().ok()?);
+    let width = u32::from_be_bytes(ihdr.get(4..8)?.try_into().ok()?);
+    let components = u16::from_be_bytes(ihdr.get(8..10)?.try_into().ok()?);
+    let bit_depth = match *ihdr.get(10)? {
+        255 => 8,
+        bpc => (bpc & 0x7f) + 1,
+    };
+
+    let colr = find_box(jp2h, b"colr");
+    let is_cmyk = colr.and_then(|colr| colr.get(0..7)) == Some(&[1, 0, 0, 0, 0, 0, 12][..]);
+    let icc_description = colr
+        .filter(|colr| colr.first() == Some(&2))
+        .and_then(|colr| icc_description(colr.get(3..)?));
+    let color_type = match components {
+        1 => ColorType::Gray,
+        2 => ColorType::GrayAlpha,
+        4 if is_cmyk => ColorType::Cmyk,
+        4.. => ColorType::Rgba,
+        _ => ColorType::Rgb,
+    };
 
+    Some(ImageInfo {
+        format: "jpeg2000".to_string(),
+        width,
+        height,
+        bit_depth,
+        color_type,
+        has_alpha: matches!(color_type, ColorType::GrayAlpha | ColorType::Rgba),
+        frame_count: 1,
+        icc_description,
+        ..Default::default()
+    })
+}
+
+/// ISO BMFF 形式のボックス列から指定した種類のボックスを探し、その中身を返す
+fn find_box<'a>(mut bytes: &'a [u8], box_type: &[u8; 4]) -> Option<&'a [u8]> {
+    while bytes.len() >= 8 {
+        let length = u32::from_be_bytes(bytes[0..4].try_into().ok()?) as u64;
+        let (header, length) = match length {
+            // 0 はファイルの終端まで、1 は 64 ビットの拡張長
+            0 => (8, bytes.len() as u64),
+            1 => (16, u64::from_be_bytes(bytes.get(8..16)?.try_into().ok()?)),
+            _ => (8, length),
+        };
+        let length = usize::try_from(length).ok()?;
+        if length < header || length > bytes.len() {
+            return None;
+        }
+        if &bytes[4..8] == box_type {
+            return Some(&bytes[header..length]);
+        }
+        bytes = &bytes[length..];
+    }
     None
 }
 
-/// HEIFファイルを読み込み、DynamicImageに変換する関数
-fn heif_to_dynamic_image(bytes: &[u8]) -> Result<DynamicImage, AppError> {
+/// HEIF のヘッダーからサイズ・ビット数・アルファの有無・画像数・EXIF を読み取る
+/// EXIF のメタデータブロックは、先頭 4 バイトが TIFF ヘッダーまでのオフセット
+#[cfg(feature = "heif")]
+fn heif_probe(bytes: &[u8], format: &str) -> Result<ImageInfo, AppError> {
+    let ctx = HeifContext::read_from_bytes(bytes).map_err(AppError::decode)?;
+    let handle = ctx.primary_image_handle().map_err(AppError::decode)?;
+    let has_alpha = handle.has_alpha_channel();
+
+    let mut exif_ids = [0; 1];
+    let exif = (handle.metadata_block_ids(&mut exif_ids, b"Exif") > 0)
+        .then(|| handle.metadata(exif_ids[0]).ok())
+        .flatten()
+        .and_then(|data| {
+            let offset = u32::from_be_bytes(data.get(0..4)?.try_into().ok()?) as usize;
+            exif_summary(data.get(4usize.checked_add(offset)?..)?)
+        });
+
+    Ok(ImageInfo {
+        format: format.to_string(),
+        width: handle.width(),
+        height: handle.height(),
+        bit_depth: handle.luma_bits_per_pixel(),
+        color_type: if has_alpha {
+            ColorType::Rgba
+        } else {
+            ColorType::Rgb
+        },
+        has_alpha,
+        frame_count: ctx.number_of_top_level_images().max(1) as u32,
+        exif,
+        ..Default::default()
+    })
+}
+
+/// HEIF に埋め込まれた ICC プロファイルまたは nclx を読み取る
+#[cfg(feature = "heif")]
+fn heif_color_profile(bytes: &[u8]) -> Option<ColorProfile> {
+    let ctx = HeifContext::read_from_bytes(bytes).ok()?;
+    let handle = ctx.primary_image_handle().ok()?;
+    if let Some(raw) = handle.color_profile_raw() {
+        return Some(ColorProfile::Icc(raw.data));
+    }
+    let nclx = handle.color_profile_nclx()?;
+    Some(ColorProfile::Nclx {
+        color_primaries: nclx.color_primaries() as u8,
+        transfer_characteristics: nclx.transfer_characteristics() as u8,
+    })
+}
+
+/// HEIFファイル (HEIC / AVIF) を読み込み、DynamicImageに変換する関数
+#[cfg(feature = "heif")]
+fn heif_to_dynamic_image(bytes: &[u8], format: &str) -> Result<DynamicImage, AppError> {
     let lib_heif = LibHeif::new();
 
-    let ctx = HeifContext::read_from_bytes(bytes).map_err(|e| AppError::Decode(e.to_string()))?;
-    let handle = ctx
-        .primary_image_handle()
-        .map_err(|e| AppError::Decode(e.to_string()))?;
+    let ctx = HeifContext::read_from_bytes(bytes).map_err(AppError::decode)?;
+    let handle = ctx.primary_image_handle().map_err(AppError::decode)?;
+    let img = heif_handle_to_dynamic_image(&lib_heif, &handle)?;
+
+    eprintln!("Decoder: Finish decoding {}.", format.to_uppercase());
+    Ok(img)
+}
+
+/// HEIF に埋め込まれたサムネイルのうち最初のものをデコードする
+#[cfg(feature = "heif")]
+fn heif_thumbnail(bytes: &[u8]) -> Option<DynamicImage> {
+    let ctx = HeifContext::read_from_bytes(bytes).ok()?;
+    let handle = ctx.primary_image_handle().ok()?;
+    let mut thumbnail_ids = [0; 1];
+    if handle.thumbnail_ids(&mut thumbnail_ids) == 0 {
+        return None;
+    }
+    let thumbnail = handle.thumbnail(thumbnail_ids[0]).ok()?;
+    heif_handle_to_dynamic_image(&LibHeif::new(), &thumbnail).ok()
+}
+
+/// HEIF の画像ハンドルを RGBA でデコードし、DynamicImage に変換する
+#[cfg(feature = "heif")]
+fn heif_handle_to_dynamic_image(
+    lib_heif: &LibHeif,
+    handle: &ImageHandle,
+) -> Result<DynamicImage, AppError> {
+    // サムネイルは `decode` の確認を通らないため、ここでも上限を確かめる
+    let width = handle.width();
+    let height = handle.height();
+    check_size(width, height, 4, 8)?;
     let img = lib_heif
         .decode(
-            &handle,
+            handle,
             libheif_rs::ColorSpace::Rgb(libheif_rs::RgbChroma::Rgba),
             None,
         )
-        .map_err(|e| AppError::Decode(e.to_string()))?;
+        .map_err(AppError::decode)?;
 
-    let width = handle.width();
-    let height = handle.height();
     let planes = img.planes();
     let interleaved_plane = planes
         .interleaved
         .ok_or(AppError::Decode("Interleaved plane not found".to_string()))?;
-    let pixel_data = interleaved_plane.data.to_vec();
+    // 行の末尾に詰め物がある場合があるため、stride ごとに1行分を取り出す
+    let row_bytes = width as usize * 4;
+    let pixel_data: Vec<u8> = interleaved_plane
+        .data
+        .chunks(interleaved_plane.stride)
+        .take(height as usize)
+        .flat_map(|row| &row[..row_bytes.min(row.len())])
+        .copied()
+        .collect();
 
-    let image_buffer: ImageBuffer<Rgba<u8>, Vec<u8>> =
-        ImageBuffer::from_raw(width, height, pixel_data).ok_or(AppError::Decode(
-            "Failed to create ImageBuffer from raw data".to_string(),
-        ))?;
+    let image_buffer = image::RgbaImage::from_raw(width, height, pixel_data).ok_or(
+        AppError::Decode("Failed to create ImageBuffer from raw data".to_string()),
+    )?;
 
-    println!("Decoder: Finish decoding HEIC.");
     Ok(DynamicImage::ImageRgba8(image_buffer))
 }
 
 /// JPEG 2000 ファイルを読み込み、DynamicImageに変換する
+#[cfg(feature = "jpeg2000")]
 fn jpeg2k_to_dynamic_image(bytes: &[u8]) -> Result<DynamicImage, AppError> {
+    // jpeg2k は各サンプルを i32 で保持するため、32 ビットとして見積もる
+    if let Some(info) = jp2_probe(bytes) {
+        check_info(&ImageInfo {
+            bit_depth: 32,
+            ..info
+        })?;
+    }
+
     // Use the `jpeg2k` crate to decode JPEG 2000 from bytes
-    let jp2_image =
-        jpeg2k::Image::from_bytes(bytes).map_err(|e| AppError::Decode(e.to_string()))?;
+    let jp2_image = jpeg2k::Image::from_bytes(bytes).map_err(AppError::decode)?;
 
     let width = jp2_image.width();
     let height = jp2_image.height();
@@ -165,11 +672,173 @@ fn jpeg2k_to_dynamic_image(bytes: &[u8]) -> Result<DynamicImage, AppError> {
         }
     };
 
-    println!("Decoder: Finish decoding JPEG 2000.");
+    eprintln!("Decoder: Finish decoding JPEG 2000.");
 
     Ok(dynamic_image)
 }
 
+/// JPEG が CMYK または YCCK (4チャンネル) で保存されているかを判定する
+fn is_cmyk_jpeg(bytes: &[u8]) -> bool {
+    let mut decoder = JpegDecoder::new(bytes);
+    if decoder.decode_headers().is_err() {
+        return false;
+    }
+    matches!(
+        decoder.get_input_colorspace(),
+        Some(JpegColorSpace::CMYK | JpegColorSpace::YCCK)
+    )
+}
+
+/// JPEG のマーカーを走査し、Adobe APP14 セグメントが存在するかを返す
+/// Adobe 製ソフトが書き出した CMYK JPEG は値が反転 (255 = インク無し) して保存されている
+fn has_adobe_app14(bytes: &[u8]) -> bool {
+    // SOI (FF D8) の直後からセグメントを順に読む
+    let mut pos = 2;
+    while pos + 4 <= bytes.len() {
+        if bytes[pos] != 0xFF {
+            return false;
+        }
+        let marker = bytes[pos + 1];
+        // SOS 以降は画像データなので探索を打ち切る
+        if marker == 0xDA || marker == 0xD9 {
+            return false;
+        }
+        let length = u16::from_be_bytes([bytes[pos + 2], bytes[pos + 3]]) as usize;
+        let segment = &bytes[(pos + 4).min(bytes.len())..(pos + 2 + length).min(bytes.len())];
+        if marker == 0xEE && segment.starts_with(b"Adobe") {
+            return true;
+        }
+        pos += 2 + length;
+    }
+    false
+}
+
+/// CMYK/YCCK の JPEG を読み込み、sRGB の DynamicImage に変換する
+/// # 注意
+/// - Adobe APP14 マーカーがある場合は反転された値として扱います。
+/// - 埋め込み ICC プロファイルが CMYK プロファイルであればそれを使って sRGB に変換し、
+///   無い場合や読み込めない場合は簡易的な CMYK→RGB 変換式を使用します。
+fn cmyk_jpeg_to_dynamic_image(bytes: &[u8]) -> Result<DynamicImage, AppError> {
+    let mut decoder = JpegDecoder::new(bytes);
+    decoder.decode_headers().map_err(AppError::decode)?;
+    if let Some(info) = decoder.info() {
+        check_size(info.width as u32, info.height as u32, 4, 8)?;
+    }
+    let input_colorspace = decoder
+        .get_input_colorspace()
+        .ok_or(AppError::Decode("JPEG headers not decoded".to_string()))?;
+
+    // 色変換させずに4チャンネルの生データをそのまま受け取る
+    let options = decoder
+        .get_options()
+        .jpeg_set_out_colorspace(input_colorspace);
+    decoder.set_options(options);
+    let mut pixels = decoder.decode().map_err(AppError::decode)?;
+    let info = decoder
+        .info()
+        .ok_or(AppError::Decode("JPEG dimensions not found".to_string()))?;
+    let (width, height) = (info.width as u32, info.height as u32);
+
+    // YCCK は YCbCr 部分を RGB に戻し、反転して CMY を得る (K はそのまま)
+    if input_colorspace == JpegColorSpace::YCCK {
+        for px in pixels.chunks_exact_mut(4) {
+            let y = px[0] as f32;
+            let cb = px[1] as f32 - 128.0;
+            let cr = px[2] as f32 - 128.0;
+            let r = (y + 1.402 * cr).round().clamp(0.0, 255.0) as u8;
+            let g = (y - 0.344_136 * cb - 0.714_136 * cr)
+                .round()
+                .clamp(0.0, 255.0) as u8;
+            let b = (y + 1.772 * cb).round().clamp(0.0, 255.0) as u8;
+            px[0] = 255 - r;
+            px[1] = 255 - g;
+            px[2] = 255 - b;
+        }
+    }
+
+    // Adobe 形式の反転を戻し、0 = インク無し / 255 = インク最大 に揃える
+    if has_adobe_app14(bytes) {
+        for v in pixels.iter_mut() {
+            *v = 255 - *v;
+        }
+    }
+
+    let rgb = match decoder
+        .icc_profile()
+        .and_then(|icc| cmyk_icc_transform(&icc))
+    {
+        Some(transform) => {
+            eprintln!("Decoder: Applying embedded CMYK ICC profile...");
+            let mut rgb = vec![0u8; (width as usize) * (height as usize) * 3];
+            transform.convert(&pixels, &mut rgb);
+            rgb
+        }
+        None => {
+            eprintln!("Decoder: No usable ICC profile, using default CMYK conversion...");
+            pixels
+                .chunks_exact(4)
+                .flat_map(|px| {
+                    let k = 255 - px[3] as u32;
+                    [px[0], px[1], px[2]].map(|c| ((255 - c as u32) * k / 255) as u8)
+                })
+                .collect()
+        }
+    };
+
+    let image_buffer = image::RgbImage::from_raw(width, height, rgb).ok_or(AppError::Decode(
+        "Failed to create ImageBuffer from raw data".to_string(),
+    ))?;
+
+    eprintln!("Decoder: Finish decoding CMYK JPEG.");
+    Ok(DynamicImage::ImageRgb8(image_buffer))
+}
+
+/// CMYK の ICC プロファイルから sRGB への変換を作成する
+/// プロファイルが CMYK 用でない場合や解析できない場合は None を返す
+fn cmyk_icc_transform(icc: &[u8]) -> Option<Transform> {
+    // ICC ヘッダーの 16〜20 バイト目がデータ色空間のシグネチャ
+    if icc.get(16..20) != Some(b"CMYK".as_slice()) {
+        return None;
+    }
+    let profile = Profile::new_from_slice(icc, false)?;
+    let mut srgb = Profile::new_sRGB();
+    srgb.precache_output_transform();
+    Transform::new_to(
+        &profile,
+        &srgb,
+        DataType::CMYK,
+        DataType::RGB8,
+        Intent::Perceptual,
+    )
+}
+
+// feature で無効化された形式は、判別のみ行い UnsupportedFormat を返す
+
+#[cfg(not(feature = "heif"))]
+fn heif_probe(_bytes: &[u8], format: &str) -> Result<ImageInfo, AppError> {
+    Err(AppError::disabled(format, "heif"))
+}
+
+#[cfg(not(feature = "heif"))]
+fn heif_color_profile(_bytes: &[u8]) -> Option<ColorProfile> {
+    None
+}
+
+#[cfg(not(feature = "heif"))]
+fn heif_thumbnail(_bytes: &[u8]) -> Option<DynamicImage> {
+    None
+}
+
+#[cfg(not(feature = "heif"))]
+fn heif_to_dynamic_image(_bytes: &[u8], format: &str) -> Result<DynamicImage, AppError> {
+    Err(AppError::disabled(format, "heif"))
+}
+
+#[cfg(not(feature = "jpeg2000"))]
+fn jpeg2k_to_dynamic_image(_bytes: &[u8]) -> Result<DynamicImage, AppError> {
+    Err(AppError::disabled("jpeg2000", "jpeg2000"))
+}
+
 /*
 /// ACESフィルミックトーンマッピング
 fn aces_tonemap(x: f32) -> f32 {
@@ -226,3 +895,87 @@ pub fn exr_to_dynamic_image(
     Ok(DynamicImage::ImageRgba8(rgba_image))
 }
 */
+
+#[cfg(all(test, feature = "jpeg"))]
+mod tests {
+    use super::*;
+    use mozjpeg::ColorSpace as MozColorSpace;
+
+    /// 単色の 4 チャンネル JPEG を mozjpeg で作る (値はファイルに格納される値そのもの)
+    /// mozjpeg は CMYK / YCCK で保存する場合、常に Adobe APP14 を書き込む
+    fn four_channel_jpeg(stored: [u8; 4], color_space: MozColorSpace) -> Vec<u8> {
+        let (width, height) = (16, 16);
+        let mut compress = mozjpeg::Compress::new(MozColorSpace::JCS_CMYK);
+        compress.set_color_space(color_space);
+        compress.set_size(width, height);
+        compress.set_quality(100.0);
+        let mut started = compress.start_compress(Vec::new()).unwrap();
+        started
+            .write_scanlines(&stored.repeat(width * height))
+            .unwrap();
+        started.finish().unwrap()
+    }
+
+    /// Adobe APP14 セグメントを取り除く (反転していない CMYK の JPEG として扱わせる)
+    fn strip_adobe_app14(jpeg: &[u8]) -> Vec<u8> {
+        let mut out = jpeg[..2].to_vec();
+        let mut pos = 2;
+        while jpeg[pos + 1] != 0xDA {
+            let length = u16::from_be_bytes([jpeg[pos + 2], jpeg[pos + 3]]) as usize;
+            if jpeg[pos + 1] != 0xEE {
+                out.extend_from_slice(&jpeg[pos..pos + 2 + length]);
+            }
+            pos += 2 + length;
+        }
+        out.extend_from_slice(&jpeg[pos..]);
+        out
+    }
+
+    /// デコードした画像の中央の画素が期待する RGB に近いか確かめる
+    fn assert_rgb(jpeg: &[u8], expected: [u8; 3]) {
+        assert!(is_cmyk_jpeg(jpeg));
+        let rgb = cmyk_jpeg_to_dynamic_image(jpeg).unwrap().to_rgb8();
+        let actual = rgb.get_pixel(8, 8).0;
+        for (a, e) in actual.iter().zip(expected) {
+            assert!(
+                a.abs_diff(e) <= 4,
+                "expected {:?}, got {:?}",
+                expected,
+                actual
+            );
+        }
+    }
+
+    // インクの量 (0 = インク無し): シアン 25%、イエロー 100%、墨 0% → (191, 255, 0)
+    const INK: [u8; 4] = [64, 0, 255, 0];
+    const RGB: [u8; 3] = [191, 255, 0];
+
+    #[test]
+    fn plain_cmyk() {
+        let jpeg = strip_adobe_app14(&four_channel_jpeg(INK, MozColorSpace::JCS_CMYK));
+        assert!(!has_adobe_app14(&jpeg));
+        assert_rgb(&jpeg, RGB);
+    }
+
+    #[test]
+    fn adobe_inverted_cmyk() {
+        let jpeg = four_channel_jpeg(INK.map(|v| 255 - v), MozColorSpace::JCS_CMYK);
+        assert!(has_adobe_app14(&jpeg));
+        assert_rgb(&jpeg, RGB);
+    }
+
+    #[test]
+    fn adobe_ycck_is_inverted_once() {
+        // YCCK は Adobe 形式 (反転した CMYK) を YCC に変換して保存される
+        let jpeg = four_channel_jpeg(INK.map(|v| 255 - v), MozColorSpace::JCS_YCCK);
+        assert!(has_adobe_app14(&jpeg));
+        assert_rgb(&jpeg, RGB);
+    }
+
+    #[test]
+    fn key_only() {
+        // 墨 50% のみ → 中間の灰色
+        let jpeg = four_channel_jpeg([255, 255, 255, 127], MozColorSpace::JCS_YCCK);
+        assert_rgb(&jpeg, [127, 127, 127]);
+    }
+}
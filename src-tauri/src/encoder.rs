@@ -1,44 +1,363 @@
+use crate::codec::{self, OptionField, OptionKind, with_defaults};
 use crate::error::AppError;
-use crate::options;
+use crate::header::exif_for_output;
+use crate::options::{self, OutputFormat};
+use crate::quantize::quantize;
 use image::DynamicImage;
+#[cfg(feature = "avif")]
 use imgref::Img;
+#[cfg(feature = "webp")]
 use libwebp_sys::{
     WebPEncodeLosslessRGB, WebPEncodeLosslessRGBA, WebPEncodeRGB, WebPEncodeRGBA, WebPFree,
 };
+#[cfg(feature = "jpeg")]
+use mozjpeg::{ColorSpace, Compress, Marker};
+use oxipng::{Deflaters, RawImage, StripChunks};
+#[cfg(feature = "avif")]
 use ravif::{AlphaColorMode, BitDepth, ColorModel, Encoder};
-use rgb::{RGB8, RGBA8};
+#[cfg(feature = "avif")]
+use rgb::RGB8;
+use rgb::RGBA8;
+use std::num::NonZeroU8;
+#[cfg(feature = "jpeg")]
+use std::panic;
+#[cfg(feature = "webp")]
 use std::{ffi::c_void, ptr::null_mut, slice::from_raw_parts};
 
+/// Zopfli の圧縮の繰り返し回数
+const ZOPFLI_ITERATIONS: u8 = 15;
+/// JPEG の APP1 (EXIF) マーカーに格納できる最大のバイト数
+#[cfg(feature = "jpeg")]
+const MAX_JPEG_MARKER_BYTES: usize = 65533;
+
+/// 出力に引き継ぐメタデータ
+/// icc_profile: 出力に埋め込む ICC プロファイル (WebP / JPEG / PNG のみ対応)
+/// exif: 入力画像の EXIF (TIFF 形式の生データ、JPEG / PNG のみ対応)
+#[derive(Debug, Clone, Default)]
+pub struct Metadata {
+    pub icc_profile: Option<Vec<u8>>,
+    pub exif: Option<Vec<u8>>,
+}
+
 /// 画像を指定された形式でエンコードします。
 /// # 引数
 /// - `img`: 変換対象の画像 (DynamicImage)
 /// - `options`: エンコードオプション (options::EncodeOptions)
+/// - `metadata`: 出力に引き継ぐ ICC プロファイルや EXIF
 /// # 戻り値
 /// - 成功した場合はエンコードされたバイト列を `Vec<u8>` として返します。
-/// - 失敗した場合は `Box<dyn Error>` を返します。
+/// - 失敗した場合は `AppError` を返します。
 /// # 注意
+/// - 出力形式は `codec::registry()` に登録されたエンコーダーの順に、`options` で最初に指定されているものを使います。
 /// - AVIF形式のエンコードには `ravif` クレートを使用しています。ビルド時に `libavif` ライブラリがシステムにインストールされている必要があります。
 /// - WebP形式のエンコードには `libwebp-sys` クレートを使用しています。ビルド時に `libwebp` ライブラリがシステムにインストールされている必要があります。
-pub fn encode(img: &DynamicImage, options: options::EncodeOptions) -> Result<Vec<u8>, AppError> {
-    if let Some(avif_opts) = options.avif {
-        // ここで `AvifOptions` から `ravif` 用の引数への変換を行う
-        println!("Adapter: Converting AvifOptions for ravif encoder...");
-
-        return convert_dynamic_image_to_avif(
-            img,
-            avif_opts.quality,
-            avif_opts.bit_depth.to_ravif(),
-            avif_opts.alpha_quality,
-            avif_opts.speed,
-            avif_opts.color_model.to_ravif(),
-            avif_opts.threads,
-            avif_opts.alpha_color_mode.to_ravif(),
-        );
-    } else if let Some(webp_opts) = options.webp {
-        println!("Adapter: Converting WebpOptions for libwebp_sys encoder...");
-        return convert_dynamic_image_to_webp(img, webp_opts.quality, webp_opts.lossless);
-    }
-    Ok(vec![]) // 仮の戻り値
+/// - JPEG形式のエンコードには `mozjpeg` クレートを使用しています。
+/// - PNG形式のエンコードには `oxipng` クレートを使用しています。
+pub fn encode(
+    img: &DynamicImage,
+    options: options::EncodeOptions,
+    metadata: &Metadata,
+) -> Result<Vec<u8>, AppError> {
+    let encoder = codec::registry()
+        .encoder_for(&options)
+        .ok_or(AppError::Encode("No output format specified".to_string()))?;
+    encoder.encode(img, &options, metadata)
+}
+
+/// 品質 (0-100) の項目
+fn quality_field(key: &'static str, min: f64) -> OptionField {
+    OptionField::new(
+        key,
+        OptionKind::Number {
+            min,
+            max: 100.0,
+            step: 1.0,
+        },
+    )
+}
+
+/// AVIF のエンコーダー (ravif)
+pub struct AvifEncoder;
+
+impl codec::Encoder for AvifEncoder {
+    fn format(&self) -> OutputFormat {
+        OutputFormat::Avif
+    }
+
+    fn enabled(&self) -> bool {
+        cfg!(feature = "avif")
+    }
+
+    fn options_schema(&self) -> Vec<OptionField> {
+        let choice = |key, values| OptionField::new(key, OptionKind::Choice { values });
+        let fields = vec![
+            OptionField::new("lossless", OptionKind::Boolean),
+            quality_field("quality", 0.0),
+            choice("bitDepth", vec!["Auto", "Eight", "Ten"]),
+            quality_field("alphaQuality", 0.0),
+            OptionField::new(
+                "speed",
+                OptionKind::Number {
+                    min: 0.0,
+                    max: 10.0,
+                    step: 1.0,
+                },
+            ),
+            choice("colorModel", vec!["YCbCr", "RGB"]),
+            OptionField::new(
+                "threads",
+                OptionKind::Number {
+                    min: 1.0,
+                    max: 256.0,
+                    step: 1.0,
+                },
+            ),
+            choice(
+                "alphaColorMode",
+                vec!["UnassociatedDirty", "UnassociatedClean", "Premultiplied"],
+            ),
+        ];
+        with_defaults(fields, &options::AvifOptions::default())
+    }
+
+    fn is_selected(&self, options: &options::EncodeOptions) -> bool {
+        options.avif.is_some()
+    }
+
+    fn deselect(&self, options: &mut options::EncodeOptions) {
+        options.avif = None;
+    }
+
+    fn quality(&self, options: &options::EncodeOptions) -> Option<f32> {
+        options.avif.as_ref().map(|avif| avif.quality)
+    }
+
+    fn set_quality(&self, options: &mut options::EncodeOptions, quality: f32) {
+        if let Some(avif) = &mut options.avif {
+            avif.quality = quality;
+        }
+    }
+
+    fn encode(
+        &self,
+        img: &DynamicImage,
+        options: &options::EncodeOptions,
+        _metadata: &Metadata,
+    ) -> Result<Vec<u8>, AppError> {
+        let avif_opts = options
+            .avif
+            .as_ref()
+            .ok_or(AppError::Encode("AVIF options not specified".to_string()))?;
+        encode_avif(img, avif_opts)
+    }
+}
+
+/// WebP のエンコーダー (libwebp)
+pub struct WebpEncoder;
+
+impl codec::Encoder for WebpEncoder {
+    fn format(&self) -> OutputFormat {
+        OutputFormat::Webp
+    }
+
+    fn enabled(&self) -> bool {
+        cfg!(feature = "webp")
+    }
+
+    fn options_schema(&self) -> Vec<OptionField> {
+        let fields = vec![
+            quality_field("quality", 0.0),
+            OptionField::new("lossless", OptionKind::Boolean),
+        ];
+        with_defaults(fields, &options::WebpOptions::default())
+    }
+
+    fn is_selected(&self, options: &options::EncodeOptions) -> bool {
+        options.webp.is_some()
+    }
+
+    fn deselect(&self, options: &mut options::EncodeOptions) {
+        options.webp = None;
+    }
+
+    fn quality(&self, options: &options::EncodeOptions) -> Option<f32> {
+        options
+            .webp
+            .as_ref()
+            .and_then(|webp| (!webp.lossless).then_some(webp.quality))
+    }
+
+    fn set_quality(&self, options: &mut options::EncodeOptions, quality: f32) {
+        if let Some(webp) = &mut options.webp {
+            webp.quality = quality;
+        }
+    }
+
+    fn supports_icc_profile(&self, _options: &options::EncodeOptions) -> bool {
+        true
+    }
+
+    fn encode(
+        &self,
+        img: &DynamicImage,
+        options: &options::EncodeOptions,
+        metadata: &Metadata,
+    ) -> Result<Vec<u8>, AppError> {
+        let webp_opts = options
+            .webp
+            .as_ref()
+            .ok_or(AppError::Encode("WebP options not specified".to_string()))?;
+        eprintln!("Adapter: Converting WebpOptions for libwebp_sys encoder...");
+        let webp = convert_dynamic_image_to_webp(img, webp_opts.quality, webp_opts.lossless)?;
+        match &metadata.icc_profile {
+            Some(icc) => embed_webp_icc_profile(&webp, icc, img.width(), img.height()),
+            None => Ok(webp),
+        }
+    }
+}
+
+/// JPEG のエンコーダー (MozJPEG)
+pub struct JpegEncoder;
+
+impl codec::Encoder for JpegEncoder {
+    fn format(&self) -> OutputFormat {
+        OutputFormat::Jpeg
+    }
+
+    fn enabled(&self) -> bool {
+        cfg!(feature = "jpeg")
+    }
+
+    fn options_schema(&self) -> Vec<OptionField> {
+        let fields = vec![
+            quality_field("quality", 1.0),
+            OptionField::new("progressive", OptionKind::Boolean),
+            OptionField::new(
+                "subsampling",
+                OptionKind::Choice {
+                    values: vec!["Yuv444", "Yuv420"],
+                },
+            ),
+            OptionField::new("optimizeCoding", OptionKind::Boolean),
+            OptionField::new("trellis", OptionKind::Boolean),
+            OptionField::new("baseline", OptionKind::Boolean),
+            OptionField::new("keepMetadata", OptionKind::Boolean),
+        ];
+        with_defaults(fields, &options::JpegOptions::default())
+    }
+
+    fn is_selected(&self, options: &options::EncodeOptions) -> bool {
+        options.jpeg.is_some()
+    }
+
+    fn deselect(&self, options: &mut options::EncodeOptions) {
+        options.jpeg = None;
+    }
+
+    fn quality(&self, options: &options::EncodeOptions) -> Option<f32> {
+        options.jpeg.as_ref().map(|jpeg| jpeg.quality)
+    }
+
+    fn set_quality(&self, options: &mut options::EncodeOptions, quality: f32) {
+        if let Some(jpeg) = &mut options.jpeg {
+            jpeg.quality = quality;
+        }
+    }
+
+    fn supports_icc_profile(&self, options: &options::EncodeOptions) -> bool {
+        options.jpeg.as_ref().is_some_and(|jpeg| jpeg.keep_metadata)
+    }
+
+    fn encode(
+        &self,
+        img: &DynamicImage,
+        options: &options::EncodeOptions,
+        metadata: &Metadata,
+    ) -> Result<Vec<u8>, AppError> {
+        let jpeg_opts = options
+            .jpeg
+            .as_ref()
+            .ok_or(AppError::Encode("JPEG options not specified".to_string()))?;
+        eprintln!("Adapter: Converting JpegOptions for mozjpeg encoder...");
+        convert_dynamic_image_to_jpeg(img, jpeg_opts, metadata)
+    }
+}
+
+/// PNG のエンコーダー (oxipng)
+pub struct PngEncoder;
+
+impl codec::Encoder for PngEncoder {
+    fn format(&self) -> OutputFormat {
+        OutputFormat::Png
+    }
+
+    fn options_schema(&self) -> Vec<OptionField> {
+        let quantize = vec![
+            OptionField::new(
+                "maxColors",
+                OptionKind::Number {
+                    min: 2.0,
+                    max: 256.0,
+                    step: 1.0,
+                },
+            ),
+            quality_field("minQuality", 0.0),
+            quality_field("maxQuality", 0.0),
+            OptionField::new(
+                "dithering",
+                OptionKind::Number {
+                    min: 0.0,
+                    max: 1.0,
+                    step: 0.1,
+                },
+            ),
+        ];
+        let fields = vec![
+            OptionField::new(
+                "level",
+                OptionKind::Number {
+                    min: 0.0,
+                    max: 6.0,
+                    step: 1.0,
+                },
+            ),
+            OptionField::new("zopfli", OptionKind::Boolean),
+            OptionField::new("stripMetadata", OptionKind::Boolean),
+            OptionField::new(
+                "quantize",
+                OptionKind::Group {
+                    fields: with_defaults(quantize, &options::PngQuantizeOptions::default()),
+                },
+            ),
+        ];
+        with_defaults(fields, &options::PngOptions::default())
+    }
+
+    fn is_selected(&self, options: &options::EncodeOptions) -> bool {
+        options.png.is_some()
+    }
+
+    fn deselect(&self, options: &mut options::EncodeOptions) {
+        options.png = None;
+    }
+
+    fn supports_icc_profile(&self, _options: &options::EncodeOptions) -> bool {
+        true
+    }
+
+    fn encode(
+        &self,
+        img: &DynamicImage,
+        options: &options::EncodeOptions,
+        metadata: &Metadata,
+    ) -> Result<Vec<u8>, AppError> {
+        let png_opts = options
+            .png
+            .as_ref()
+            .ok_or(AppError::Encode("PNG options not specified".to_string()))?;
+        eprintln!("Adapter: Converting PngOptions for oxipng encoder...");
+        convert_dynamic_image_to_png(img, png_opts, metadata)
+    }
 }
 
 /// 画像を WebP にエンコードします。
@@ -51,6 +370,7 @@ pub fn encode(img: &DynamicImage, options: options::EncodeOptions) -> Result<Vec
 /// - 失敗した場合は `AppError` を返します。
 /// # 注意
 /// - `libwebp-sys` クレートを使用して WebP エンコードを行います。ビルド時に `libwebp` ライブラリがシステムにインストールされている必要があります。
+#[cfg(feature = "webp")]
 fn convert_dynamic_image_to_webp(
     img: &DynamicImage,
     quality: f32,
@@ -90,7 +410,7 @@ fn convert_dynamic_image_to_webp(
         // WebP にエンコード
         // qualityが100の場合はロスレスエンコードを使用
         let len = if is_rgba {
-            println!("Optimized path: Encoding as RGBA...");
+            eprintln!("Optimized path: Encoding as RGBA...");
             // RGBA圧縮
             if lossless == true {
                 WebPEncodeLosslessRGBA(raw.as_ptr(), width, height, stride, &mut out_buf)
@@ -98,7 +418,7 @@ fn convert_dynamic_image_to_webp(
                 WebPEncodeRGBA(raw.as_ptr(), width, height, stride, quality, &mut out_buf)
             }
         } else {
-            println!("Optimized path: Encoding as RGB...");
+            eprintln!("Optimized path: Encoding as RGB...");
             // RGB圧縮
             if lossless == true {
                 WebPEncodeLosslessRGB(raw.as_ptr(), width, height, stride, &mut out_buf)
@@ -118,12 +438,310 @@ fn convert_dynamic_image_to_webp(
         // C 側で確保されたメモリを解放
         WebPFree(out_buf as *mut c_void);
 
-        println!("Finished encoding WebP.");
+        eprintln!("Finished encoding WebP.");
 
         Ok(result)
     }
 }
 
+/// 画像を JPEG にエンコードします。
+/// # 引数
+/// - `img`: 変換対象の画像 (DynamicImage)
+/// - `options`: JPEG のオプション
+/// - `metadata`: 出力に埋め込む ICC プロファイルや EXIF (`keep_metadata` が有効な場合のみ)
+/// # 戻り値
+/// - 成功した場合は JPEG のバイト列を `Vec<u8>` として返します。
+/// - 失敗した場合は `AppError` を返します。
+/// # 注意
+/// - `mozjpeg` はエラー時に panic するため、`catch_unwind` で捕捉して `AppError` に変換します。
+/// - EXIF は Orientation を 1 に、PixelX/YDimension を出力のサイズに書き換えて埋め込みます。
+/// - JPEG はアルファを持てないため、アルファは破棄されます (背景色への合成は `prepare_image` で行います)。
+/// - トレリス量子化を無効にする場合は libjpeg 互換の設定 (高速) でエンコードします。
+#[cfg(feature = "jpeg")]
+fn convert_dynamic_image_to_jpeg(
+    img: &DynamicImage,
+    options: &options::JpegOptions,
+    metadata: &Metadata,
+) -> Result<Vec<u8>, AppError> {
+    if options.quality < 1.0 || options.quality > 100.0 {
+        return Err(AppError::Encode("Quality must be between 1 and 100".into()));
+    }
+
+    // グレースケールはそのまま、それ以外は RGB に変換する
+    let (raw, color_space) = match img {
+        DynamicImage::ImageLuma8(img) => {
+            eprintln!("Optimized path: Encoding as grayscale...");
+            (img.as_raw().clone(), ColorSpace::JCS_GRAYSCALE)
+        }
+        _ => {
+            eprintln!("Optimized path: Encoding as RGB...");
+            (img.to_rgb8().into_raw(), ColorSpace::JCS_RGB)
+        }
+    };
+    let (width, height) = (img.width() as usize, img.height() as usize);
+    let exif = metadata
+        .exif
+        .as_deref()
+        .and_then(|exif| exif_for_output(exif, img.width(), img.height()));
+
+    let result = panic::catch_unwind(|| -> std::io::Result<Vec<u8>> {
+        let mut comp = Compress::new(color_space);
+        if !options.trellis {
+            comp.set_fastest_defaults();
+        }
+        comp.set_size(width, height);
+        comp.set_quality(options.quality);
+        match options.subsampling {
+            options::ChromaSubsampling::Yuv444 => {
+                comp.set_chroma_sampling_pixel_sizes((1, 1), (1, 1))
+            }
+            options::ChromaSubsampling::Yuv420 => {
+                comp.set_chroma_sampling_pixel_sizes((2, 2), (2, 2))
+            }
+        }
+        if options.baseline || !options.progressive {
+            // ベースライン: MozJPEG の既定 (JCP_MAX_COMPRESSION) はプログレッシブのスキャン構成を設定するため、
+            // スキャンの最適化を無効にしてスキャン構成を取り除き、1 回のスキャン (シーケンシャル) にする
+            comp.set_optimize_scans(false);
+        } else {
+            // プログレッシブ: 既定の設定に頼らず、スキャン構成を明示的に設定する
+            // (トレリス量子化が有効な場合は、MozJPEG がスキャンの分け方も最適化する)
+            comp.set_progressive_mode();
+        }
+        comp.set_optimize_coding(options.optimize_coding);
+
+        let mut comp = comp.start_compress(Vec::new())?;
+        if options.keep_metadata {
+            if let Some(exif) = &exif {
+                let mut app1 = Vec::with_capacity(exif.len() + 6);
+                if !exif.starts_with(b"Exif\0\0") {
+                    app1.extend_from_slice(b"Exif\0\0");
+                }
+                app1.extend_from_slice(exif);
+                if app1.len() <= MAX_JPEG_MARKER_BYTES {
+                    comp.write_marker(Marker::APP(1), &app1);
+                } else {
+                    eprintln!("EXIF is too large for a JPEG marker, skipping.");
+                }
+            }
+            if let Some(icc) = &metadata.icc_profile {
+                comp.write_icc_profile(icc);
+            }
+        }
+        comp.write_scanlines(&raw)?;
+        comp.finish()
+    });
+
+    match result {
+        Ok(Ok(jpeg)) => {
+            eprintln!("Finished encoding JPEG.");
+            Ok(jpeg)
+        }
+        Ok(Err(e)) => Err(AppError::encode("JPEG", e)),
+        Err(_) => Err(AppError::Encode("JPEG encoder panicked".into())),
+    }
+}
+
+/// 画像を PNG にエンコードし、oxipng で最適化します。
+/// # 引数
+/// - `img`: 変換対象の画像 (DynamicImage)
+/// - `options`: PNG のオプション
+/// - `metadata`: 出力に埋め込む ICC プロファイルや EXIF (EXIF は `strip_metadata` が無効な場合のみ)
+/// # 戻り値
+/// - 成功した場合は PNG のバイト列を `Vec<u8>` として返します。
+/// - 失敗した場合は `AppError` を返します。
+/// # 注意
+/// - oxipng がカラータイプ・ビット深度の削減、フィルターと圧縮の探索を行います。
+/// - `quantize` が指定されている場合は減色してパレット PNG にします。品質の下限に届かない場合は可逆圧縮になります。
+/// - EXIF は Orientation を 1 に、PixelX/YDimension を出力のサイズに書き換えて埋め込みます。
+fn convert_dynamic_image_to_png(
+    img: &DynamicImage,
+    options: &options::PngOptions,
+    metadata: &Metadata,
+) -> Result<Vec<u8>, AppError> {
+    let png_error = |e: oxipng::PngError| AppError::encode("PNG", e);
+    let indexed = options
+        .quantize
+        .as_ref()
+        .and_then(|quantize_opts| quantize(img, quantize_opts));
+
+    let mut raw = match indexed {
+        Some(indexed) => {
+            eprintln!("Optimized path: Encoding as indexed...");
+            let palette = indexed
+                .palette
+                .iter()
+                .map(|&[r, g, b, a]| RGBA8::new(r, g, b, a))
+                .collect();
+            RawImage::new(
+                img.width(),
+                img.height(),
+                oxipng::ColorType::Indexed { palette },
+                oxipng::BitDepth::Eight,
+                indexed.indices,
+            )
+        }
+        None => png_raw_image(img),
+    }
+    .map_err(png_error)?;
+
+    if let Some(icc) = &metadata.icc_profile {
+        raw.add_icc_profile(icc);
+    }
+    if !options.strip_metadata
+        && let Some(exif) = metadata
+            .exif
+            .as_deref()
+            .and_then(|exif| exif_for_output(exif, img.width(), img.height()))
+    {
+        raw.add_png_chunk(*b"eXIf", exif);
+    }
+
+    let mut png_opts = oxipng::Options::from_preset(options.level.min(6));
+    png_opts.strip = if options.strip_metadata {
+        StripChunks::Safe
+    } else {
+        StripChunks::None
+    };
+    if options.zopfli {
+        png_opts.deflate = Deflaters::Zopfli {
+            iterations: NonZeroU8::new(ZOPFLI_ITERATIONS).unwrap_or(NonZeroU8::MIN),
+        };
+    }
+    let png = raw.create_optimized_png(&png_opts).map_err(png_error)?;
+    eprintln!("Finished encoding PNG.");
+    Ok(png)
+}
+
+/// DynamicImage を oxipng の RawImage に変換する (16bit の画像は 16bit のまま)
+fn png_raw_image(img: &DynamicImage) -> Result<RawImage, oxipng::PngError> {
+    use oxipng::{BitDepth as PngBitDepth, ColorType as PngColorType};
+
+    let (width, height) = (img.width(), img.height());
+    let be_bytes = |raw: &[u16]| {
+        raw.iter()
+            .flat_map(|v| v.to_be_bytes())
+            .collect::<Vec<u8>>()
+    };
+    let gray = PngColorType::Grayscale {
+        transparent_shade: None,
+    };
+    let rgb = PngColorType::RGB {
+        transparent_color: None,
+    };
+    let (color_type, bit_depth, data) = match img {
+        DynamicImage::ImageLuma8(img) => (gray, PngBitDepth::Eight, img.as_raw().clone()),
+        DynamicImage::ImageLumaA8(img) => (
+            PngColorType::GrayscaleAlpha,
+            PngBitDepth::Eight,
+            img.as_raw().clone(),
+        ),
+        DynamicImage::ImageRgb8(img) => (rgb, PngBitDepth::Eight, img.as_raw().clone()),
+        DynamicImage::ImageRgba8(img) => {
+            (PngColorType::RGBA, PngBitDepth::Eight, img.as_raw().clone())
+        }
+        DynamicImage::ImageLuma16(img) => (gray, PngBitDepth::Sixteen, be_bytes(img.as_raw())),
+        DynamicImage::ImageLumaA16(img) => (
+            PngColorType::GrayscaleAlpha,
+            PngBitDepth::Sixteen,
+            be_bytes(img.as_raw()),
+        ),
+        DynamicImage::ImageRgb16(img) => (rgb, PngBitDepth::Sixteen, be_bytes(img.as_raw())),
+        _ if img.color().has_alpha() => (
+            PngColorType::RGBA,
+            PngBitDepth::Sixteen,
+            be_bytes(img.to_rgba16().as_raw()),
+        ),
+        _ => (rgb, PngBitDepth::Sixteen, be_bytes(img.to_rgb16().as_raw())),
+    };
+    RawImage::new(width, height, color_type, bit_depth, data)
+}
+
+/// WebP のバイト列に ICC プロファイル (ICCP チャンク) を埋め込みます。
+/// # 引数
+/// - `webp`: エンコード済みの WebP
+/// - `icc`: 埋め込む ICC プロファイル
+/// - `width`, `height`: 画像のサイズ (VP8X チャンクのキャンバスサイズに使用)
+/// # 戻り値
+/// - 成功した場合は拡張形式 (VP8X) の WebP のバイト列を返します。
+/// - 失敗した場合は `AppError` を返します。
+/// # 注意
+/// - シンプル形式 (VP8/VP8L のみ) の場合は VP8X チャンクを追加して拡張形式に変換します。
+fn embed_webp_icc_profile(
+    webp: &[u8],
+    icc: &[u8],
+    width: u32,
+    height: u32,
+) -> Result<Vec<u8>, AppError> {
+    if webp.len() < 20 || &webp[0..4] != b"RIFF" || &webp[8..12] != b"WEBP" {
+        return Err(AppError::Encode("Invalid WebP container".into()));
+    }
+    let chunks = &webp[12..];
+    let mut body: Vec<u8> = Vec::with_capacity(webp.len() + icc.len() + 32);
+
+    match &chunks[0..4] {
+        b"VP8X" => {
+            // 既存の VP8X の ICC フラグを立て、その直後に ICCP を挿入する
+            let vp8x_len = 8 + 10;
+            let mut vp8x = chunks[..vp8x_len].to_vec();
+            vp8x[8] |= 0x20;
+            body.extend_from_slice(&vp8x);
+            push_riff_chunk(&mut body, b"ICCP", icc);
+            body.extend_from_slice(&chunks[vp8x_len..]);
+        }
+        fourcc @ (b"VP8 " | b"VP8L") => {
+            let mut flags = 0x20u8;
+            // VP8L はヘッダーの alpha_is_used ビットを引き継ぐ
+            if fourcc == b"VP8L" && chunks.len() >= 13 {
+                let bits = u32::from_le_bytes([chunks[9], chunks[10], chunks[11], chunks[12]]);
+                if (bits >> 28) & 1 == 1 {
+                    flags |= 0x10;
+                }
+            }
+            let mut vp8x = vec![flags, 0, 0, 0];
+            vp8x.extend_from_slice(&(width - 1).to_le_bytes()[..3]);
+            vp8x.extend_from_slice(&(height - 1).to_le_bytes()[..3]);
+            push_riff_chunk(&mut body, b"VP8X", &vp8x);
+            push_riff_chunk(&mut body, b"ICCP", icc);
+            body.extend_from_slice(chunks);
+        }
+        _ => return Err(AppError::Encode("Unknown WebP chunk".into())),
+    }
+
+    let mut result = Vec::with_capacity(body.len() + 12);
+    result.extend_from_slice(b"RIFF");
+    result.extend_from_slice(&((body.len() + 4) as u32).to_le_bytes());
+    result.extend_from_slice(b"WEBP");
+    result.extend_from_slice(&body);
+    Ok(result)
+}
+
+/// RIFF チャンクを書き込む (奇数長の場合はパディングを追加)
+fn push_riff_chunk(out: &mut Vec<u8>, fourcc: &[u8; 4], payload: &[u8]) {
+    out.extend_from_slice(fourcc);
+    out.extend_from_slice(&(payload.len() as u32).to_le_bytes());
+    out.extend_from_slice(payload);
+    if payload.len() % 2 == 1 {
+        out.push(0);
+    }
+}
+
+/// `AvifOptions` を `ravif` 用の引数に変換して AVIF にエンコードします。
+#[cfg(feature = "avif")]
+fn encode_avif(img: &DynamicImage, avif_opts: &options::AvifOptions) -> Result<Vec<u8>, AppError> {
+    eprintln!("Adapter: Converting AvifOptions for ravif encoder...");
+    convert_dynamic_image_to_avif(
+        img,
+        avif_opts.quality,
+        avif_opts.bit_depth.to_ravif(),
+        avif_opts.alpha_quality,
+        avif_opts.speed,
+        avif_opts.color_model.to_ravif(),
+        avif_opts.threads,
+        avif_opts.alpha_color_mode.to_ravif(),
+    )
+}
+
 /// DynamicImage を AVIF 形式のバイトデータに変換する (raif クレート使用)
 ///
 /// # 引数
@@ -140,6 +758,7 @@ fn convert_dynamic_image_to_webp(
 /// * 失敗した場合はAppErrorを返します。
 /// # 注意
 /// * `ravif` クレートを使用してAVIFエンコードを行います。ビルド時に `libavif` ライブラリがシステムにインストールされている必要があります。
+#[cfg(feature = "avif")]
 fn convert_dynamic_image_to_avif(
     img: &DynamicImage,
     quality: f32,
@@ -177,7 +796,7 @@ fn convert_dynamic_image_to_avif(
     let encoded_avif = match img {
         // --- RGB8形式の場合 ---
         DynamicImage::ImageRgb8(rgb_image) => {
-            println!("Optimized path: Encoding as RGB...");
+            eprintln!("Optimized path: Encoding as RGB...");
             let width = rgb_image.width() as usize;
             let height = rgb_image.height() as usize;
 
@@ -190,7 +809,7 @@ fn convert_dynamic_image_to_avif(
         }
         // --- RGBA8形式の場合 ---
         DynamicImage::ImageRgba8(rgba_image) => {
-            println!("Standard path: Encoding as RGBA...");
+            eprintln!("Standard path: Encoding as RGBA...");
             let width = rgba_image.width() as usize;
             let height = rgba_image.height() as usize;
 
@@ -204,7 +823,7 @@ fn convert_dynamic_image_to_avif(
         // --- その他の形式の場合 (Luma8, Bgr8など) ---
         // 汎用的なRGBA8に変換してから処理する（フォールバック）
         _ => {
-            println!("Fallback path: Converting to RGBA then encoding...");
+            eprintln!("Fallback path: Converting to RGBA then encoding...");
             let rgba_image = img.to_rgba8();
             let width = rgba_image.width() as usize;
             let height = rgba_image.height() as usize;
@@ -215,7 +834,114 @@ fn convert_dynamic_image_to_avif(
             encoder.encode_rgba(image_view).map_err(AppError::Ravif)?
         }
     };
-    println!("Finished encoding AVIF.");
+    eprintln!("Finished encoding AVIF.");
 
     Ok(encoded_avif.avif_file)
 }
+
+// feature で無効化された形式は、エンコード時に UnsupportedFormat を返す
+
+#[cfg(not(feature = "avif"))]
+fn encode_avif(
+    _img: &DynamicImage,
+    _avif_opts: &options::AvifOptions,
+) -> Result<Vec<u8>, AppError> {
+    Err(AppError::disabled("avif", "avif"))
+}
+
+#[cfg(not(feature = "webp"))]
+fn convert_dynamic_image_to_webp(
+    _img: &DynamicImage,
+    _quality: f32,
+    _lossless: bool,
+) -> Result<Vec<u8>, AppError> {
+    Err(AppError::disabled("webp", "webp"))
+}
+
+#[cfg(not(feature = "jpeg"))]
+fn convert_dynamic_image_to_jpeg(
+    _img: &DynamicImage,
+    _options: &options::JpegOptions,
+    _metadata: &Metadata,
+) -> Result<Vec<u8>, AppError> {
+    Err(AppError::disabled("jpeg", "jpeg"))
+}
+
+#[cfg(all(test, feature = "jpeg"))]
+mod tests {
+    use super::*;
+    use crate::header::exif_orientation;
+    use image::RgbImage;
+
+    /// SOS までのマーカーをたどり、フレームの種類 (SOF0: 0xC0、SOF2: 0xC2 など) を返す
+    fn frame_marker(jpeg: &[u8]) -> Option<u8> {
+        let mut at = 2;
+        while at + 4 <= jpeg.len() && jpeg[at] == 0xFF {
+            let marker = jpeg[at + 1];
+            if matches!(marker, 0xC0..=0xCF) && !matches!(marker, 0xC4 | 0xC8 | 0xCC) {
+                return Some(marker);
+            }
+            if marker == 0xDA {
+                return None;
+            }
+            at += 2 + u16::from_be_bytes([jpeg[at + 2], jpeg[at + 3]]) as usize;
+        }
+        None
+    }
+
+    /// APP1 に埋め込まれた EXIF (TIFF 形式の生データ) を取り出す
+    fn app1_exif(jpeg: &[u8]) -> Option<&[u8]> {
+        let mut at = 2;
+        while at + 4 <= jpeg.len() && jpeg[at] == 0xFF && jpeg[at + 1] != 0xDA {
+            let length = u16::from_be_bytes([jpeg[at + 2], jpeg[at + 3]]) as usize;
+            let segment = jpeg.get(at + 4..at + 2 + length)?;
+            if jpeg[at + 1] == 0xE1 && segment.starts_with(b"Exif\0\0") {
+                return Some(&segment[6..]);
+            }
+            at += 2 + length;
+        }
+        None
+    }
+
+    fn gradient() -> DynamicImage {
+        DynamicImage::ImageRgb8(RgbImage::from_fn(64, 48, |x, y| {
+            image::Rgb([(x * 4) as u8, (y * 5) as u8, 128])
+        }))
+    }
+
+    #[test]
+    fn baseline_and_progressive_frames() {
+        let img = gradient();
+        for trellis in [false, true] {
+            let encode = |progressive, baseline| {
+                let options = options::JpegOptions {
+                    progressive,
+                    baseline,
+                    trellis,
+                    ..Default::default()
+                };
+                convert_dynamic_image_to_jpeg(&img, &options, &Metadata::default()).unwrap()
+            };
+            assert_eq!(frame_marker(&encode(true, false)), Some(0xC2));
+            assert_eq!(frame_marker(&encode(false, false)), Some(0xC0));
+            assert_eq!(frame_marker(&encode(true, true)), Some(0xC0));
+        }
+    }
+
+    #[test]
+    fn exif_orientation_is_reset() {
+        // Orientation = 6 だけを持つ EXIF (リトルエンディアン)
+        let mut exif = b"II*\0\x08\0\0\0\x01\0".to_vec();
+        exif.extend_from_slice(&[0x12, 0x01, 3, 0, 1, 0, 0, 0, 6, 0, 0, 0]);
+        exif.extend_from_slice(&[0, 0, 0, 0]);
+        assert_eq!(exif_orientation(&exif), Some(6));
+
+        let metadata = Metadata {
+            icc_profile: None,
+            exif: Some(exif),
+        };
+        let jpeg =
+            convert_dynamic_image_to_jpeg(&gradient(), &Default::default(), &metadata).unwrap();
+        assert_eq!(exif_orientation(app1_exif(&jpeg).unwrap()), Some(1));
+    }
+}
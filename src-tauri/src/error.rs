@@ -1,5 +1,15 @@
+use serde::ser::{Serialize, SerializeStruct, Serializer};
+use serde_json::{Map, Value, json};
+use std::error::Error as _;
+use std::io;
+use std::path::{Path, PathBuf};
 use thiserror::Error;
 
+/// 外部のライブラリのエラー (原因として保持し、`details.sources` に含める)
+pub type BoxError = Box<dyn std::error::Error + Send + Sync>;
+
+/// 変換処理のエラー
+/// フロントエンドには `{ code, message, details, path }` の形でシリアライズされる (`code` で種類を判別する)
 #[derive(Error, Debug)]
 pub enum AppError {
     #[error("Image decoding failed: {0}")]
@@ -8,20 +18,373 @@ pub enum AppError {
     #[error("Image encoding failed: {0}")]
     Encode(String),
 
+    /// 画像ライブラリ (image・libheif・jpeg2k など) のエラーでデコードに失敗した
+    #[error("Image decoding failed: {source}")]
+    DecodeSource {
+        #[source]
+        source: BoxError,
+    },
+
+    /// format: 出力形式 ("PNG" など)、source: エンコーダー (oxipng・mozjpeg など) のエラー
+    #[error("Image encoding failed: {format} encoding failed: {source}")]
+    EncodeSource {
+        format: &'static str,
+        #[source]
+        source: BoxError,
+    },
+
+    #[error("Image transform failed: {0}")]
+    Transform(String),
+
+    /// step: 失敗した処理 ("watermark font" など)、source: ライブラリ (ab_glyph など) のエラー
+    #[error("Image transform failed: {step}: {source}")]
+    TransformSource {
+        step: &'static str,
+        #[source]
+        source: BoxError,
+    },
+
+    #[error("Image comparison failed: {0}")]
+    Compare(String),
+
     // ravif::Errorを保持するためのバリアントを追加
+    #[cfg(feature = "avif")]
     #[error("AVIF encoding error: {0}")]
     Ravif(#[from] ravif::Error), //
 
     #[error("Filesystem error: {0}")]
     Io(#[from] std::io::Error), // std::io::Errorから自動変換
 
-    #[error("Unsupported format")]
-    UnsupportedFormat,
+    /// action: 失敗した操作 ("read", "write" など)、path: 対象のファイル
+    #[error("Failed to {action} {}: {source}", .path.display())]
+    File {
+        action: &'static str,
+        path: PathBuf,
+        #[source]
+        source: io::Error,
+    },
+
+    /// feature: 形式を無効にしている cargo の feature (このバージョンで対応していない形式の場合は None)
+    #[error("Unsupported format: {format}{}", feature_note(.feature))]
+    UnsupportedFormat {
+        format: String,
+        feature: Option<&'static str>,
+    },
+
+    /// 出力形式や設定の組み合わせで使えない機能 (ロスレス出力での目標サイズなど)
+    #[error("Unsupported feature: {0}")]
+    UnsupportedFeature(String),
+
+    /// limit: 超えた制限の名前 ("maxPixels" など)、actual: 実際の値 (デコーダーの内部で検出した場合は None)、max: 上限
+    #[error("Limit exceeded: {limit}{} (max {max})", actual_note(.actual))]
+    LimitExceeded {
+        limit: &'static str,
+        actual: Option<u64>,
+        max: u64,
+    },
+
+    /// 引数の値が不正 (ファイル名を取り出せないパスなど)
+    #[error("Invalid input: {0}")]
+    InvalidInput(String),
+
+    /// バックグラウンドの処理が完了しなかった (パニックなど)
+    #[error("Background task failed: {0}")]
+    Task(String),
+
+    /// 分離モードのワーカープロセスが異常終了した (ネイティブライブラリのクラッシュなど)
+    #[error("Worker process crashed: {0}")]
+    WorkerCrashed(String),
+
+    /// 分離モードのワーカープロセスが制限時間内に応答しなかった (プロセスは終了させる)
+    #[error("Worker process timed out after {seconds} seconds")]
+    WorkerTimeout { seconds: u64 },
+
+    /// 一括変換が中止された (`cancel_batch`)
+    #[error("Operation cancelled")]
+    Cancelled,
+
+    /// ワーカープロセスの中で発生したエラー (コード・メッセージ・詳細・パスを引き継ぐ)
+    #[error("{message}")]
+    Worker {
+        code: &'static str,
+        message: String,
+        details: Option<Value>,
+        path: Option<PathBuf>,
+    },
+}
+
+/// `AppError::code` が返すコードの一覧
+const CODES: [&str; 17] = [
+    "decode_failed",
+    "encode_failed",
+    "transform_failed",
+    "compare_failed",
+    "file_not_found",
+    "permission_denied",
+    "file_exists",
+    "disk_full",
+    "io_error",
+    "unsupported_format",
+    "unsupported_feature",
+    "limit_exceeded",
+    "cancelled",
+    "invalid_input",
+    "internal",
+    "worker_crashed",
+    "worker_timeout",
+];
+
+impl AppError {
+    /// cargo の feature で無効化された形式のエラーを作る
+    pub fn disabled(format: &str, feature: &'static str) -> Self {
+        AppError::UnsupportedFormat {
+            format: format.to_string(),
+            feature: Some(feature),
+        }
+    }
+
+    /// 画像ライブラリのデコードのエラーを、原因として保持する
+    pub fn decode(source: impl Into<BoxError>) -> Self {
+        AppError::DecodeSource {
+            source: source.into(),
+        }
+    }
+
+    /// エンコーダーのエラーを、出力形式と原因とともに保持する
+    pub fn encode(format: &'static str, source: impl Into<BoxError>) -> Self {
+        AppError::EncodeSource {
+            format,
+            source: source.into(),
+        }
+    }
+
+    /// 変換処理で使うライブラリのエラーを、処理の名前と原因とともに保持する
+    pub fn transform(step: &'static str, source: impl Into<BoxError>) -> Self {
+        AppError::TransformSource {
+            step,
+            source: source.into(),
+        }
+    }
+
+    /// ファイルの読み書きのエラーに、操作とパスを付け加える
+    pub fn file(action: &'static str, path: impl AsRef<Path>, source: io::Error) -> Self {
+        AppError::File {
+            action,
+            path: path.as_ref().to_path_buf(),
+            source,
+        }
+    }
+
+    /// エラーの種類を表す安定したコード (フロントエンドでの分岐や翻訳のキーに使う)
+    pub fn code(&self) -> &'static str {
+        match self {
+            AppError::Decode(_) | AppError::DecodeSource { .. } => "decode_failed",
+            #[cfg(feature = "avif")]
+            AppError::Ravif(_) => "encode_failed",
+            AppError::Encode(_) | AppError::EncodeSource { .. } => "encode_failed",
+            AppError::Transform(_) | AppError::TransformSource { .. } => "transform_failed",
+            AppError::Compare(_) => "compare_failed",
+            AppError::Io(source) | AppError::File { source, .. } => io_code(source),
+            AppError::UnsupportedFormat { .. } => "unsupported_format",
+            AppError::UnsupportedFeature(_) => "unsupported_feature",
+            AppError::LimitExceeded { .. } => "limit_exceeded",
+            AppError::InvalidInput(_) => "invalid_input",
+            AppError::Task(_) => "internal",
+            AppError::WorkerCrashed(_) => "worker_crashed",
+            AppError::WorkerTimeout { .. } => "worker_timeout",
+            AppError::Cancelled => "cancelled",
+            AppError::Worker { code, .. } => code,
+        }
+    }
+
+    /// 文字列のコードを `AppError::code` のコードに戻す (ワーカープロセスから受け取ったエラー用)
+    /// 知らないコードは "internal" になる
+    pub fn known_code(code: &str) -> &'static str {
+        CODES
+            .into_iter()
+            .find(|known| *known == code)
+            .unwrap_or("internal")
+    }
+
+    /// エラーの対象のファイル (パスが分かる場合のみ)
+    pub fn path(&self) -> Option<&Path> {
+        match self {
+            AppError::File { path, .. } => Some(path),
+            AppError::Worker { path, .. } => path.as_deref(),
+            _ => None,
+        }
+    }
+
+    /// 種類ごとの詳細と、原因のエラーのメッセージ (sources)
+    pub fn details(&self) -> Option<Value> {
+        let mut details = match self {
+            AppError::File { action, .. } => json!({ "action": action }),
+            AppError::UnsupportedFormat { format, feature } => {
+                json!({ "format": format, "feature": feature })
+            }
+            AppError::LimitExceeded { limit, actual, max } => {
+                json!({ "limit": limit, "actual": actual, "max": max })
+            }
+            AppError::WorkerTimeout { seconds } => json!({ "seconds": seconds }),
+            AppError::Worker { details, .. } => {
+                details.clone().unwrap_or_else(|| Value::Object(Map::new()))
+            }
+            _ => Value::Object(Map::new()),
+        };
+
+        let mut sources = Vec::new();
+        let mut source = self.source();
+        while let Some(error) = source {
+            sources.push(Value::String(error.to_string()));
+            source = error.source();
+        }
+        if !sources.is_empty() {
+            details["sources"] = Value::Array(sources);
+        }
+
+        details
+            .as_object()
+            .is_some_and(|map| !map.is_empty())
+            .then_some(details)
+    }
 }
 
-/// Tauriコマンドは String を返す必要があるため、変換を実装
-impl From<AppError> for String {
-    fn from(error: AppError) -> Self {
-        error.to_string()
+/// I/O エラーの種類ごとのコード (UI が容量不足や権限不足を区別できるように)
+fn io_code(error: &io::Error) -> &'static str {
+    match error.kind() {
+        io::ErrorKind::NotFound => "file_not_found",
+        io::ErrorKind::PermissionDenied => "permission_denied",
+        io::ErrorKind::AlreadyExists => "file_exists",
+        io::ErrorKind::StorageFull => "disk_full",
+        _ => "io_error",
+    }
+}
+
+/// LimitExceeded のメッセージに、実際の値を付け加える
+fn actual_note(actual: &Option<u64>) -> String {
+    actual
+        .map(|actual| format!(" is {}", actual))
+        .unwrap_or_default()
+}
+
+/// UnsupportedFormat のメッセージに、無効になっている feature を付け加える
+fn feature_note(feature: &Option<&'static str>) -> String {
+    feature
+        .map(|feature| format!(" (built without the `{}` feature)", feature))
+        .unwrap_or_default()
+}
+
+/// Tauriコマンドのエラーとして返せるよう、`{ code, message, details, path }` にシリアライズする
+impl Serialize for AppError {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut state = serializer.serialize_struct("AppError", 4)?;
+        state.serialize_field("code", self.code())?;
+        state.serialize_field("message", &self.to_string())?;
+        state.serialize_field("details", &self.details())?;
+        state.serialize_field("path", &self.path().map(|path| path.to_string_lossy()))?;
+        state.end()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::ImageError;
+    use image::error::{DecodingError, ImageFormatHint};
+
+    /// 全ての種類のエラー (種類を追加した場合はここにも追加する)
+    fn all_errors() -> Vec<AppError> {
+        let io_error = |kind| io::Error::new(kind, "test");
+        let mut errors = vec![
+            AppError::Decode("test".into()),
+            AppError::Encode("test".into()),
+            AppError::decode(io_error(io::ErrorKind::InvalidData)),
+            AppError::encode("PNG", io_error(io::ErrorKind::InvalidData)),
+            AppError::Transform("test".into()),
+            AppError::transform("test", io_error(io::ErrorKind::InvalidData)),
+            AppError::Compare("test".into()),
+            AppError::Io(io_error(io::ErrorKind::Other)),
+            AppError::disabled("avif", "avif"),
+            AppError::UnsupportedFeature("test".into()),
+            AppError::LimitExceeded {
+                limit: "maxPixels",
+                actual: Some(2),
+                max: 1,
+            },
+            AppError::InvalidInput("test".into()),
+            AppError::Task("test".into()),
+            AppError::WorkerCrashed("test".into()),
+            AppError::WorkerTimeout { seconds: 1 },
+            AppError::Cancelled,
+            AppError::Worker {
+                code: "decode_failed",
+                message: "test".into(),
+                details: None,
+                path: None,
+            },
+        ];
+        for kind in [
+            io::ErrorKind::NotFound,
+            io::ErrorKind::PermissionDenied,
+            io::ErrorKind::AlreadyExists,
+            io::ErrorKind::StorageFull,
+            io::ErrorKind::Other,
+        ] {
+            errors.push(AppError::file("read", "test.png", io_error(kind)));
+        }
+        #[cfg(feature = "avif")]
+        errors.push(AppError::Ravif(ravif::Error::TooFewPixels));
+        errors
+    }
+
+    #[test]
+    fn codes_round_trip() {
+        let errors = all_errors();
+        for error in &errors {
+            assert_eq!(
+                AppError::known_code(error.code()),
+                error.code(),
+                "{}",
+                error
+            );
+        }
+        // CODES の全てのコードは、いずれかの種類のエラーから返される
+        for code in CODES {
+            assert!(
+                code == "internal" || errors.iter().any(|error| error.code() == code),
+                "{}",
+                code
+            );
+        }
+        assert_eq!(AppError::known_code("no_such_code"), "internal");
+    }
+
+    #[test]
+    fn library_errors_are_kept_as_sources() {
+        let source = ImageError::Decoding(DecodingError::new(
+            ImageFormatHint::Unknown,
+            "broken header",
+        ));
+        let error = AppError::decode(source);
+        assert_eq!(error.code(), "decode_failed");
+        let details = error.details().unwrap();
+        assert!(
+            details["sources"][0]
+                .as_str()
+                .unwrap()
+                .contains("broken header")
+        );
+
+        let error = AppError::encode("PNG", io::Error::other("disk"));
+        assert_eq!(error.code(), "encode_failed");
+        assert_eq!(error.details().unwrap()["sources"][0], "disk");
+
+        let error = AppError::transform("watermark font", io::Error::other("bad glyph table"));
+        assert_eq!(error.code(), "transform_failed");
+        assert_eq!(
+            error.to_string(),
+            "Image transform failed: watermark font: bad glyph table"
+        );
+        assert_eq!(error.details().unwrap()["sources"][0], "bad glyph table");
     }
 }
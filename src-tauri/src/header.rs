@@ -0,0 +1,440 @@
+use crate::options::ExifSummary;
+use exif::{Exif, In, Reader as ExifReader, Tag, Value};
+use image::ImageFormat;
+
+/// TIFF の IFD をたどる最大数 (壊れたファイルで循環しないように)
+const MAX_TIFF_PAGES: u32 = 10000;
+
+/// 出力用に書き換える EXIF のタグ (Orientation、Exif IFD へのポインター、PixelX/YDimension)
+const TAG_ORIENTATION: u16 = 0x0112;
+const TAG_EXIF_IFD: u16 = 0x8769;
+const TAG_PIXEL_X: u16 = 0xA002;
+const TAG_PIXEL_Y: u16 = 0xA003;
+/// TIFF の値の型 (SHORT: 2 バイト、LONG: 4 バイト)
+const TIFF_SHORT: u16 = 3;
+const TIFF_LONG: u16 = 4;
+
+/// 画像全体をデコードせずに、フレーム数 (アニメーション) またはページ数 (TIFF) を数える
+/// # 引数
+/// - `format`: 画像の形式
+/// - `bytes`: 画像のバイトデータ
+/// # 戻り値
+/// - フレーム数を返します。数えられない形式や読み取りに失敗した場合は 1 を返します。
+/// # 注意
+/// - PNG は acTL チャンク、WebP は ANMF チャンク、GIF は画像ブロック、TIFF は IFD の数を数えます。
+pub fn frame_count(format: ImageFormat, bytes: &[u8]) -> u32 {
+    let count = match format {
+        ImageFormat::Png => png_frame_count(bytes),
+        ImageFormat::Gif => gif_frame_count(bytes),
+        ImageFormat::WebP => webp_frame_count(bytes),
+        ImageFormat::Tiff => tiff_page_count(bytes),
+        _ => None,
+    };
+    count.unwrap_or(1).max(1)
+}
+
+/// APNG の acTL チャンクからフレーム数を読み取る (IDAT より前に無ければ静止画)
+fn png_frame_count(bytes: &[u8]) -> Option<u32> {
+    let mut pos = 8;
+    while pos + 8 <= bytes.len() {
+        let length = u32::from_be_bytes(bytes[pos..pos + 4].try_into().ok()?) as usize;
+        match &bytes[pos + 4..pos + 8] {
+            b"acTL" => {
+                let data = bytes.get(pos + 8..pos + 12)?;
+                return Some(u32::from_be_bytes(data.try_into().ok()?));
+            }
+            b"IDAT" | b"IEND" => return Some(1),
+            _ => {}
+        }
+        // 長さ + 種類 + データ + CRC
+        pos = pos.checked_add(12 + length)?;
+    }
+    None
+}
+
+/// GIF のブロックをたどり、画像ブロック (Image Descriptor) の数を数える
+fn gif_frame_count(bytes: &[u8]) -> Option<u32> {
+    // ヘッダー (6) + 論理画面記述子 (7)
+    let flags = *bytes.get(10)?;
+    let mut pos = 13;
+    if flags & 0x80 != 0 {
+        pos += 3 << ((flags & 0x07) + 1);
+    }
+
+    let mut count = 0;
+    loop {
+        match *bytes.get(pos)? {
+            // Image Descriptor
+            0x2C => {
+                count += 1;
+                let flags = *bytes.get(pos + 9)?;
+                pos += 10;
+                if flags & 0x80 != 0 {
+                    pos += 3 << ((flags & 0x07) + 1);
+                }
+                // LZW の最小コードサイズの後にデータのサブブロックが続く
+                pos = skip_gif_sub_blocks(bytes, pos + 1)?;
+            }
+            // Extension
+            0x21 => pos = skip_gif_sub_blocks(bytes, pos + 2)?,
+            // Trailer
+            0x3B => return Some(count),
+            _ => return (count > 0).then_some(count),
+        }
+    }
+}
+
+/// GIF のサブブロックの列を読み飛ばし、終端 (長さ 0) の次の位置を返す
+fn skip_gif_sub_blocks(bytes: &[u8], mut pos: usize) -> Option<usize> {
+    loop {
+        let length = *bytes.get(pos)? as usize;
+        pos += 1;
+        if length == 0 {
+            return Some(pos);
+        }
+        pos += length;
+    }
+}
+
+/// WebP の ANMF チャンク (アニメーションのフレーム) の数を数える
+fn webp_frame_count(bytes: &[u8]) -> Option<u32> {
+    if bytes.get(0..4)? != b"RIFF" || bytes.get(8..12)? != b"WEBP" {
+        return None;
+    }
+    let mut pos = 12;
+    let mut count = 0;
+    while pos + 8 <= bytes.len() {
+        let length = u32::from_le_bytes(bytes[pos + 4..pos + 8].try_into().ok()?) as usize;
+        if &bytes[pos..pos + 4] == b"ANMF" {
+            count += 1;
+        }
+        // 奇数長のチャンクはパディングされる
+        pos = pos.checked_add(8 + length + (length & 1))?;
+    }
+    Some(count)
+}
+
+/// TIFF の IFD の連鎖をたどり、ページ数を数える
+fn tiff_page_count(bytes: &[u8]) -> Option<u32> {
+    let big_endian = match bytes.get(0..2)? {
+        b"II" => false,
+        b"MM" => true,
+        _ => return None,
+    };
+    let read_u16 = |pos: usize| -> Option<u16> {
+        let data = bytes.get(pos..pos + 2)?.try_into().ok()?;
+        Some(if big_endian {
+            u16::from_be_bytes(data)
+        } else {
+            u16::from_le_bytes(data)
+        })
+    };
+    let read_u32 = |pos: usize| -> Option<u32> {
+        let data = bytes.get(pos..pos + 4)?.try_into().ok()?;
+        Some(if big_endian {
+            u32::from_be_bytes(data)
+        } else {
+            u32::from_le_bytes(data)
+        })
+    };
+
+    let mut offset = read_u32(4)? as usize;
+    let mut count = 0;
+    while offset != 0 && count < MAX_TIFF_PAGES {
+        let entries = read_u16(offset)? as usize;
+        count += 1;
+        let next = read_u32(offset + 2 + entries * 12)? as usize;
+        // 後ろ向きの参照は循環とみなして打ち切る
+        if next <= offset {
+            break;
+        }
+        offset = next;
+    }
+    Some(count)
+}
+
+/// EXIF (TIFF 形式の生データ) を解析する
+fn parse_exif(exif: &[u8]) -> Option<Exif> {
+    ExifReader::new().read_raw(exif.to_vec()).ok()
+}
+
+/// EXIF から Orientation (1-8) を読み取る
+/// # 引数
+/// - `exif`: EXIF (TIFF 形式の生データ)
+/// # 戻り値
+/// - Orientation が記録されている場合はその値を返します。
+pub fn exif_orientation(exif: &[u8]) -> Option<u16> {
+    let exif = parse_exif(exif)?;
+    let value = exif
+        .get_field(Tag::Orientation, In::PRIMARY)?
+        .value
+        .get_uint(0)?;
+    (1..=8).contains(&value).then_some(value as u16)
+}
+
+/// EXIF の IFD1 に埋め込まれたサムネイル (JPEG) を取り出す
+/// # 引数
+/// - `exif`: EXIF (TIFF 形式の生データ)
+/// # 戻り値
+/// - サムネイルが埋め込まれている場合は、その JPEG のバイト列を返します。
+pub fn exif_thumbnail(exif: &[u8]) -> Option<&[u8]> {
+    let parsed = parse_exif(exif)?;
+    let offset = parsed
+        .get_field(Tag::JPEGInterchangeFormat, In::THUMBNAIL)?
+        .value
+        .get_uint(0)? as usize;
+    let length = parsed
+        .get_field(Tag::JPEGInterchangeFormatLength, In::THUMBNAIL)?
+        .value
+        .get_uint(0)? as usize;
+    // オフセットは TIFF ヘッダーの先頭からの位置
+    exif.get(offset..offset.checked_add(length)?)
+}
+
+/// 出力に引き継ぐ EXIF を、変換後の画像に合わせて書き換える
+/// # 引数
+/// - `exif`: EXIF (TIFF 形式の生データ、先頭に "Exif\0\0" が付いていても良い)
+/// - `width` / `height`: 出力画像のサイズ
+/// # 戻り値
+/// - 書き換えた EXIF (TIFF 形式の生データ) を返します。TIFF として読み取れない場合は None を返します。
+/// # 注意
+/// - 画素には変換時に Orientation を適用済みのため、Orientation を 1 (回転無し) にします。
+/// - PixelXDimension / PixelYDimension を出力のサイズに書き換えます。
+/// - 値はその場で書き換えるため、他の項目やオフセットは変わりません。
+pub fn exif_for_output(exif: &[u8], width: u32, height: u32) -> Option<Vec<u8>> {
+    let mut tiff = exif.strip_prefix(b"Exif\0\0").unwrap_or(exif).to_vec();
+    let big_endian = match tiff.get(0..2)? {
+        b"MM" => true,
+        b"II" => false,
+        _ => return None,
+    };
+    let ifd0 = tiff_u32(&tiff, 4, big_endian)? as usize;
+
+    if let Some(entry) = find_ifd_entry(&tiff, ifd0, TAG_ORIENTATION, big_endian) {
+        set_entry_value(&mut tiff, entry, 1, big_endian);
+    }
+    let exif_ifd = find_ifd_entry(&tiff, ifd0, TAG_EXIF_IFD, big_endian)
+        .and_then(|entry| tiff_u32(&tiff, entry + 8, big_endian));
+    if let Some(exif_ifd) = exif_ifd {
+        for (tag, value) in [(TAG_PIXEL_X, width), (TAG_PIXEL_Y, height)] {
+            if let Some(entry) = find_ifd_entry(&tiff, exif_ifd as usize, tag, big_endian) {
+                set_entry_value(&mut tiff, entry, value, big_endian);
+            }
+        }
+    }
+    Some(tiff)
+}
+
+fn tiff_u16(tiff: &[u8], at: usize, big_endian: bool) -> Option<u16> {
+    let bytes = tiff.get(at..at.checked_add(2)?)?.try_into().ok()?;
+    Some(if big_endian {
+        u16::from_be_bytes(bytes)
+    } else {
+        u16::from_le_bytes(bytes)
+    })
+}
+
+fn tiff_u32(tiff: &[u8], at: usize, big_endian: bool) -> Option<u32> {
+    let bytes = tiff.get(at..at.checked_add(4)?)?.try_into().ok()?;
+    Some(if big_endian {
+        u32::from_be_bytes(bytes)
+    } else {
+        u32::from_le_bytes(bytes)
+    })
+}
+
+/// IFD から指定したタグの項目 (12 バイト) の位置を探す
+fn find_ifd_entry(tiff: &[u8], ifd: usize, tag: u16, big_endian: bool) -> Option<usize> {
+    let count = tiff_u16(tiff, ifd, big_endian)? as usize;
+    (0..count)
+        .map(|i| ifd + 2 + i * 12)
+        .take_while(|&entry| entry + 12 <= tiff.len())
+        .find(|&entry| tiff_u16(tiff, entry, big_endian) == Some(tag))
+}
+
+/// 値が 1 つの SHORT / LONG の項目の値を書き換える
+/// SHORT に収まらない値は、同じ 4 バイトの領域に収まる LONG に型を変える
+fn set_entry_value(tiff: &mut [u8], entry: usize, value: u32, big_endian: bool) {
+    let value_type = tiff_u16(tiff, entry + 2, big_endian);
+    if tiff_u32(tiff, entry + 4, big_endian) != Some(1) {
+        return;
+    }
+    let (new_type, bytes): (u16, [u8; 4]) = match value_type {
+        Some(TIFF_SHORT) if value <= u16::MAX as u32 => {
+            let short = if big_endian {
+                (value as u16).to_be_bytes()
+            } else {
+                (value as u16).to_le_bytes()
+            };
+            (TIFF_SHORT, [short[0], short[1], 0, 0])
+        }
+        Some(TIFF_SHORT | TIFF_LONG) => (
+            TIFF_LONG,
+            if big_endian {
+                value.to_be_bytes()
+            } else {
+                value.to_le_bytes()
+            },
+        ),
+        _ => return,
+    };
+    let type_bytes = if big_endian {
+        new_type.to_be_bytes()
+    } else {
+        new_type.to_le_bytes()
+    };
+    tiff[entry + 2..entry + 4].copy_from_slice(&type_bytes);
+    tiff[entry + 8..entry + 12].copy_from_slice(&bytes);
+}
+
+/// EXIF から撮影情報などの主な項目を読み取る
+/// # 引数
+/// - `exif`: EXIF (TIFF 形式の生データ)
+/// # 戻り値
+/// - 解析できた場合は `ExifSummary` を返します (記録されていない項目は None)。
+pub fn exif_summary(exif: &[u8]) -> Option<ExifSummary> {
+    let exif = parse_exif(exif)?;
+    let text = |tag: Tag| -> Option<String> {
+        match &exif.get_field(tag, In::PRIMARY)?.value {
+            Value::Ascii(values) => {
+                let text = String::from_utf8_lossy(values.first()?).trim().to_string();
+                (!text.is_empty()).then_some(text)
+            }
+            _ => None,
+        }
+    };
+    let rational = |tag: Tag| -> Option<f64> {
+        match &exif.get_field(tag, In::PRIMARY)?.value {
+            Value::Rational(values) => values.first().map(|r| r.to_f64()),
+            _ => None,
+        }
+    };
+    let exposure_time = exif
+        .get_field(Tag::ExposureTime, In::PRIMARY)
+        .map(|field| field.display_value().to_string());
+    let iso = exif
+        .get_field(Tag::PhotographicSensitivity, In::PRIMARY)
+        .and_then(|field| field.value.get_uint(0));
+
+    Some(ExifSummary {
+        make: text(Tag::Make),
+        model: text(Tag::Model),
+        lens_model: text(Tag::LensModel),
+        software: text(Tag::Software),
+        date_time_original: text(Tag::DateTimeOriginal).or_else(|| text(Tag::DateTime)),
+        exposure_time,
+        f_number: rational(Tag::FNumber),
+        iso,
+        focal_length: rational(Tag::FocalLength),
+        gps_latitude: gps_coordinate(&exif, Tag::GPSLatitude, Tag::GPSLatitudeRef, b'S'),
+        gps_longitude: gps_coordinate(&exif, Tag::GPSLongitude, Tag::GPSLongitudeRef, b'W'),
+    })
+}
+
+/// GPS の度・分・秒を 10 進の度に変換する (南緯・西経は負の値)
+fn gps_coordinate(exif: &Exif, tag: Tag, ref_tag: Tag, negative: u8) -> Option<f64> {
+    let Value::Rational(values) = &exif.get_field(tag, In::PRIMARY)?.value else {
+        return None;
+    };
+    let [degrees, minutes, seconds] = values.get(0..3)? else {
+        return None;
+    };
+    let value = degrees.to_f64() + minutes.to_f64() / 60.0 + seconds.to_f64() / 3600.0;
+    let is_negative = match &exif.get_field(ref_tag, In::PRIMARY).map(|f| &f.value) {
+        Some(Value::Ascii(refs)) => refs.first().and_then(|r| r.first()) == Some(&negative),
+        _ => false,
+    };
+    Some(if is_negative { -value } else { value })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Orientation = 6 と、PixelX (SHORT) / PixelY (LONG) を持つ Exif IFD だけの EXIF を作る
+    fn tiff(big_endian: bool) -> Vec<u8> {
+        let u16_bytes = |v: u16| {
+            if big_endian {
+                v.to_be_bytes()
+            } else {
+                v.to_le_bytes()
+            }
+        };
+        let u32_bytes = |v: u32| {
+            if big_endian {
+                v.to_be_bytes()
+            } else {
+                v.to_le_bytes()
+            }
+        };
+        let entry = |tag: u16, value_type: u16, value: u32| {
+            let mut entry = Vec::new();
+            entry.extend_from_slice(&u16_bytes(tag));
+            entry.extend_from_slice(&u16_bytes(value_type));
+            entry.extend_from_slice(&u32_bytes(1));
+            if value_type == TIFF_SHORT {
+                entry.extend_from_slice(&u16_bytes(value as u16));
+                entry.extend_from_slice(&[0, 0]);
+            } else {
+                entry.extend_from_slice(&u32_bytes(value));
+            }
+            entry
+        };
+
+        let mut tiff = Vec::new();
+        tiff.extend_from_slice(if big_endian { b"MM" } else { b"II" });
+        tiff.extend_from_slice(&u16_bytes(42));
+        tiff.extend_from_slice(&u32_bytes(8));
+        // IFD0 (8 + 2 + 12 * 2 + 4 = 38 から Exif IFD)
+        tiff.extend_from_slice(&u16_bytes(2));
+        tiff.extend(entry(TAG_ORIENTATION, TIFF_SHORT, 6));
+        tiff.extend(entry(TAG_EXIF_IFD, TIFF_LONG, 38));
+        tiff.extend_from_slice(&u32_bytes(0));
+        // Exif IFD
+        tiff.extend_from_slice(&u16_bytes(2));
+        tiff.extend(entry(TAG_PIXEL_X, TIFF_SHORT, 4000));
+        tiff.extend(entry(TAG_PIXEL_Y, TIFF_LONG, 3000));
+        tiff.extend_from_slice(&u32_bytes(0));
+        tiff
+    }
+
+    fn pixel_dimensions(exif: &[u8]) -> (u32, u32) {
+        let exif = parse_exif(exif).unwrap();
+        let value = |tag| {
+            exif.get_field(tag, In::PRIMARY)
+                .and_then(|field| field.value.get_uint(0))
+                .unwrap()
+        };
+        (value(Tag::PixelXDimension), value(Tag::PixelYDimension))
+    }
+
+    #[test]
+    fn exif_for_output_resets_orientation_and_dimensions() {
+        for big_endian in [false, true] {
+            let exif = tiff(big_endian);
+            assert_eq!(exif_orientation(&exif), Some(6));
+            assert_eq!(pixel_dimensions(&exif), (4000, 3000));
+
+            let output = exif_for_output(&exif, 300, 400).unwrap();
+            assert_eq!(output.len(), exif.len());
+            assert_eq!(exif_orientation(&output), Some(1));
+            assert_eq!(pixel_dimensions(&output), (300, 400));
+        }
+    }
+
+    #[test]
+    fn exif_for_output_widens_short_dimensions() {
+        let output = exif_for_output(&tiff(false), 70000, 5).unwrap();
+        assert_eq!(pixel_dimensions(&output), (70000, 5));
+    }
+
+    #[test]
+    fn exif_for_output_strips_app1_prefix() {
+        let mut app1 = b"Exif\0\0".to_vec();
+        app1.extend(tiff(true));
+        let output = exif_for_output(&app1, 1, 1).unwrap();
+        assert!(output.starts_with(b"MM"));
+        assert_eq!(exif_orientation(&output), Some(1));
+        assert_eq!(exif_for_output(b"not a tiff", 1, 1), None);
+    }
+}
@@ -0,0 +1,496 @@
+//! デコードとエンコードを子プロセスで行う分離モード
+//!
+//! libheif・OpenJPEG・libwebp などの C のライブラリが壊れたファイルでクラッシュしても、
+//! アプリ全体が終了しないように、変換処理を同じ実行ファイルの子プロセス (ワーカー) で実行します。
+//! ワーカーが異常終了した場合は [`AppError::WorkerCrashed`]、制限時間を超えた場合は
+//! [`AppError::WorkerTimeout`] を返すため、呼び出し側はそのファイルを失敗として次に進めます。
+//!
+//! 実行ファイルの `main` の最初で [`run_worker_if_requested`] を呼び出してください。
+//!
+//! # プロトコル
+//! 標準入出力で、要求と応答を次のフレームとしてやり取りします。
+//!
+//! - ヘッダーの長さ (u32、リトルエンディアン) とヘッダー (JSON)
+//! - データの長さ (u64、リトルエンディアン) とデータ (入力画像または変換結果のバイト列、無い場合は長さ 0)
+//!
+//! ワーカーは要求を 1 つずつ処理し、標準入力が閉じられると終了します。
+
+use crate::cancel::{check_cancelled, is_cancelled};
+use crate::error::AppError;
+use crate::limits::{decode_limits, set_decode_limits};
+use crate::options::{
+    ConvertResult, DecodeLimits, EncodeOptions, FileConvertResult, IsolationOptions,
+    SizePolicyOptions,
+};
+use crate::pipeline;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::panic::{self, AssertUnwindSafe};
+use std::path::PathBuf;
+use std::process::{Child, ChildStdin, ChildStdout, Command, Stdio};
+use std::sync::{Mutex, mpsc};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// ワーカーとして起動するための引数
+pub const WORKER_ARG: &str = "--isolated-worker";
+
+/// 応答を待つ間に、一括変換の中止と制限時間を確かめる間隔
+const CANCEL_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// 待機中のワーカー (変換が終わるたびに戻し、次の変換で再利用する)
+static IDLE_WORKERS: Mutex<Vec<Worker>> = Mutex::new(Vec::new());
+
+/// ワーカーへの要求
+/// limits: 親プロセスで設定されているデコード時の上限
+#[derive(Serialize, Deserialize)]
+struct Request {
+    limits: DecodeLimits,
+    job: Job,
+}
+
+/// ワーカーで行う処理
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+enum Job {
+    /// データの画像を変換する (`pipeline::convert_with_report`)
+    Convert { options: EncodeOptions },
+    /// ファイルを変換して保存する (`pipeline::convert_file`)
+    ConvertFile {
+        input_path: String,
+        output_path: String,
+        options: EncodeOptions,
+        policy: SizePolicyOptions,
+    },
+}
+
+/// ワーカーからの応答
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "type", content = "value", rename_all = "camelCase")]
+enum Response {
+    /// 変換結果 (バイト列はデータとして送るため `data` は空)
+    Converted(ConvertResult),
+    FileConverted(FileConvertResult),
+    Error(WorkerError),
+}
+
+/// ワーカーで発生したエラー (`AppError` のシリアライズ結果と同じ形)
+#[derive(Serialize, Deserialize)]
+struct WorkerError {
+    code: String,
+    message: String,
+    details: Option<Value>,
+    path: Option<PathBuf>,
+}
+
+impl From<WorkerError> for AppError {
+    fn from(error: WorkerError) -> Self {
+        AppError::Worker {
+            code: AppError::known_code(&error.code),
+            message: error.message,
+            details: error.details,
+            path: error.path,
+        }
+    }
+}
+
+/// 画像のバイト列を子プロセスで変換し、出力サイズや使用した品質などの詳細とともに返します。
+/// # 引数
+/// - `data`: 変換対象の画像データのバイト列
+/// - `options`: エンコードオプション
+/// - `isolation`: 分離モードのオプション (制限時間)
+/// # 戻り値
+/// - 成功した場合は `ConvertResult` を返します。
+/// - 失敗した場合は `AppError` を返します (子プロセスの中のエラーは `AppError::Worker`)。
+/// # 注意
+/// - `codec::set_registry` で差し替えたレジストリは子プロセスには引き継がれません。
+pub fn convert_with_report(
+    data: &[u8],
+    options: EncodeOptions,
+    isolation: &IsolationOptions,
+) -> Result<ConvertResult, AppError> {
+    match run(Job::Convert { options }, data, isolation)? {
+        (Response::Converted(mut result), data) => {
+            result.data = data;
+            Ok(result)
+        }
+        (response, _) => Err(unexpected(response)),
+    }
+}
+
+/// 画像のバイト列を子プロセスで変換します。
+/// # 引数
+/// - `data`: 変換対象の画像データのバイト列
+/// - `options`: エンコードオプション
+/// - `isolation`: 分離モードのオプション (制限時間)
+/// # 戻り値
+/// - 成功した場合はエンコードされたバイト列を返します。
+/// - 失敗した場合は `AppError` を返します。
+pub fn convert(
+    data: &[u8],
+    options: EncodeOptions,
+    isolation: &IsolationOptions,
+) -> Result<Vec<u8>, AppError> {
+    convert_with_report(data, options, isolation).map(|result| result.data)
+}
+
+/// 画像ファイルを子プロセスで変換して保存します。
+/// # 引数
+/// - `input_path`: 変換対象の画像ファイルのパス
+/// - `output_path`: 保存先のファイルのパス
+/// - `options`: エンコードオプション
+/// - `policy`: サイズのポリシー
+/// - `isolation`: 分離モードのオプション (制限時間)
+/// # 戻り値
+/// - 成功した場合はファイルごとの結果を返します。
+/// - 失敗した場合は `AppError` を返します。
+/// # 注意
+/// - ファイルの読み書きも子プロセスで行います (入力画像を親プロセスに読み込みません)。
+/// - `cancel_batch` で中止された場合は、変換中のワーカーを終了させて `AppError::Cancelled` を返します。
+pub fn convert_file(
+    input_path: &str,
+    output_path: &str,
+    options: EncodeOptions,
+    policy: &SizePolicyOptions,
+    isolation: &IsolationOptions,
+) -> Result<FileConvertResult, AppError> {
+    check_cancelled()?;
+    let job = Job::ConvertFile {
+        input_path: input_path.to_string(),
+        output_path: output_path.to_string(),
+        options,
+        policy: *policy,
+    };
+    match run(job, &[], isolation)? {
+        (Response::FileConverted(result), _) => Ok(result),
+        (response, _) => Err(unexpected(response)),
+    }
+}
+
+/// 期待しない種類の応答をエラーにする (ワーカーのエラーはそのまま返す)
+fn unexpected(response: Response) -> AppError {
+    match response {
+        Response::Error(error) => error.into(),
+        _ => AppError::Task("Unexpected response from worker process".to_string()),
+    }
+}
+
+/// 待機中のワーカー (無ければ新しく起動したもの) に要求を送り、応答を待つ
+/// 失敗したワーカーは終了させ、再利用しない
+/// # 注意
+/// - 待機中に終了していたワーカーには要求を送れないため、新しいワーカーを起動して 1 度だけ送り直します
+///   (変換中のファイルが原因ではないため、`WorkerCrashed` にはしません)。
+fn run(
+    job: Job,
+    data: &[u8],
+    isolation: &IsolationOptions,
+) -> Result<(Response, Vec<u8>), AppError> {
+    // 一括変換の中止で終了させるのはファイルの変換のみ (ペーストなどの変換は中止しない)
+    let cancellable = matches!(job, Job::ConvertFile { .. });
+    let request = Request {
+        limits: decode_limits(),
+        job,
+    };
+    let mut worker = match idle_worker() {
+        Some(mut worker) => {
+            if worker.send(&request, data).is_err() {
+                eprintln!(
+                    "Isolation: Idle worker process {} has exited, starting a new one",
+                    worker.child.id()
+                );
+                drop(worker);
+                worker = Worker::spawn()?;
+                worker.send_or_crash(&request, data)?;
+            }
+            worker
+        }
+        None => {
+            let mut worker = Worker::spawn()?;
+            worker.send_or_crash(&request, data)?;
+            worker
+        }
+    };
+    let response = worker.receive(isolation.timeout_seconds, cancellable)?;
+    IDLE_WORKERS
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .push(worker);
+    Ok(response)
+}
+
+/// 待機中のワーカーを取り出す (既に終了しているワーカーは捨てる)
+fn idle_worker() -> Option<Worker> {
+    let mut idle = IDLE_WORKERS.lock().unwrap_or_else(|e| e.into_inner());
+    while let Some(mut worker) = idle.pop() {
+        if matches!(worker.child.try_wait(), Ok(None)) {
+            return Some(worker);
+        }
+        eprintln!(
+            "Isolation: Discarding exited worker process {}",
+            worker.child.id()
+        );
+    }
+    None
+}
+
+/// 起動したワーカープロセス
+struct Worker {
+    child: Child,
+    stdin: BufWriter<ChildStdin>,
+    // 応答の待機中は読み取り用のスレッドに渡すため Option
+    stdout: Option<BufReader<ChildStdout>>,
+}
+
+impl Worker {
+    /// 同じ実行ファイルをワーカーとして起動する (ログは標準エラー出力をそのまま使う)
+    fn spawn() -> Result<Self, AppError> {
+        let exe = std::env::current_exe()?;
+        let mut child = Command::new(&exe)
+            .arg(WORKER_ARG)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::inherit())
+            .spawn()
+            .map_err(|e| AppError::file("start", &exe, e))?;
+        let stdin = child.stdin.take().expect("stdin is piped");
+        let stdout = child.stdout.take().expect("stdout is piped");
+        eprintln!("Isolation: Started worker process {}", child.id());
+        Ok(Self {
+            child,
+            stdin: BufWriter::new(stdin),
+            stdout: Some(BufReader::new(stdout)),
+        })
+    }
+
+    /// 要求を送る (プロセスが終了していてパイプが閉じられている場合はエラー)
+    fn send(&mut self, request: &Request, data: &[u8]) -> io::Result<()> {
+        write_frame(&mut self.stdin, request, data)
+    }
+
+    /// 要求を送り、送れない場合は異常終了として扱う
+    fn send_or_crash(&mut self, request: &Request, data: &[u8]) -> Result<(), AppError> {
+        self.send(request, data).map_err(|_| self.crashed())
+    }
+
+    /// 応答を制限時間まで待つ (`cancellable` の場合は、一括変換が中止されるとプロセスを終了させる)
+    fn receive(
+        &mut self,
+        timeout_seconds: Option<u64>,
+        cancellable: bool,
+    ) -> Result<(Response, Vec<u8>), AppError> {
+        // 読み取りは別のスレッドで行い、制限時間を超えたらプロセスを終了させる
+        // (終了するとパイプが閉じ、読み取り用のスレッドも終わる)
+        let mut stdout = self.stdout.take().expect("worker is not in use");
+        let (sender, receiver) = mpsc::channel();
+        thread::spawn(move || {
+            let response = read_frame::<Response>(&mut stdout);
+            let _ = sender.send((stdout, response));
+        });
+        let deadline = timeout_seconds.map(|seconds| Instant::now() + Duration::from_secs(seconds));
+        let received = loop {
+            match receiver.recv_timeout(CANCEL_POLL_INTERVAL) {
+                Err(mpsc::RecvTimeoutError::Timeout) => {
+                    if cancellable && is_cancelled() {
+                        eprintln!(
+                            "Isolation: Batch cancelled, killing worker process {}",
+                            self.child.id()
+                        );
+                        self.kill();
+                        return Err(AppError::Cancelled);
+                    }
+                    if deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+                        break Err(mpsc::RecvTimeoutError::Timeout);
+                    }
+                }
+                received => break received,
+            }
+        };
+
+        match received {
+            Ok((stdout, Ok(Some(response)))) => {
+                self.stdout = Some(stdout);
+                Ok(response)
+            }
+            Err(mpsc::RecvTimeoutError::Timeout) => {
+                eprintln!(
+                    "Isolation: Worker process {} timed out, killing it",
+                    self.child.id()
+                );
+                self.kill();
+                Err(AppError::WorkerTimeout {
+                    seconds: timeout_seconds.unwrap_or_default(),
+                })
+            }
+            // パイプが閉じられた (プロセスが終了した) か、応答が壊れている
+            _ => Err(self.crashed()),
+        }
+    }
+
+    /// 終了したワーカーの終了状態 (シグナルや終了コード) をエラーにする
+    fn crashed(&mut self) -> AppError {
+        self.kill();
+        let status = match self.child.wait() {
+            Ok(status) => status.to_string(),
+            Err(e) => e.to_string(),
+        };
+        eprintln!(
+            "Isolation: Worker process {} crashed ({})",
+            self.child.id(),
+            status
+        );
+        AppError::WorkerCrashed(status)
+    }
+
+    fn kill(&mut self) {
+        // 既に終了している場合のエラーは無視する
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+impl Drop for Worker {
+    fn drop(&mut self) {
+        self.kill();
+    }
+}
+
+/// ワーカーとして起動された場合 (最初の引数が `WORKER_ARG`) は、要求を処理してプロセスを終了する
+/// # 注意
+/// - 通常の起動では何もせずに戻ります。引数の解析や GUI の初期化より前に呼び出してください。
+/// - 標準出力はプロトコルに使うため、ワーカーのログは標準エラー出力に出力されます。
+pub fn run_worker_if_requested() {
+    if std::env::args().nth(1).as_deref() != Some(WORKER_ARG) {
+        return;
+    }
+    let code = match run_worker() {
+        Ok(()) => 0,
+        Err(e) => {
+            eprintln!("Worker: {}", e);
+            1
+        }
+    };
+    std::process::exit(code);
+}
+
+/// 標準入力が閉じられるまで、要求を 1 つずつ処理して応答を返す
+fn run_worker() -> io::Result<()> {
+    let mut input = BufReader::new(io::stdin().lock());
+    let mut output = BufWriter::new(io::stdout().lock());
+    while let Some((request, data)) = read_frame::<Request>(&mut input)? {
+        set_decode_limits(request.limits);
+        // パニックはエラーとして返し、ワーカーは次の要求を待つ
+        let (response, data) =
+            panic::catch_unwind(AssertUnwindSafe(|| run_job(request.job, &data))).unwrap_or_else(
+                |_| {
+                    let error = AppError::Task("Worker panicked".to_string());
+                    (Response::Error(worker_error(&error)), Vec::new())
+                },
+            );
+        write_frame(&mut output, &response, &data)?;
+    }
+    Ok(())
+}
+
+/// 要求された処理を行い、応答とデータを返す
+fn run_job(job: Job, data: &[u8]) -> (Response, Vec<u8>) {
+    let result = match job {
+        Job::Convert { options } => {
+            pipeline::convert_with_report(data, options).map(|mut result| {
+                let data = std::mem::take(&mut result.data);
+                (Response::Converted(result), data)
+            })
+        }
+        Job::ConvertFile {
+            input_path,
+            output_path,
+            options,
+            policy,
+        } => pipeline::convert_file(&input_path, &output_path, options, &policy)
+            .map(|result| (Response::FileConverted(result), Vec::new())),
+    };
+    result.unwrap_or_else(|e| (Response::Error(worker_error(&e)), Vec::new()))
+}
+
+fn worker_error(error: &AppError) -> WorkerError {
+    WorkerError {
+        code: error.code().to_string(),
+        message: error.to_string(),
+        details: error.details(),
+        path: error.path().map(PathBuf::from),
+    }
+}
+
+/// ヘッダー (JSON) とデータを 1 つのフレームとして書き込む
+fn write_frame(writer: &mut impl Write, header: &impl Serialize, data: &[u8]) -> io::Result<()> {
+    let header = serde_json::to_vec(header)?;
+    writer.write_all(&(header.len() as u32).to_le_bytes())?;
+    writer.write_all(&header)?;
+    writer.write_all(&(data.len() as u64).to_le_bytes())?;
+    writer.write_all(data)?;
+    writer.flush()
+}
+
+/// フレームを 1 つ読み取る (フレームの先頭でパイプが閉じられた場合は None)
+fn read_frame<T: DeserializeOwned>(reader: &mut impl Read) -> io::Result<Option<(T, Vec<u8>)>> {
+    // 1 バイトも読めない場合だけを終わりとし、途中で閉じられたフレームはエラーにする
+    let mut length = [0; 4];
+    loop {
+        match reader.read(&mut length[..1]) {
+            Ok(0) => return Ok(None),
+            Ok(_) => break,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        }
+    }
+    reader.read_exact(&mut length[1..])?;
+    let mut header = vec![0; u32::from_le_bytes(length) as usize];
+    reader.read_exact(&mut header)?;
+    let mut length = [0; 8];
+    reader.read_exact(&mut length)?;
+    let mut data = vec![0; u64::from_le_bytes(length) as usize];
+    reader.read_exact(&mut data)?;
+    let header = serde_json::from_slice(&header)?;
+    Ok(Some((header, data)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn frames_round_trip() {
+        let mut buffer = Vec::new();
+        let first = serde_json::json!({ "type": "convert", "quality": 80 });
+        write_frame(&mut buffer, &first, b"image bytes").unwrap();
+        write_frame(&mut buffer, &"second", &[]).unwrap();
+
+        let mut reader = Cursor::new(buffer);
+        let (header, data) = read_frame::<Value>(&mut reader).unwrap().unwrap();
+        assert_eq!(header, first);
+        assert_eq!(data, b"image bytes");
+        let (header, data) = read_frame::<String>(&mut reader).unwrap().unwrap();
+        assert_eq!(header, "second");
+        assert!(data.is_empty());
+        // フレームの先頭で閉じられた場合は None
+        assert!(read_frame::<Value>(&mut reader).unwrap().is_none());
+    }
+
+    #[test]
+    fn truncated_frame_is_an_error() {
+        assert!(
+            read_frame::<Value>(&mut Cursor::new(Vec::new()))
+                .unwrap()
+                .is_none()
+        );
+
+        let mut buffer = Vec::new();
+        write_frame(&mut buffer, &"header", b"data").unwrap();
+        for length in [2, 6, buffer.len() - 1] {
+            let mut reader = Cursor::new(&buffer[..length]);
+            assert!(read_frame::<Value>(&mut reader).is_err(), "{}", length);
+        }
+    }
+}
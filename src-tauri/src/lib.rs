@@ -1,76 +1,65 @@
-mod decoder;
-pub mod error;
-
-use exif::{In, Reader as ExifReader, Tag};
-use image::{self, ImageFormat, RgbaImage, imageops::*};
-use std::io::Cursor;
-
-// 独自の形式を定義するためのenum
-pub enum DetectedFormat {
-    Heic,
-    // Exr,
-    Jpeg2000,
-    // imageクレートがサポートするその他の形式
-    Standard(ImageFormat),
-}
-
-/// バイトデータのマジックナンバーから画像形式を判別する
-pub fn detect_format(bytes: &[u8]) -> Option<DetectedFormat> {
-    // HEIC/AVIF (ISOBMFFコンテナ) のチェック
-    // ftyp ボックスが "heic", "heix", "avif" などを含むか
-    if bytes.len() > 12 && &bytes[4..8] == b"ftyp" {
-        let ftyp = &bytes[8..12];
-        if ftyp == b"heic" || ftyp == b"heix" || ftyp == b"hevc" || ftyp == b"heim" {
-            return Some(DetectedFormat::Heic);
-        }
-        // AVIFの判別もここに追加できる
-        if ftyp == b"avif" || ftyp == b"avis" {
-            // AVIFの場合はimageクレートが扱えるのでStandardに流す
-            if let Ok(format) = image::guess_format(bytes) {
-                return Some(DetectedFormat::Standard(format));
-            }
-        }
-    }
-    /*
-    // EXRのチェック
-    if bytes.starts_with(&[0x76, 0x2f, 0x31, 0x01]) {
-        return Some(DetectedFormat::Exr);
-    }
-    */
+//! DropCompressImage の変換処理
+//!
+//! デスクトップアプリ (Tauri) とコマンドライン版で共有する、デコード → 変形 → エンコードの処理をまとめたライブラリです。
+//! 他の Rust のツールからは次の API を利用できます。
+//!
+//! - 画像のデコードと、ヘッダーのみの読み取り: [`decode`] / [`probe`]
+//! - デコード時のサイズやメモリ量の上限: [`set_decode_limits`] / [`decode_limits`]
+//! - `DynamicImage` のエンコード: [`encode`]
+//! - 変形やサイズの目標を含む変換処理の全体: [`convert`] / [`convert_with_report`] / [`convert_file`]
+//! - ファイルに書き込まずに設定を確認するための、一部分のエンコード: [`preview_encode`]
+//! - 画質の比較とレスポンシブ画像の生成: [`compare_images`] / [`generate_variants`]
+//! - ファイル一覧に表示するプレビューの生成 (ディスクのキャッシュ付き): [`generate_preview`] / [`preview_file`]
+//! - 各処理のオプションと結果の型 (serde で camelCase にシリアライズされます): [`options`]
+//! - すべての処理が返すエラー: [`AppError`]
+//! - 入出力形式のデコーダー・エンコーダーと、その登録先: [`codec`]
+//! - ネイティブライブラリのクラッシュからアプリを守る、子プロセスでの変換: [`isolation`]
+//! - 一括変換 ([`convert_file`] の繰り返し) の中止: [`start_batch`] / [`cancel_batch`]
+//!
+//! # 例
+//! ```no_run
+//! use drop_compress_image_lib::options::{EncodeOptions, WebpOptions};
+//!
+//! let data = std::fs::read("input.jpg")?;
+//! let options = EncodeOptions {
+//!     webp: Some(WebpOptions {
+//!         quality: 80.0,
+//!         lossless: false,
+//!     }),
+//!     ..Default::default()
+//! };
+//! let webp = drop_compress_image_lib::convert(&data, options)?;
+//! std::fs::write("output.webp", webp)?;
+//! # Ok::<(), Box<dyn std::error::Error>>(())
+//! ```
 
-    // JPEG 2000のチェック
-    if bytes.starts_with(b"\x00\x00\x00\x0CjP  \r\n\x87\n") {
-        return Some(DetectedFormat::Jpeg2000);
-    }
-
-    // 上記のいずれでもない場合、imageクレートの形式推測に任せる
-    if let Ok(format) = image::guess_format(bytes) {
-        return Some(DetectedFormat::Standard(format));
-    }
-
-    None
-}
+pub mod codec;
+pub mod error;
+pub mod isolation;
+pub mod options;
 
-/// EXIF Orientation をもとに画像を回転・反転
-pub fn correct_orientation(img: &RgbaImage, data: &[u8]) -> RgbaImage {
-    if let Ok(exif) = ExifReader::new().read_from_container(&mut Cursor::new(data)) {
-        if let Some(field) = exif.get_field(Tag::Orientation, In::PRIMARY) {
-            match field.value.get_uint(0) {
-                Some(2) => flip_horizontal(img),
-                Some(3) => rotate180(img),
-                Some(4) => flip_vertical(img),
-                Some(5) => rotate90(&flip_horizontal(img)),
-                Some(6) => rotate90(img),
-                Some(7) => rotate270(&flip_horizontal(img)),
-                Some(8) => rotate270(img),
-                _ => img.clone(),
-            }
-        } else {
-            img.clone()
-        }
-    } else {
-        img.clone()
-    }
-}
+mod alpha;
+mod auto;
+mod cancel;
+mod color;
+mod decoder;
+mod encoder;
+mod header;
+mod limits;
+mod metrics;
+mod pipeline;
+mod preview;
+mod quantize;
+mod target;
+mod transform;
+mod variant;
+mod watermark;
 
-// End of file src-tauri/src/lib.rs
+pub use cancel::{cancel_batch, is_cancelled, start_batch};
+pub use decoder::{ColorProfile, decode, probe, read_color_profile, read_exif, read_thumbnail};
+pub use encoder::{Metadata, encode};
+pub use error::AppError;
+pub use limits::{decode_limits, set_decode_limits};
+pub use pipeline::{compare_images, convert, convert_file, convert_with_report, preview_encode};
+pub use preview::{generate_preview, preview_file};
+pub use variant::{Variant, VariantSet, generate_variants};
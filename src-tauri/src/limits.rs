@@ -0,0 +1,105 @@
+use crate::error::AppError;
+use crate::options::{ColorType, DecodeLimits, ImageInfo};
+use image::error::{ImageError, LimitErrorKind};
+use std::sync::{LazyLock, RwLock};
+
+static LIMITS: LazyLock<RwLock<DecodeLimits>> =
+    LazyLock::new(|| RwLock::new(DecodeLimits::default()));
+
+/// デコード時の資源の上限を設定する
+/// # 引数
+/// - `limits`: 以降のデコードで使う上限 (`DecodeLimits::unlimited()` で制限しない)
+/// # 注意
+/// - すべてのスレッドのデコードに適用されます。
+pub fn set_decode_limits(limits: DecodeLimits) {
+    *LIMITS.write().unwrap_or_else(|e| e.into_inner()) = limits;
+}
+
+/// 現在のデコード時の資源の上限
+pub fn decode_limits() -> DecodeLimits {
+    *LIMITS.read().unwrap_or_else(|e| e.into_inner())
+}
+
+/// ヘッダーから読み取った情報が上限に収まるかを確かめる
+/// メモリ量は、デコード結果を画素数 × チャンネル数 × チャンネルあたりのバイト数として見積もる
+pub(crate) fn check_info(info: &ImageInfo) -> Result<(), AppError> {
+    let channels = match info.color_type {
+        ColorType::Gray => 1,
+        ColorType::GrayAlpha => 2,
+        ColorType::Rgb => 3,
+        ColorType::Rgba | ColorType::Cmyk => 4,
+    };
+    check_size(info.width, info.height, channels, info.bit_depth)?;
+    let limits = decode_limits();
+    check(
+        "maxFrames",
+        info.frame_count as u64,
+        limits.max_frames.map(u64::from),
+    )
+}
+
+/// 幅・高さ・画素数・メモリ量が上限に収まるかを確かめる
+/// # 引数
+/// - `channels`: デコード結果のチャンネル数
+/// - `bit_depth`: 1 チャンネルあたりのビット数
+pub(crate) fn check_size(
+    width: u32,
+    height: u32,
+    channels: u64,
+    bit_depth: u8,
+) -> Result<(), AppError> {
+    let limits = decode_limits();
+    check("maxWidth", width as u64, limits.max_width.map(u64::from))?;
+    check("maxHeight", height as u64, limits.max_height.map(u64::from))?;
+    let pixels = width as u64 * height as u64;
+    check("maxPixels", pixels, limits.max_pixels)?;
+    let bytes_per_channel = bit_depth.max(1).div_ceil(8) as u64;
+    let alloc = pixels.saturating_mul(channels * bytes_per_channel);
+    check("maxAlloc", alloc, limits.max_alloc)
+}
+
+fn check(limit: &'static str, actual: u64, max: Option<u64>) -> Result<(), AppError> {
+    match max {
+        Some(max) if actual > max => Err(AppError::LimitExceeded {
+            limit,
+            actual: Some(actual),
+            max,
+        }),
+        _ => Ok(()),
+    }
+}
+
+/// image クレートのデコーダーに渡す上限 (ヘッダーの確認をすり抜けた場合の歯止め)
+pub(crate) fn image_limits() -> image::Limits {
+    let limits = decode_limits();
+    let mut image_limits = image::Limits::no_limits();
+    image_limits.max_image_width = limits.max_width;
+    image_limits.max_image_height = limits.max_height;
+    image_limits.max_alloc = limits.max_alloc;
+    image_limits
+}
+
+/// image クレートのエラーを変換する (上限を超えた場合は `AppError::LimitExceeded`)
+/// image クレートは超えた値を返さないため、actual は None になる
+pub(crate) fn image_error(error: ImageError) -> AppError {
+    let limits = decode_limits();
+    let exceeded = match &error {
+        ImageError::Limits(e) => match e.kind() {
+            LimitErrorKind::DimensionError => limits
+                .max_width
+                .map(|max| ("maxWidth", max as u64))
+                .or(limits.max_height.map(|max| ("maxHeight", max as u64))),
+            LimitErrorKind::InsufficientMemory => limits.max_alloc.map(|max| ("maxAlloc", max)),
+            _ => None,
+        },
+        _ => None,
+    };
+    match exceeded {
+        Some((limit, max)) => AppError::LimitExceeded {
+            limit,
+            actual: None,
+            max,
+        },
+        None => AppError::decode(error),
+    }
+}
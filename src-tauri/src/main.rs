@@ -1,11 +1,10 @@
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 mod command;
-mod decoder;
-mod encoder;
-mod error;
-mod options;
 
 fn main() {
+    // 分離モードのワーカーとして起動された場合は、GUI を初期化せずに変換処理だけを行う
+    drop_compress_image_lib::isolation::run_worker_if_requested();
+
     tauri::Builder::default()
         .plugin(tauri_plugin_opener::init())
         .plugin(tauri_plugin_dialog::init())
@@ -13,7 +12,19 @@ fn main() {
         // Vue から呼び出せるコマンド関数を登録
         .invoke_handler(tauri::generate_handler![
             command::convert,
-            command::parse_path
+            command::convert_with_report,
+            command::preview_encode,
+            command::convert_file,
+            command::convert_variants,
+            command::compare_images,
+            command::probe_image,
+            command::generate_preview,
+            command::parse_path,
+            command::list_codecs,
+            command::set_decode_limits,
+            command::get_decode_limits,
+            command::start_batch,
+            command::cancel_batch
         ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
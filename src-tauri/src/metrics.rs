@@ -0,0 +1,590 @@
+use crate::error::AppError;
+use crate::options::{CompareOptions, CompareResult, FitMode, QualityMetric, TransformOptions};
+use crate::transform::apply_transform;
+use base64::{Engine, engine::general_purpose::STANDARD as BASE64};
+use image::{DynamicImage, ImageFormat, RgbImage};
+use rgb::RGB8;
+use std::io::Cursor;
+
+/// 透明部分を合成する背景色 (両方の画像で同じ色を使う)
+const ALPHA_BACKGROUND: u8 = 128;
+
+/// 指定した指標で 2 つの画像の差を評価する
+/// # 引数
+/// - `metric`: 評価指標
+/// - `original`: 元の画像
+/// - `distorted`: 比較対象の画像 (エンコード結果をデコードしたものなど)
+/// # 戻り値
+/// - 成功した場合はスコアを返します。
+/// - 失敗した場合は `AppError` を返します。
+/// # 注意
+/// - 2 つの画像は同じサイズである必要があります。
+/// - アルファを持つ画像は灰色の背景に合成してから比較します。
+pub fn score(
+    metric: QualityMetric,
+    original: &DynamicImage,
+    distorted: &DynamicImage,
+) -> Result<f64, AppError> {
+    match metric {
+        QualityMetric::Ssimulacra2 => ssimulacra2(original, distorted),
+        QualityMetric::Dssim => dssim(original, distorted),
+    }
+}
+
+/// 2 つの画像を比較し、各種の指標と差分画像を返す
+/// # 引数
+/// - `original`: 元の画像
+/// - `modified`: 比較対象の画像 (変換後の画像など)
+/// - `options`: 比較のオプション
+/// # 戻り値
+/// - 成功した場合は `CompareResult` を返します。
+/// - 失敗した場合は `AppError` を返します。
+/// # 注意
+/// - サイズが異なる場合は、比較対象を元画像のサイズにリサイズしてから比較します。
+pub fn compare(
+    original: &DynamicImage,
+    modified: &DynamicImage,
+    options: &CompareOptions,
+) -> Result<CompareResult, AppError> {
+    let resized = (original.width(), original.height()) != (modified.width(), modified.height());
+    let aligned;
+    let modified = if resized {
+        eprintln!(
+            "Compare: Resizing {}x{} to {}x{}...",
+            modified.width(),
+            modified.height(),
+            original.width(),
+            original.height()
+        );
+        aligned = apply_transform(
+            modified.clone(),
+            &TransformOptions {
+                width: Some(original.width()),
+                height: Some(original.height()),
+                fit: FitMode::Fill,
+                ..Default::default()
+            },
+        )?;
+        &aligned
+    } else {
+        modified
+    };
+
+    let (a, b) = (flatten(original), flatten(modified));
+    eprintln!("Compare: Computing metrics...");
+    let heatmap = if options.heatmap {
+        Some(difference_heatmap(&a, &b, options.amplify)?)
+    } else {
+        None
+    };
+
+    Ok(CompareResult {
+        width: a.width(),
+        height: a.height(),
+        resized,
+        psnr: psnr(&a, &b),
+        ssim: ssim(&a, &b),
+        ssimulacra2: ssimulacra2(original, modified)?,
+        max_abs_error: max_abs_error(&a, &b),
+        heatmap,
+    })
+}
+
+/// PSNR (dB) を計算する (同一の場合は None)
+pub fn psnr(a: &RgbImage, b: &RgbImage) -> Option<f64> {
+    let sum: f64 = a
+        .as_raw()
+        .iter()
+        .zip(b.as_raw())
+        .map(|(&x, &y)| (x as f64 - y as f64).powi(2))
+        .sum();
+    let mse = sum / a.as_raw().len().max(1) as f64;
+    (mse > 0.0).then(|| 10.0 * (255.0 * 255.0 / mse).log10())
+}
+
+/// 輝度の SSIM を計算する (ガウス窓 σ = 1.5)
+pub fn ssim(a: &RgbImage, b: &RgbImage) -> f64 {
+    const C1: f32 = (0.01 * 255.0) * (0.01 * 255.0);
+    const C2: f32 = (0.03 * 255.0) * (0.03 * 255.0);
+    let (width, height) = (a.width() as usize, a.height() as usize);
+    let luma = |img: &RgbImage| -> Vec<f32> {
+        img.pixels()
+            .map(|p| 0.299 * p[0] as f32 + 0.587 * p[1] as f32 + 0.114 * p[2] as f32)
+            .collect()
+    };
+    let product =
+        |a: &[f32], b: &[f32]| -> Vec<f32> { a.iter().zip(b).map(|(a, b)| a * b).collect() };
+
+    let (y1, y2) = (luma(a), luma(b));
+    let mu1 = gaussian_blur(&y1, width, height);
+    let mu2 = gaussian_blur(&y2, width, height);
+    let s11 = gaussian_blur(&product(&y1, &y1), width, height);
+    let s22 = gaussian_blur(&product(&y2, &y2), width, height);
+    let s12 = gaussian_blur(&product(&y1, &y2), width, height);
+
+    let sum: f64 = (0..y1.len())
+        .map(|i| {
+            let (m1, m2) = (mu1[i], mu2[i]);
+            let num = (2.0 * m1 * m2 + C1) * (2.0 * (s12[i] - m1 * m2) + C2);
+            let den = (m1 * m1 + m2 * m2 + C1) * ((s11[i] - m1 * m1) + (s22[i] - m2 * m2) + C2);
+            (num / den) as f64
+        })
+        .sum();
+    sum / y1.len().max(1) as f64
+}
+
+/// チャンネルごとの最大絶対誤差 (R, G, B)
+pub fn max_abs_error(a: &RgbImage, b: &RgbImage) -> [u8; 3] {
+    let mut max = [0u8; 3];
+    for (p, q) in a.pixels().zip(b.pixels()) {
+        for c in 0..3 {
+            max[c] = max[c].max(p[c].abs_diff(q[c]));
+        }
+    }
+    max
+}
+
+/// 差分を増幅したヒートマップを PNG の data URI として作成する
+/// 差分なしは黒、差分が大きくなるにつれて赤 → 黄 → 白になる
+fn difference_heatmap(a: &RgbImage, b: &RgbImage, amplify: f32) -> Result<String, AppError> {
+    let heatmap = RgbImage::from_fn(a.width(), a.height(), |x, y| {
+        let (p, q) = (a.get_pixel(x, y), b.get_pixel(x, y));
+        let diff = (0..3).map(|c| p[c].abs_diff(q[c])).max().unwrap_or(0);
+        let v = (diff as f32 * amplify).clamp(0.0, 765.0);
+        image::Rgb([
+            v.min(255.0) as u8,
+            (v - 255.0).clamp(0.0, 255.0) as u8,
+            (v - 510.0).clamp(0.0, 255.0) as u8,
+        ])
+    });
+    let mut png = Vec::new();
+    heatmap
+        .write_to(&mut Cursor::new(&mut png), ImageFormat::Png)
+        .map_err(|e| AppError::encode("PNG", e))?;
+    Ok(format!("data:image/png;base64,{}", BASE64.encode(png)))
+}
+
+/// SSIMULACRA2 のスコアを計算する (100 が同一、高いほど高画質)
+/// # 注意
+/// - 目安として 90 以上で視覚的にほぼ劣化なし、70 前後で高品質、50 前後で中品質です。
+pub fn ssimulacra2(original: &DynamicImage, distorted: &DynamicImage) -> Result<f64, AppError> {
+    let (a, b) = (flatten(original), flatten(distorted));
+    check_dimensions(&a, &b)?;
+
+    let mut a = Planes::linear_rgb(&a);
+    let mut b = Planes::linear_rgb(&b);
+    // [スケール][チャンネル] = [SSIM 1-norm, SSIM 4-norm, 劣化 1-norm, 劣化 4-norm, 欠損 1-norm, 欠損 4-norm]
+    let mut averages = [[[0.0f64; 6]; 3]; SSIMULACRA2_SCALES];
+
+    for (scale, level) in averages.iter_mut().enumerate() {
+        if scale > 0 {
+            a = a.downsample();
+            b = b.downsample();
+        }
+        if a.width < 8 || a.height < 8 {
+            break;
+        }
+        let (xa, xb) = (a.to_xyb(), b.to_xyb());
+        for (c, avg) in level.iter_mut().enumerate() {
+            *avg = scale_averages(&xa.data[c], &xb.data[c], xa.width, xa.height);
+        }
+    }
+
+    let mut sum = 0.0;
+    for c in 0..3 {
+        for (scale, level) in averages.iter().enumerate() {
+            let avg = &level[c];
+            for n in 0..2 {
+                let i = ((c * SSIMULACRA2_SCALES + scale) * 2 + n) * 3;
+                sum += SSIMULACRA2_WEIGHTS[i] * avg[n].abs();
+                sum += SSIMULACRA2_WEIGHTS[i + 1] * avg[2 + n].abs();
+                sum += SSIMULACRA2_WEIGHTS[i + 2] * avg[4 + n].abs();
+            }
+        }
+    }
+
+    let mut s = sum * 0.956_238_261_683_484_4;
+    s = 2.326_765_642_916_932 * s - 0.020_884_521_182_843_837 * s * s
+        + 6.248_496_625_763_138e-5 * s * s * s;
+    Ok(if s > 0.0 {
+        100.0 - 10.0 * s.powf(0.627_633_646_783_138_7)
+    } else {
+        100.0
+    })
+}
+
+/// DSSIM の値を計算する (0 が同一、低いほど高画質)
+pub fn dssim(original: &DynamicImage, distorted: &DynamicImage) -> Result<f64, AppError> {
+    let (a, b) = (flatten(original), flatten(distorted));
+    check_dimensions(&a, &b)?;
+
+    let attr = dssim_core::Dssim::new();
+    let create = |img: &RgbImage| {
+        let pixels: &[RGB8] = bytemuck::cast_slice(img.as_raw());
+        attr.create_image_rgb(pixels, img.width() as usize, img.height() as usize)
+            .ok_or(AppError::Compare(
+                "Failed to prepare image for DSSIM".to_string(),
+            ))
+    };
+    let (value, _) = attr.compare(&create(&a)?, create(&b)?);
+    Ok(value.into())
+}
+
+/// 画像を RGB8 に変換する (アルファは灰色の背景に合成する)
+pub fn flatten(img: &DynamicImage) -> RgbImage {
+    if !img.color().has_alpha() {
+        return img.to_rgb8();
+    }
+    let rgba = img.to_rgba8();
+    RgbImage::from_fn(rgba.width(), rgba.height(), |x, y| {
+        let p = rgba.get_pixel(x, y);
+        let a = p[3] as u32;
+        let mix =
+            |c: usize| ((p[c] as u32 * a + ALPHA_BACKGROUND as u32 * (255 - a) + 127) / 255) as u8;
+        image::Rgb([mix(0), mix(1), mix(2)])
+    })
+}
+
+/// 2 つの画像のサイズが一致するか確認する
+fn check_dimensions(a: &RgbImage, b: &RgbImage) -> Result<(), AppError> {
+    if a.dimensions() != b.dimensions() {
+        return Err(AppError::Compare(format!(
+            "Image dimensions differ: {}x{} and {}x{}",
+            a.width(),
+            a.height(),
+            b.width(),
+            b.height()
+        )));
+    }
+    Ok(())
+}
+
+/// sRGB の 8bit 値をリニアに変換する
+pub fn srgb_to_linear(v: u8) -> f32 {
+    let v = v as f32 / 255.0;
+    if v <= 0.04045 {
+        v / 12.92
+    } else {
+        ((v + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+/// SSIMULACRA2 で評価するスケールの数
+const SSIMULACRA2_SCALES: usize = 6;
+
+/// SSIMULACRA2 の重み (チャンネル × スケール × ノルム × [SSIM, 劣化, 欠損])
+#[rustfmt::skip]
+const SSIMULACRA2_WEIGHTS: [f64; 108] = [
+    0.0, 0.0007376606707406586, 0.0,
+    0.0, 0.0007793481682867309, 0.0,
+    0.0, 0.0004371155730107379, 0.0,
+    1.1041726426657346, 0.00066284834129271, 0.00015231632783718752,
+    0.0, 0.0016406437456599754, 0.0,
+    1.8422455520539298, 11.441172603757666, 0.0,
+    0.0007989109436015163, 0.000176816438078653, 0.0,
+    1.8787594979546387, 10.94906990605142, 0.0,
+    0.0007289346991508072, 0.9677937080626833, 0.0,
+    0.00014003424285435884, 0.9981766977854967, 0.00031949755934435053,
+    0.0004550992113792063, 0.0, 0.0,
+    0.0013648766163243398, 0.0, 0.0,
+    0.0, 0.0, 0.0,
+    7.466890328078848, 0.0, 17.445833984131262,
+    0.0006235601634041466, 0.0, 0.0,
+    6.683678146179332, 0.00037724407979611296, 1.027889937768264,
+    225.20515300849274, 0.0, 0.0,
+    19.213238186143016, 0.0011401524586618361, 0.001237755635509985,
+    176.39317598450694, 0.0, 0.0,
+    24.43300999870476, 0.28520802612117757, 0.0004485436923833408,
+    0.0, 0.0, 0.0,
+    34.77906344483772, 44.835625328877896, 0.0,
+    0.0, 0.0, 0.0,
+    0.0, 0.0, 0.0,
+    0.0, 0.0008680556573291698, 0.0,
+    0.0, 0.0, 0.0,
+    0.0005313191874358747, 0.0, 0.00016533814161379112,
+    0.0, 0.0, 0.0,
+    0.0, 0.0, 0.0004179171803251336,
+    0.0017290828234722833, 0.0, 0.0020827005846636437,
+    0.0, 0.0, 8.826982764996862,
+    23.19243343998926, 0.0, 95.1080498811086,
+    0.9863978034400682, 0.9834382792465353, 0.0012286405048278493,
+    171.2667255897307, 0.9807858872435379, 0.0,
+    0.0, 0.0, 0.0005130064588990679,
+    0.0, 0.00010854057858411537, 0.0,
+];
+
+/// チャンネルごとの浮動小数点画像
+struct Planes {
+    width: usize,
+    height: usize,
+    data: [Vec<f32>; 3],
+}
+
+impl Planes {
+    /// sRGB 画像をリニア RGB に変換する
+    fn linear_rgb(img: &RgbImage) -> Self {
+        let lut: Vec<f32> = (0..=255).map(srgb_to_linear).collect();
+        let mut data = [Vec::new(), Vec::new(), Vec::new()];
+        for (c, plane) in data.iter_mut().enumerate() {
+            *plane = img.pixels().map(|p| lut[p[c] as usize]).collect();
+        }
+        Self {
+            width: img.width() as usize,
+            height: img.height() as usize,
+            data,
+        }
+    }
+
+    /// 2x2 画素の平均で 1/2 に縮小する
+    fn downsample(&self) -> Self {
+        let (width, height) = (self.width.div_ceil(2), self.height.div_ceil(2));
+        let data = self.data.clone().map(|plane| {
+            let mut out = vec![0.0; width * height];
+            for y in 0..height {
+                for x in 0..width {
+                    let mut sum = 0.0;
+                    for (dx, dy) in [(0, 0), (1, 0), (0, 1), (1, 1)] {
+                        let sx = (x * 2 + dx).min(self.width - 1);
+                        let sy = (y * 2 + dy).min(self.height - 1);
+                        sum += plane[sy * self.width + sx];
+                    }
+                    out[y * width + x] = sum / 4.0;
+                }
+            }
+            out
+        });
+        Self {
+            width,
+            height,
+            data,
+        }
+    }
+
+    /// リニア RGB から XYB (SSIMULACRA2 用に正の値へ調整したもの) に変換する
+    fn to_xyb(&self) -> Self {
+        const M: [[f32; 3]; 3] = [
+            [0.30, 0.622, 0.078],
+            [0.23, 0.692, 0.078],
+            [0.243_422_69, 0.204_767_44, 0.551_809_87],
+        ];
+        const BIAS: f32 = 0.003_793_073_3;
+        let bias_cbrt = BIAS.cbrt();
+
+        let len = self.width * self.height;
+        let mut data = [
+            Vec::with_capacity(len),
+            Vec::with_capacity(len),
+            Vec::with_capacity(len),
+        ];
+        for i in 0..len {
+            let rgb = [self.data[0][i], self.data[1][i], self.data[2][i]];
+            let mixed = M.map(|m| {
+                let v = m[0] * rgb[0] + m[1] * rgb[1] + m[2] * rgb[2] + BIAS;
+                v.max(0.0).cbrt() - bias_cbrt
+            });
+            let x = 0.5 * (mixed[0] - mixed[1]);
+            let y = 0.5 * (mixed[0] + mixed[1]);
+            let b = mixed[2];
+            data[0].push(x * 14.0 + 0.42);
+            data[1].push(y + 0.01);
+            data[2].push((b - y) + 0.55);
+        }
+        Self {
+            width: self.width,
+            height: self.height,
+            data,
+        }
+    }
+}
+
+/// 1 チャンネル・1 スケール分の SSIM マップとエッジ差分マップの平均を求める
+fn scale_averages(p1: &[f32], p2: &[f32], width: usize, height: usize) -> [f64; 6] {
+    const C2: f32 = 0.0009;
+    let product =
+        |a: &[f32], b: &[f32]| -> Vec<f32> { a.iter().zip(b).map(|(a, b)| a * b).collect() };
+
+    let mu1 = gaussian_blur(p1, width, height);
+    let mu2 = gaussian_blur(p2, width, height);
+    let s11 = gaussian_blur(&product(p1, p1), width, height);
+    let s22 = gaussian_blur(&product(p2, p2), width, height);
+    let s12 = gaussian_blur(&product(p1, p2), width, height);
+
+    let mut sums = [0.0f64; 6];
+    for i in 0..p1.len() {
+        let (m1, m2) = (mu1[i], mu2[i]);
+        let num_m = 1.0 - (m1 - m2) * (m1 - m2);
+        let num_s = 2.0 * (s12[i] - m1 * m2) + C2;
+        let denom_s = (s11[i] - m1 * m1) + (s22[i] - m2 * m2) + C2;
+        let d = (1.0 - num_m * num_s / denom_s).max(0.0) as f64;
+        sums[0] += d;
+        sums[1] += d.powi(4);
+
+        let edge = ((1.0 + (p2[i] - m2).abs()) / (1.0 + (p1[i] - m1).abs()) - 1.0) as f64;
+        let (artifact, detail_lost) = (edge.max(0.0), (-edge).max(0.0));
+        sums[2] += artifact;
+        sums[3] += artifact.powi(4);
+        sums[4] += detail_lost;
+        sums[5] += detail_lost.powi(4);
+    }
+
+    let n = p1.len() as f64;
+    [
+        sums[0] / n,
+        (sums[1] / n).sqrt().sqrt(),
+        sums[2] / n,
+        (sums[3] / n).sqrt().sqrt(),
+        sums[4] / n,
+        (sums[5] / n).sqrt().sqrt(),
+    ]
+}
+
+/// σ = 1.5 のガウスぼかし (分離可能フィルター、端は重みを正規化する)
+pub fn gaussian_blur(src: &[f32], width: usize, height: usize) -> Vec<f32> {
+    const SIGMA: f32 = 1.5;
+    const RADIUS: isize = 5;
+    let kernel: Vec<f32> = (-RADIUS..=RADIUS)
+        .map(|i| (-((i * i) as f32) / (2.0 * SIGMA * SIGMA)).exp())
+        .collect();
+
+    let pass = |src: &[f32], len: usize, lines: usize, at: &dyn Fn(usize, usize) -> usize| {
+        let mut out = vec![0.0; src.len()];
+        for line in 0..lines {
+            for pos in 0..len {
+                let (mut sum, mut weight) = (0.0, 0.0);
+                for (k, w) in kernel.iter().enumerate() {
+                    let p = pos as isize + k as isize - RADIUS;
+                    if p >= 0 && (p as usize) < len {
+                        sum += src[at(line, p as usize)] * w;
+                        weight += w;
+                    }
+                }
+                out[at(line, pos)] = sum / weight;
+            }
+        }
+        out
+    };
+
+    let horizontal = pass(src, width, height, &|y, x| y * width + x);
+    pass(&horizontal, height, width, &|x, y| y * width + x)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::Rgb;
+
+    /// 緩やかなグラデーションと細かい模様を持つ画像
+    fn sample(width: u32, height: u32) -> DynamicImage {
+        DynamicImage::ImageRgb8(RgbImage::from_fn(width, height, |x, y| {
+            let texture = if (x / 2 + y / 2) % 2 == 0 { 20 } else { 0 };
+            Rgb([
+                (x * 3) as u8 + texture,
+                (y * 3) as u8,
+                ((x + y) * 2) as u8 + texture,
+            ])
+        }))
+    }
+
+    /// 決まった乱数列で、各画素に ±amplitude のノイズを加える
+    fn with_noise(img: &DynamicImage, amplitude: i32) -> DynamicImage {
+        let mut state: u32 = 12345;
+        let mut rgb = img.to_rgb8();
+        for value in rgb.iter_mut() {
+            state = state.wrapping_mul(1_103_515_245).wrapping_add(12345);
+            let noise = (state >> 16) as i32 % (2 * amplitude + 1) - amplitude;
+            *value = (*value as i32 + noise).clamp(0, 255) as u8;
+        }
+        DynamicImage::ImageRgb8(rgb)
+    }
+
+    #[test]
+    fn identical_images_are_perfect() {
+        let img = sample(64, 48);
+        let result = compare(&img, &img, &CompareOptions::default()).unwrap();
+        assert_eq!(result.ssimulacra2, 100.0);
+        assert_eq!(result.psnr, None);
+        assert!((result.ssim - 1.0).abs() < 1e-6, "{}", result.ssim);
+        assert_eq!(result.max_abs_error, [0, 0, 0]);
+        assert!(!result.resized);
+        assert!(dssim(&img, &img).unwrap() < 1e-6);
+    }
+
+    #[test]
+    fn scores_drop_as_noise_increases() {
+        let img = sample(64, 64);
+        let results: Vec<_> = [2, 8, 32]
+            .into_iter()
+            .map(|amplitude| {
+                let noisy = with_noise(&img, amplitude);
+                let result = compare(&img, &noisy, &CompareOptions::default()).unwrap();
+                (result, dssim(&img, &noisy).unwrap())
+            })
+            .collect();
+        for pair in results.windows(2) {
+            let ((better, better_dssim), (worse, worse_dssim)) = (&pair[0], &pair[1]);
+            assert!(better.ssimulacra2 > worse.ssimulacra2);
+            assert!(better.psnr.unwrap() > worse.psnr.unwrap());
+            assert!(better.ssim > worse.ssim);
+            assert!(better_dssim < worse_dssim);
+            assert!(better.max_abs_error[0] <= worse.max_abs_error[0]);
+        }
+        assert!(results[0].0.ssimulacra2 < 100.0);
+    }
+
+    #[test]
+    fn scores_drop_as_jpeg_quality_decreases() {
+        let img = sample(64, 64);
+        let scores: Vec<f64> = [95, 60, 10]
+            .into_iter()
+            .map(|quality| {
+                let mut jpeg = Vec::new();
+                image::codecs::jpeg::JpegEncoder::new_with_quality(&mut jpeg, quality)
+                    .encode_image(&img)
+                    .unwrap();
+                let decoded = image::load_from_memory(&jpeg).unwrap();
+                ssimulacra2(&img, &decoded).unwrap()
+            })
+            .collect();
+        assert!(
+            scores[0] > scores[1] && scores[1] > scores[2],
+            "{:?}",
+            scores
+        );
+    }
+
+    #[test]
+    fn mismatched_sizes_are_rejected() {
+        let (a, b) = (sample(16, 16), sample(16, 12));
+        for result in [ssimulacra2(&a, &b), dssim(&a, &b)] {
+            assert!(matches!(result, Err(AppError::Compare(_))));
+        }
+    }
+
+    #[test]
+    fn compare_resizes_to_the_original() {
+        let original = sample(64, 48);
+        let options = CompareOptions {
+            heatmap: true,
+            ..Default::default()
+        };
+        let result = compare(&original, &sample(32, 24), &options).unwrap();
+        assert!(result.resized);
+        assert_eq!((result.width, result.height), (64, 48));
+        assert!(
+            result
+                .heatmap
+                .unwrap()
+                .starts_with("data:image/png;base64,")
+        );
+    }
+
+    #[test]
+    fn alpha_is_flattened_onto_gray() {
+        let img = DynamicImage::ImageRgba8(image::RgbaImage::from_pixel(
+            2,
+            2,
+            image::Rgba([255, 0, 0, 0]),
+        ));
+        assert_eq!(flatten(&img).get_pixel(0, 0), &Rgb([128, 128, 128]));
+    }
+}
@@ -1,4 +1,4 @@
-use ravif;
+use crate::codec::registry;
 use serde::{Deserialize, Serialize}; // 変換先の型にアクセスするために必要
 
 #[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
@@ -9,6 +9,7 @@ pub enum BitDepth {
 }
 
 // BitDepthに変換メソッドを実装
+#[cfg(feature = "avif")]
 impl BitDepth {
     pub fn to_ravif(self) -> ravif::BitDepth {
         match self {
@@ -26,6 +27,7 @@ pub enum ColorModel {
 }
 
 // ColorModelに変換メソッドを実装
+#[cfg(feature = "avif")]
 impl ColorModel {
     pub fn to_ravif(self) -> ravif::ColorModel {
         match self {
@@ -43,6 +45,7 @@ pub enum AlphaColorMode {
     Premultiplied,
 }
 
+#[cfg(feature = "avif")]
 impl AlphaColorMode {
     pub fn to_ravif(self) -> ravif::AlphaColorMode {
         match self {
@@ -67,7 +70,7 @@ impl AlphaColorMode {
 ///     ただし、入力画像が8ビット以上であっても、AVIFエンコード時にBitDepth::Eightを選択することも可能です。
 ///     逆に、10ビット以上の画像に対してBitDepth::Eightを選択すると、情報の損失が発生する可能性があります。
 ///    そのため、可能な限り入力画像のビット深度に合わせた設定を推奨します。
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct AvifOptions {
     pub lossless: bool,
@@ -80,24 +83,894 @@ pub struct AvifOptions {
     pub alpha_color_mode: AlphaColorMode,
 }
 
+impl Default for AvifOptions {
+    fn default() -> Self {
+        Self {
+            lossless: false,
+            quality: 80.0,
+            bit_depth: BitDepth::Auto,
+            alpha_quality: 80.0,
+            speed: 5,
+            color_model: ColorModel::YCbCr,
+            threads: None,
+            alpha_color_mode: AlphaColorMode::UnassociatedClean,
+        }
+    }
+}
+
 /// WebP形式のオプション
 /// quality: 0-100 (0は最低品質、100は最高品質)
 /// lossless: true/false (可逆圧縮を使うかどうか
 /// 注意: losslessがtrueの場合、qualityは無視される)
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct WebpOptions {
     pub quality: f32,
     pub lossless: bool,
 }
 
+impl Default for WebpOptions {
+    fn default() -> Self {
+        Self {
+            quality: 80.0,
+            lossless: false,
+        }
+    }
+}
+
+/// JPEG のクロマサブサンプリング
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ChromaSubsampling {
+    Yuv444,
+    #[default]
+    Yuv420,
+}
+
+/// JPEG形式のオプション (MozJPEG)
+/// quality: 1-100
+/// progressive: プログレッシブ JPEG にする
+/// subsampling: クロマサブサンプリング (4:4:4 / 4:2:0)
+/// optimize_coding: ハフマンテーブルを最適化する
+/// trellis: トレリス量子化を使う (サイズは小さくなるが遅くなる)
+/// baseline: ベースライン (非プログレッシブ) にする (progressive より優先)
+/// keep_metadata: 元画像の EXIF と ICC プロファイルを引き継ぐ (向きは画素に適用済みのため Orientation は 1 に、画素数は出力に合わせて書き換える)
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase", default)]
+pub struct JpegOptions {
+    pub quality: f32,
+    pub progressive: bool,
+    pub subsampling: ChromaSubsampling,
+    pub optimize_coding: bool,
+    pub trellis: bool,
+    pub baseline: bool,
+    pub keep_metadata: bool,
+}
+
+impl Default for JpegOptions {
+    fn default() -> Self {
+        Self {
+            quality: 80.0,
+            progressive: true,
+            subsampling: ChromaSubsampling::default(),
+            optimize_coding: true,
+            trellis: true,
+            baseline: false,
+            keep_metadata: true,
+        }
+    }
+}
+
+/// PNG の減色 (パレット化) のオプション
+/// max_colors: パレットの最大色数 (2-256)
+/// min_quality: 品質の下限 (0-100)。最大色数でもこれに届かない場合は減色せずに可逆圧縮する
+/// max_quality: 品質の上限 (0-100)。これに届く最も少ない色数を選ぶ (100 の場合は常に最大色数)
+/// dithering: Floyd-Steinberg ディザリングの強さ (0.0 で無効、1.0 で最大)
+/// 品質は SSIMULACRA2 のスコアで判定する (スコアを計算できない小さな画像は可逆圧縮する)
+#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
+#[serde(rename_all = "camelCase", default)]
+pub struct PngQuantizeOptions {
+    pub max_colors: u16,
+    pub min_quality: f32,
+    pub max_quality: f32,
+    pub dithering: f32,
+}
+
+impl Default for PngQuantizeOptions {
+    fn default() -> Self {
+        Self {
+            max_colors: 256,
+            min_quality: 0.0,
+            max_quality: 100.0,
+            dithering: 1.0,
+        }
+    }
+}
+
+/// PNG形式のオプション (oxipng)
+/// level: 最適化レベル (0-6、大きいほどフィルターと圧縮の探索が増えて遅くなる)
+/// zopfli: Zopfli で圧縮する (非常に遅いがサイズはさらに小さくなる)
+/// strip_metadata: 表示に影響しないチャンクを削除する (ICC プロファイルは残す)
+/// quantize: 減色のオプション (None の場合は可逆圧縮)
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase", default)]
+pub struct PngOptions {
+    pub level: u8,
+    pub zopfli: bool,
+    pub strip_metadata: bool,
+    pub quantize: Option<PngQuantizeOptions>,
+}
+
+impl Default for PngOptions {
+    fn default() -> Self {
+        Self {
+            level: 2,
+            zopfli: false,
+            strip_metadata: true,
+            quantize: None,
+        }
+    }
+}
+
+/// カラーマネジメントの方式
+/// Srgb: 埋め込みプロファイルから sRGB に変換する
+/// Preserve: 画素値を変換せず、プロファイルを出力に埋め込んで広色域を保持する
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ColorMode {
+    #[default]
+    Srgb,
+    Preserve,
+}
+
+/// レンダリングインテント (ICC 仕様の値に対応)
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RenderingIntent {
+    #[default]
+    Perceptual,
+    RelativeColorimetric,
+    Saturation,
+    AbsoluteColorimetric,
+}
+
+impl RenderingIntent {
+    pub fn to_qcms(self) -> qcms::Intent {
+        match self {
+            RenderingIntent::Perceptual => qcms::Intent::Perceptual,
+            RenderingIntent::RelativeColorimetric => qcms::Intent::RelativeColorimetric,
+            RenderingIntent::Saturation => qcms::Intent::Saturation,
+            RenderingIntent::AbsoluteColorimetric => qcms::Intent::AbsoluteColorimetric,
+        }
+    }
+}
+
+/// カラーマネジメントのオプション
+/// mode: 変換方式 (ColorMode::Srgb, ColorMode::Preserve)
+/// intent: sRGB へ変換する際のレンダリングインテント
+/// 注意: 出力形式がプロファイルの埋め込みに対応していない場合 (AVIF)、Preserve でも sRGB に変換されます。
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct ColorOptions {
+    pub mode: ColorMode,
+    pub intent: RenderingIntent,
+}
+
+/// 幅と高さを両方指定した場合の合わせ方
+/// Contain: アスペクト比を保って枠内に収める
+/// Cover: アスペクト比を保って枠を覆い、はみ出した部分を中央で切り抜く
+/// Fill: アスペクト比を無視して指定サイズに引き伸ばす
+/// Inside: Contain と同じだが拡大はしない (縮小のみ)
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FitMode {
+    #[default]
+    Contain,
+    Cover,
+    Fill,
+    Inside,
+}
+
+/// リサンプリングフィルター
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ResizeFilter {
+    #[default]
+    Lanczos3,
+    Mitchell,
+    CatmullRom,
+}
+
+/// 変形処理 (リサイズ前に指定順で適用される)
+/// Crop: 指定範囲を切り抜く
+/// Rotate90 / Rotate180 / Rotate270: 時計回りに回転する
+/// FlipHorizontal / FlipVertical: 左右 / 上下を反転する
+/// Trim: 単色または完全に透明な余白を取り除く (tolerance: 背景色とみなす色差の許容値)
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum TransformOperation {
+    Crop {
+        x: u32,
+        y: u32,
+        width: u32,
+        height: u32,
+    },
+    Rotate90,
+    Rotate180,
+    Rotate270,
+    FlipHorizontal,
+    FlipVertical,
+    Trim {
+        #[serde(default)]
+        tolerance: u8,
+    },
+}
+
+/// 変形のオプション
+/// operations: リサイズ前に指定順で適用する変形処理
+/// max_width / max_height: 最大サイズ (超える場合のみアスペクト比を保って縮小)
+/// width / height: 出力サイズ (片方のみの場合はアスペクト比を維持)
+/// fit: 幅と高さを両方指定した場合の合わせ方
+/// filter: リサンプリングフィルター
+/// linear: リニアライトで処理する (ガンマ補正済み縮小)
+/// premultiply_alpha: 乗算済みアルファで処理する (透明部分の色の滲みを防ぐ)
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase", default)]
+pub struct TransformOptions {
+    pub operations: Vec<TransformOperation>,
+    pub max_width: Option<u32>,
+    pub max_height: Option<u32>,
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    pub fit: FitMode,
+    pub filter: ResizeFilter,
+    pub linear: bool,
+    pub premultiply_alpha: bool,
+}
+
+impl Default for TransformOptions {
+    fn default() -> Self {
+        Self {
+            operations: Vec::new(),
+            max_width: None,
+            max_height: None,
+            width: None,
+            height: None,
+            fit: FitMode::default(),
+            filter: ResizeFilter::default(),
+            linear: true,
+            premultiply_alpha: true,
+        }
+    }
+}
+
+/// アルファチャンネルのオプション
+/// drop_opaque: 全ての画素が不透明な場合はアルファを取り除いて RGB でエンコードする
+/// background: 指定した背景色 (RGB) に合成して透明部分を無くす
+/// clean_transparent: 完全に透明な画素の色を揃える (見えない色情報を捨てて圧縮率を上げる)
+///   ロスレスの出力でも元の色情報が失われるため、既定では無効
+/// 乗算済みアルファでの処理は TransformOptions の premultiply_alpha と AvifOptions の alpha_color_mode で指定する
+#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
+#[serde(rename_all = "camelCase", default)]
+pub struct AlphaOptions {
+    pub drop_opaque: bool,
+    pub background: Option<[u8; 3]>,
+    pub clean_transparent: bool,
+}
+
+impl Default for AlphaOptions {
+    fn default() -> Self {
+        Self {
+            drop_opaque: true,
+            background: None,
+            clean_transparent: false,
+        }
+    }
+}
+
+/// 目標ファイルサイズのオプション (WebP / AVIF / JPEG の非可逆圧縮)
+/// max_bytes: 出力の最大サイズ (バイト、必ず 1 以上を指定する)
+/// min_quality: 探索する品質の下限 (上限は各形式のオプションの quality)
+/// allow_downscale: 品質の下限でも収まらない場合に縮小を許可する
+#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
+#[serde(rename_all = "camelCase", default)]
+pub struct TargetSizeOptions {
+    pub max_bytes: u64,
+    pub min_quality: f32,
+    pub allow_downscale: bool,
+}
+
+impl Default for TargetSizeOptions {
+    fn default() -> Self {
+        Self {
+            max_bytes: 0,
+            min_quality: default_min_quality(),
+            allow_downscale: false,
+        }
+    }
+}
+
+/// 知覚品質の評価指標
+/// Ssimulacra2: 100 が同一、高いほど高画質 (目安: 90 で劣化がほぼ分からない、70 で高品質)
+/// Dssim: 0 が同一、低いほど高画質
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum QualityMetric {
+    #[default]
+    Ssimulacra2,
+    Dssim,
+}
+
+impl QualityMetric {
+    /// スコアが目標を満たしているか
+    pub fn meets(self, score: f64, target: f64) -> bool {
+        match self {
+            QualityMetric::Ssimulacra2 => score >= target,
+            QualityMetric::Dssim => score <= target,
+        }
+    }
+}
+
+/// 知覚品質の目標のオプション (WebP / AVIF / JPEG の非可逆圧縮)
+/// metric: 評価指標
+/// score: 目標スコア
+/// min_quality: 探索する品質の下限 (上限は各形式のオプションの quality)
+/// 注意: target_size も指定されている場合は target_size が優先されます。
+#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
+#[serde(rename_all = "camelCase")]
+pub struct TargetQualityOptions {
+    #[serde(default)]
+    pub metric: QualityMetric,
+    pub score: f64,
+    #[serde(default = "default_min_quality")]
+    pub min_quality: f32,
+}
+
+fn default_min_quality() -> f32 {
+    10.0
+}
+
+/// 自動選択の候補
+/// WebpLossy: WebP 非可逆圧縮
+/// WebpLossless: WebP 可逆圧縮
+/// Avif: AVIF
+/// Original: 元のファイルをそのまま使う (変形・透かし・背景色への合成を行わない場合のみ)
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AutoCandidate {
+    WebpLossy,
+    WebpLossless,
+    Avif,
+    Original,
+}
+
+impl AutoCandidate {
+    /// 非可逆圧縮の候補か (知覚品質の閾値の対象になる)
+    pub fn is_lossy(self) -> bool {
+        matches!(self, AutoCandidate::WebpLossy | AutoCandidate::Avif)
+    }
+
+    /// 候補の出力形式 (Original の場合は入力の形式のため None)
+    pub fn format(self) -> Option<OutputFormat> {
+        match self {
+            AutoCandidate::WebpLossy | AutoCandidate::WebpLossless => Some(OutputFormat::Webp),
+            AutoCandidate::Avif => Some(OutputFormat::Avif),
+            AutoCandidate::Original => None,
+        }
+    }
+}
+
+/// 出力形式の自動選択のオプション
+/// candidates: 比較する候補
+/// quality: 非可逆圧縮の候補の品質 (webp / avif のオプションが指定されている場合はそちらを使う)
+/// min_score: 非可逆圧縮の候補に求める SSIMULACRA2 のスコア (下回る候補は選ばない)
+/// 注意: auto を指定した場合、target_size と target_quality は無視されます。
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase", default)]
+pub struct AutoOptions {
+    pub candidates: Vec<AutoCandidate>,
+    pub quality: f32,
+    pub min_score: Option<f64>,
+}
+
+impl Default for AutoOptions {
+    fn default() -> Self {
+        Self {
+            candidates: vec![
+                AutoCandidate::WebpLossy,
+                AutoCandidate::WebpLossless,
+                AutoCandidate::Avif,
+                AutoCandidate::Original,
+            ],
+            quality: 80.0,
+            min_score: None,
+        }
+    }
+}
+
+/// 透かしの配置位置
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "camelCase")]
+pub enum Anchor {
+    TopLeft,
+    Top,
+    TopRight,
+    Left,
+    Center,
+    Right,
+    BottomLeft,
+    Bottom,
+    #[default]
+    BottomRight,
+}
+
+/// 透かし (ロゴ画像またはテキスト) のオプション
+/// image_path: 重ねる画像のパス (text より優先)
+/// text: 重ねる文字列
+/// font_path: テキストに使用するフォントファイル (未指定の場合は同梱の DejaVu Sans)
+/// color: テキストの色 (RGB)
+/// anchor: 配置位置
+/// margin: 画像の端からの余白 (px)、タイル表示の場合は間隔
+/// scale: 透かしの幅 (画像の幅に対する割合)
+/// opacity: 不透明度 (0.0〜1.0)
+/// tile: 画像全体に敷き詰める
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase", default)]
+pub struct WatermarkOptions {
+    pub image_path: Option<String>,
+    pub text: Option<String>,
+    pub font_path: Option<String>,
+    pub color: [u8; 3],
+    pub anchor: Anchor,
+    pub margin: u32,
+    pub scale: f32,
+    pub opacity: f32,
+    pub tile: bool,
+}
+
+impl Default for WatermarkOptions {
+    fn default() -> Self {
+        Self {
+            image_path: None,
+            text: None,
+            font_path: None,
+            color: [255, 255, 255],
+            anchor: Anchor::default(),
+            margin: 16,
+            scale: 0.2,
+            opacity: 0.5,
+            tile: false,
+        }
+    }
+}
+
 /// 全てのエンコードオプションをまとめる親構造体
-#[derive(Serialize, Deserialize, Debug)]
+/// 出力形式ごとに 1 つのフィールドを持つ (形式の追加には、ここへのフィールドの追加が必要)
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
 #[serde(rename_all = "camelCase")]
 pub struct EncodeOptions {
     pub avif: Option<AvifOptions>,
     pub webp: Option<WebpOptions>,
-    // 将来的にJPEG EXRのオプションもここに追加できる
+    pub jpeg: Option<JpegOptions>,
+    pub png: Option<PngOptions>,
+    pub color: Option<ColorOptions>,
+    pub transform: Option<TransformOptions>,
+    pub watermark: Option<WatermarkOptions>,
+    pub alpha: Option<AlphaOptions>,
+    pub target_size: Option<TargetSizeOptions>,
+    pub target_quality: Option<TargetQualityOptions>,
+    pub auto: Option<AutoOptions>,
+    // 将来的にEXRのオプションもここに追加できる
+}
+
+impl EncodeOptions {
+    /// 出力形式が ICC プロファイルの埋め込みに対応しているか
+    pub fn supports_icc_profile(&self) -> bool {
+        registry()
+            .encoder_for(self)
+            .is_some_and(|encoder| encoder.supports_icc_profile(self))
+    }
+
+    /// エンコードに使われる出力形式 (encode と同じ優先順位)
+    pub fn format(&self) -> Option<OutputFormat> {
+        registry().encoder_for(self).map(|encoder| encoder.format())
+    }
+
+    /// 非可逆圧縮の品質 (品質を持たない形式やロスレスの場合は None)
+    pub fn quality(&self) -> Option<f32> {
+        registry()
+            .encoder_for(self)
+            .and_then(|encoder| encoder.quality(self))
+    }
+
+    /// 品質のみを変更したコピーを返す
+    pub fn with_quality(&self, quality: f32) -> EncodeOptions {
+        let mut options = self.clone();
+        if let Some(encoder) = registry().encoder_for(self) {
+            encoder.set_quality(&mut options, quality);
+        }
+        options
+    }
+
+    /// 指定した出力形式のオプションのみを残したコピーを返す
+    /// 該当する形式のオプションが無い場合は None を返す
+    pub fn select(&self, format: OutputFormat) -> Option<EncodeOptions> {
+        if !registry().encoder(format)?.is_selected(self) {
+            return None;
+        }
+        let mut options = self.clone();
+        for encoder in registry().encoders() {
+            if encoder.format() != format {
+                encoder.deselect(&mut options);
+            }
+        }
+        Some(options)
+    }
+}
+
+/// 出力形式
+/// 形式を追加する場合は `EncodeOptions` のフィールドも追加する (`codec::Encoder` を参照)
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "lowercase")]
+pub enum OutputFormat {
+    Avif,
+    Webp,
+    Jpeg,
+    Png,
+}
+
+impl OutputFormat {
+    /// ファイルの拡張子 (ドット無し)
+    pub fn extension(self) -> &'static str {
+        match self {
+            OutputFormat::Avif => "avif",
+            OutputFormat::Webp => "webp",
+            OutputFormat::Jpeg => "jpg",
+            OutputFormat::Png => "png",
+        }
+    }
+
+    /// MIME タイプ
+    pub fn mime_type(self) -> &'static str {
+        match self {
+            OutputFormat::Avif => "image/avif",
+            OutputFormat::Webp => "image/webp",
+            OutputFormat::Jpeg => "image/jpeg",
+            OutputFormat::Png => "image/png",
+        }
+    }
+}
+
+/// レスポンシブ画像の出力対象
+/// width: 出力幅 (元画像より大きい場合は元画像の幅になる)
+/// format: 出力形式
+/// options: エンコードオプション (format に対応する形式のオプションが必要)
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct VariantTarget {
+    pub width: u32,
+    pub format: OutputFormat,
+    pub options: EncodeOptions,
+}
+
+/// レスポンシブ画像の生成計画
+/// targets: 出力対象の一覧
+/// file_name_pattern: 出力ファイル名のパターン ({name}: 元のファイル名 (拡張子無し), {width}: 幅, {ext}: 拡張子、ディレクトリは含められない)
+/// allow_upscale: 元画像より大きい幅への拡大を許可するか
+/// manifest: マニフェスト (JSON) と HTML の生成オプション (省略時は生成しない)
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct VariantPlan {
+    pub targets: Vec<VariantTarget>,
+    #[serde(default = "default_file_name_pattern")]
+    pub file_name_pattern: String,
+    #[serde(default)]
+    pub allow_upscale: bool,
+    #[serde(default)]
+    pub manifest: Option<ManifestOptions>,
+}
+
+fn default_file_name_pattern() -> String {
+    "{name}-{width}w.{ext}".to_string()
+}
+
+/// プレースホルダーの種類
+/// None: 生成しない
+/// Lqip: 縮小してぼかした WebP を base64 の data URI で埋め込む
+/// Blurhash: BlurHash 文字列
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PlaceholderKind {
+    #[default]
+    None,
+    Lqip,
+    Blurhash,
+}
+
+/// マニフェストと HTML の生成オプション
+/// sizes: `<source>` の sizes 属性
+/// alt: `<img>` の alt 属性
+/// url_prefix: srcset / src のファイル名の前に付ける URL
+/// placeholder: プレースホルダーの種類
+/// html: `<picture>` の HTML スニペットをファイルに書き出すか
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase", default)]
+pub struct ManifestOptions {
+    pub sizes: String,
+    pub alt: String,
+    pub url_prefix: String,
+    pub placeholder: PlaceholderKind,
+    pub html: bool,
+}
+
+impl Default for ManifestOptions {
+    fn default() -> Self {
+        Self {
+            sizes: "100vw".to_string(),
+            alt: String::new(),
+            url_prefix: String::new(),
+            placeholder: PlaceholderKind::None,
+            html: true,
+        }
+    }
+}
+
+/// `<picture>` の `<source>` 要素の情報
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct ManifestSource {
+    #[serde(rename = "type")]
+    pub mime_type: String,
+    pub srcset: String,
+    pub sizes: String,
+}
+
+/// フォールバックの `<img>` 要素の情報
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct ManifestImage {
+    pub src: String,
+    pub width: u32,
+    pub height: u32,
+}
+
+/// プレースホルダー
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(tag = "kind", content = "value", rename_all = "lowercase")]
+pub enum Placeholder {
+    Lqip(String),
+    Blurhash(String),
+}
+
+/// 1つの入力画像に対するマニフェスト
+/// name: 元のファイル名 (拡張子無し)
+/// sources: 形式ごとの `<source>` 情報
+/// fallback: フォールバックの `<img>` 情報 (最大幅の画像)
+/// placeholder: プレースホルダー
+/// html: `<picture>` の HTML スニペット
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct Manifest {
+    pub name: String,
+    pub sources: Vec<ManifestSource>,
+    pub fallback: ManifestImage,
+    pub placeholder: Option<Placeholder>,
+    pub html: String,
+}
+
+/// レスポンシブ画像の生成結果
+/// outputs: 生成されたファイルの一覧
+/// manifest: マニフェスト (生成しなかった場合は None)
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct VariantResult {
+    pub outputs: Vec<VariantOutput>,
+    pub manifest: Option<Manifest>,
+}
+
+/// 生成されたファイルの情報
+/// path: 出力ファイルのパス
+/// width / height: 出力画像のサイズ
+/// format: 出力形式
+/// size: ファイルサイズ (バイト)
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct VariantOutput {
+    pub path: String,
+    pub width: u32,
+    pub height: u32,
+    pub format: OutputFormat,
+    pub size: u64,
+}
+
+/// 変換結果の詳細
+/// data: エンコードされたバイト列
+/// width / height: 出力画像のサイズ
+/// size: 出力サイズ (バイト)
+/// quality: 最終的に使用した品質 (品質を探索した場合のみ)
+/// attempts: エンコードを試行した回数
+/// score: 知覚品質のスコア (知覚品質の目標を指定した場合のみ)
+/// chosen: 自動選択で選ばれた候補 (自動選択の場合のみ)
+/// candidates: 自動選択の各候補の結果 (自動選択の場合のみ)
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct ConvertResult {
+    pub data: Vec<u8>,
+    pub width: u32,
+    pub height: u32,
+    pub size: u64,
+    pub quality: Option<f32>,
+    pub attempts: u32,
+    pub score: Option<f64>,
+    pub chosen: Option<AutoCandidate>,
+    pub candidates: Vec<CandidateReport>,
+}
+
+/// 自動選択の候補ごとの結果
+/// candidate: 候補
+/// size: 出力サイズ (バイト、失敗した場合は None)
+/// score: SSIMULACRA2 のスコア (閾値を指定した非可逆圧縮の候補のみ)
+/// accepted: 閾値を満たし、選択の対象になったか
+/// error: エンコードに失敗した場合のエラーメッセージ
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct CandidateReport {
+    pub candidate: AutoCandidate,
+    pub size: Option<u64>,
+    pub score: Option<f64>,
+    pub accepted: bool,
+    pub error: Option<String>,
+}
+
+/// 画像比較のオプション
+/// heatmap: 差分のヒートマップ画像を生成する
+/// amplify: ヒートマップの差分の増幅率
+#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
+#[serde(rename_all = "camelCase", default)]
+pub struct CompareOptions {
+    pub heatmap: bool,
+    pub amplify: f32,
+}
+
+impl Default for CompareOptions {
+    fn default() -> Self {
+        Self {
+            heatmap: false,
+            amplify: 10.0,
+        }
+    }
+}
+
+/// 画像比較の結果
+/// width / height: 比較したサイズ (元画像のサイズ)
+/// resized: サイズが異なるため比較対象を元画像のサイズにリサイズしたか
+/// psnr: PSNR (dB、同一の場合は None)
+/// ssim: SSIM (輝度、1.0 が同一)
+/// ssimulacra2: SSIMULACRA2 (100 が同一)
+/// max_abs_error: チャンネルごとの最大絶対誤差 (R, G, B)
+/// heatmap: 差分を増幅したヒートマップ (PNG の data URI)
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct CompareResult {
+    pub width: u32,
+    pub height: u32,
+    pub resized: bool,
+    pub psnr: Option<f64>,
+    pub ssim: f64,
+    pub ssimulacra2: f64,
+    pub max_abs_error: [u8; 3],
+    pub heatmap: Option<String>,
+}
+
+/// 出力が元のファイルより大きい (または削減率が足りない) 場合の扱い
+/// Skip: 書き込まない
+/// KeepOriginal: 元のファイルを出力先にコピーする (同じ名前のファイルが既にある場合はエラー)
+/// WriteAnyway: そのまま書き込む (既定、サイズに関わらず常に書き込む)
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LargerOutputPolicy {
+    Skip,
+    KeepOriginal,
+    #[default]
+    WriteAnyway,
+}
+
+/// ファイル変換時のサイズのポリシー
+/// on_larger: 削減率が min_savings_percent に満たない場合の扱い
+/// min_savings_percent: 必要な最小の削減率 (%、0 の場合は元より小さくならなかった場合のみ)
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default)]
+#[serde(rename_all = "camelCase", default)]
+pub struct SizePolicyOptions {
+    pub on_larger: LargerOutputPolicy,
+    pub min_savings_percent: f64,
+}
+
+/// ファイル変換の結果
+/// Written: 変換結果を書き込んだ
+/// Skipped: 削減率が足りないため書き込まなかった
+/// KeptOriginal: 削減率が足りない (または自動選択で元のファイルが選ばれた) ため、変換結果を書き込まずに元のファイルを残した
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FileOutcome {
+    Written,
+    Skipped,
+    KeptOriginal,
+}
+
+/// ファイル変換の結果の詳細
+/// input_path: 入力ファイルのパス
+/// output_path: 書き込んだファイルのパス (書き込まなかった場合は None)
+/// outcome: 結果
+/// input_size: 元のファイルのサイズ (バイト)
+/// converted_size: 変換結果のサイズ (バイト)
+/// savings_percent: 削減率 (%、大きくなった場合は負の値)
+/// chosen: 自動選択で選ばれた候補 (output_path の拡張子は候補の形式に合わせる)
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct FileConvertResult {
+    pub input_path: String,
+    pub output_path: Option<String>,
+    pub outcome: FileOutcome,
+    pub input_size: u64,
+    pub converted_size: u64,
+    pub savings_percent: f64,
+    #[serde(default)]
+    pub chosen: Option<AutoCandidate>,
+}
+
+/// 画像のヘッダーから読み取った情報
+/// format: 入力形式 ("jpeg", "png", "webp", "heic", "jpeg2000" など)
+/// width / height: 画像のサイズ (px、EXIF の回転は適用しない)
+/// file_size: ファイルのサイズ (バイト)
+/// bit_depth: 1 チャンネルあたりのビット数
+/// color_type: 色の種類
+/// has_alpha: アルファチャンネルを持つか
+/// frame_count: フレーム数 (アニメーション) またはページ数 (TIFF)、静止画は 1
+/// icc_description: 埋め込まれた ICC プロファイルの説明 ("Display P3" など)
+/// orientation: EXIF の Orientation (1-8、記録されていない場合は None)
+/// exif: EXIF の主な項目 (EXIF が無い場合は None)
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct ImageInfo {
+    pub format: String,
+    pub width: u32,
+    pub height: u32,
+    pub file_size: u64,
+    pub bit_depth: u8,
+    pub color_type: ColorType,
+    pub has_alpha: bool,
+    pub frame_count: u32,
+    pub icc_description: Option<String>,
+    pub orientation: Option<u16>,
+    pub exif: Option<ExifSummary>,
+}
+
+/// 入力画像の色の種類
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "camelCase")]
+pub enum ColorType {
+    Gray,
+    GrayAlpha,
+    #[default]
+    Rgb,
+    Rgba,
+    Cmyk,
+}
+
+/// EXIF の主な項目
+/// make / model: カメラのメーカーと機種
+/// lens_model: レンズ
+/// software: 編集ソフト
+/// date_time_original: 撮影日時 ("YYYY:MM:DD HH:MM:SS")
+/// exposure_time: 露出時間 ("1/125" など)
+/// f_number: F 値
+/// iso: ISO 感度
+/// focal_length: 焦点距離 (mm)
+/// gps_latitude / gps_longitude: 撮影位置 (10 進の度、南緯・西経は負の値)
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct ExifSummary {
+    pub make: Option<String>,
+    pub model: Option<String>,
+    pub lens_model: Option<String>,
+    pub software: Option<String>,
+    pub date_time_original: Option<String>,
+    pub exposure_time: Option<String>,
+    pub f_number: Option<f64>,
+    pub iso: Option<u32>,
+    pub focal_length: Option<f64>,
+    pub gps_latitude: Option<f64>,
+    pub gps_longitude: Option<f64>,
 }
 
 /// ファイルパス情報
@@ -107,7 +980,151 @@ pub struct EncodeOptions {
 #[derive(serde::Serialize)]
 #[serde(rename_all = "camelCase")] // JS側でキャメルケースになるように設定
 pub struct PathInfo {
-    pub(crate) file_name: Option<String>,
-    pub(crate) extension: Option<String>,
-    pub(crate) parent_dir: Option<String>,
+    pub file_name: Option<String>,
+    pub extension: Option<String>,
+    pub parent_dir: Option<String>,
+}
+
+/// プレビュー (ファイル一覧のサムネイル) のオプション
+/// max_width / max_height: 収める枠のサイズ (px、超える場合のみアスペクト比を保って縮小)
+/// format: 出力形式 (通常は Webp または Png)
+/// quality: 非可逆圧縮の品質 (0-100)
+/// use_embedded: EXIF や HEIF に埋め込まれたサムネイルが十分な大きさの場合はそれを使う
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase", default)]
+pub struct PreviewOptions {
+    pub max_width: u32,
+    pub max_height: u32,
+    pub format: OutputFormat,
+    pub quality: f32,
+    pub use_embedded: bool,
+}
+
+impl Default for PreviewOptions {
+    fn default() -> Self {
+        Self {
+            max_width: 256,
+            max_height: 256,
+            format: OutputFormat::Webp,
+            quality: 75.0,
+            use_embedded: true,
+        }
+    }
+}
+
+/// プレビューの生成結果
+/// data: エンコードされたバイト列
+/// mime_type: 出力形式の MIME タイプ
+/// width / height: プレビューのサイズ (EXIF の回転を適用した後)
+/// cached: ディスクのキャッシュから返したか
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct PreviewResult {
+    pub data: Vec<u8>,
+    pub mime_type: String,
+    pub width: u32,
+    pub height: u32,
+    pub cached: bool,
+}
+
+/// 設定確認用のエンコードで切り抜く範囲
+/// x / y / width / height: 範囲 (px、EXIF の向きと operations を適用した後、リサイズ前の画像の座標)
+#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
+#[serde(rename_all = "camelCase")]
+pub struct PreviewRegion {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+/// 設定確認用のエンコード (ファイルに書き込まずに結果を確認する) のオプション
+/// region: 切り抜く範囲 (None の場合は全体)。リサイズ前の画像の座標で指定し、画像からはみ出した部分は除く (空の範囲はエラー)
+/// max_width / max_height: 切り抜いた範囲をこのサイズに収まるよう縮小する (None の場合は縮小しない)
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default)]
+#[serde(rename_all = "camelCase", default)]
+pub struct PreviewEncodeOptions {
+    pub region: Option<PreviewRegion>,
+    pub max_width: Option<u32>,
+    pub max_height: Option<u32>,
+}
+
+/// 設定確認用のエンコードの結果
+/// size: 範囲をエンコードしたサイズ (バイト)
+/// estimated_size: 画像全体をエンコードした場合の推定サイズ (バイト、画素数の比から見積もる)
+/// encode_millis: エンコードにかかった時間 (ms)
+/// quality: 使用した品質 (品質を持たない形式やロスレスの場合は None)
+/// width / height: エンコードした範囲のサイズ
+/// full_width / full_height: 画像全体の出力サイズ
+/// rgba: エンコード結果をデコードした RGBA の画素 (width × height × 4 バイト)
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct PreviewEncodeResult {
+    pub size: u64,
+    pub estimated_size: u64,
+    pub encode_millis: f64,
+    pub quality: Option<f32>,
+    pub width: u32,
+    pub height: u32,
+    pub full_width: u32,
+    pub full_height: u32,
+    pub rgba: Vec<u8>,
+}
+
+/// デコード時の資源の上限 (巨大なサイズを宣言した「解凍爆弾」への対策)
+/// max_width / max_height: 幅・高さの上限 (px)
+/// max_pixels: 画素数 (幅 × 高さ) の上限
+/// max_alloc: デコード結果のメモリ量の上限 (バイト、画素数 × チャンネル数 × チャンネルあたりのバイト数で見積もる)
+/// max_frames: フレーム数 (アニメーション) またはページ数 (TIFF) の上限
+/// いずれも None の場合は制限しない
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "camelCase", default)]
+pub struct DecodeLimits {
+    pub max_width: Option<u32>,
+    pub max_height: Option<u32>,
+    pub max_pixels: Option<u64>,
+    pub max_alloc: Option<u64>,
+    pub max_frames: Option<u32>,
+}
+
+impl Default for DecodeLimits {
+    fn default() -> Self {
+        Self {
+            max_width: Some(65535),
+            max_height: Some(65535),
+            max_pixels: Some(256 * 1024 * 1024),
+            // image クレートの既定の上限 (512 MiB) に合わせる
+            max_alloc: Some(512 * 1024 * 1024),
+            max_frames: Some(10000),
+        }
+    }
+}
+
+impl DecodeLimits {
+    /// 制限しない設定
+    pub fn unlimited() -> Self {
+        Self {
+            max_width: None,
+            max_height: None,
+            max_pixels: None,
+            max_alloc: None,
+            max_frames: None,
+        }
+    }
+}
+
+/// 分離モード (デコードとエンコードを子プロセスで行う) のオプション
+/// timeout_seconds: 1 つの変換の制限時間 (秒、超えた場合はプロセスを終了させる。None の場合は制限しない)
+#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
+#[serde(rename_all = "camelCase", default)]
+pub struct IsolationOptions {
+    pub timeout_seconds: Option<u64>,
+}
+
+impl Default for IsolationOptions {
+    fn default() -> Self {
+        Self {
+            timeout_seconds: Some(120),
+        }
+    }
 }
@@ -0,0 +1,623 @@
+use crate::alpha::apply_alpha;
+use crate::auto::encode_auto;
+use crate::cancel::check_cancelled;
+use crate::color::apply_color_management;
+use crate::decoder::{ColorProfile, check_decodable, decode, read_color_profile, read_exif};
+use crate::encoder::{Metadata, encode};
+use crate::error::AppError;
+use crate::metrics::compare;
+use crate::options::{
+    AutoCandidate, ColorOptions, CompareOptions, CompareResult, ConvertResult, EncodeOptions,
+    FileConvertResult, FileOutcome, LargerOutputPolicy, OutputFormat, PreviewEncodeOptions,
+    PreviewEncodeResult, PreviewRegion, SizePolicyOptions, TransformOptions,
+};
+use crate::target::{encode_to_quality, encode_to_size};
+use crate::transform::{apply_exif_orientation, apply_transform, apply_transform_region};
+use crate::watermark::apply_watermark;
+use image::DynamicImage;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+
+/// 画像のバイト列をデコードし、指定された形式でエンコードします。
+/// # 引数
+/// - `data`: 変換対象の画像データのバイト列
+/// - `options`: エンコードオプション
+/// # 戻り値
+/// - 成功した場合はエンコードされたバイト列を `Vec<u8>` として返します。
+/// - 失敗した場合は `AppError` を返します。
+pub fn convert(data: &[u8], options: EncodeOptions) -> Result<Vec<u8>, AppError> {
+    convert_with_report(data, options).map(|result| result.data)
+}
+
+/// 画像のバイト列を変換し、出力サイズや使用した品質などの詳細とともに返します。
+/// # 引数
+/// - `data`: 変換対象の画像データのバイト列
+/// - `options`: エンコードオプション
+/// # 戻り値
+/// - 成功した場合は `ConvertResult` を返します。
+/// - 失敗した場合は `AppError` を返します。
+pub fn convert_with_report(data: &[u8], options: EncodeOptions) -> Result<ConvertResult, AppError> {
+    eprintln!("Decoding...");
+    let img = decode(data)?;
+    // 画素を加工しない場合のみ、元のファイルを自動選択の候補にできる
+    let original = (options.transform.is_none()
+        && options.watermark.is_none()
+        && options.alpha.is_none_or(|alpha| alpha.background.is_none()))
+    .then_some(data);
+    encode_image_with_report(
+        img,
+        read_color_profile(data),
+        read_exif(data),
+        options,
+        original,
+    )
+}
+
+/// 画像ファイルを変換して保存します。変換結果が十分に小さくならない場合はポリシーに従います。
+/// # 引数
+/// - `input_path`: 変換対象の画像ファイルのパス
+/// - `output_path`: 保存先のファイルのパス
+/// - `options`: エンコードオプション
+/// - `policy`: サイズのポリシー
+/// # 戻り値
+/// - 成功した場合はファイルごとの結果 (書き込んだか、サイズ、削減率) を返します。
+/// - 失敗した場合は `AppError` を返します。
+/// # 注意
+/// - `auto` の場合は、選ばれた候補の形式 (Original の場合は入力ファイルの拡張子) に合わせて `output_path` の拡張子を変えます。
+/// - 自動選択で元のファイルが選ばれ、保存先が入力と同じパスの場合は書き込まずに `FileOutcome::KeptOriginal` を返します。
+/// - `KeepOriginal` の場合は、元のファイル名のまま出力先のディレクトリにコピーします (同じパスの場合はコピーしません)。
+///   出力先に同じ名前の別のファイルがある場合は、上書きせずに `AppError::File` を返します。
+/// - `cancel_batch` で中止された場合は、変換の前と書き込みの前に `AppError::Cancelled` を返します。
+pub fn convert_file(
+    input_path: &str,
+    output_path: &str,
+    options: EncodeOptions,
+    policy: &SizePolicyOptions,
+) -> Result<FileConvertResult, AppError> {
+    check_cancelled()?;
+    let data = fs::read(input_path).map_err(|e| AppError::file("read", input_path, e))?;
+    let result = convert_with_report(&data, options)?;
+    // 変換中に中止された場合は、結果を書き込まない
+    check_cancelled()?;
+    let converted = result.data;
+    let output_path = output_path_for(Path::new(output_path), Path::new(input_path), result.chosen);
+
+    let (input_size, converted_size) = (data.len() as u64, converted.len() as u64);
+    let savings_percent = if input_size == 0 {
+        0.0
+    } else {
+        (1.0 - converted_size as f64 / input_size as f64) * 100.0
+    };
+    let enough = converted_size < input_size && savings_percent >= policy.min_savings_percent;
+
+    let keeps_input =
+        result.chosen == Some(AutoCandidate::Original) && output_path == Path::new(input_path);
+    let (outcome, written) = if enough || policy.on_larger == LargerOutputPolicy::WriteAnyway {
+        if keeps_input {
+            // 元のファイルが選ばれ、保存先が入力と同じパスになった場合は書き込まずに元のファイルを残す
+            (
+                FileOutcome::KeptOriginal,
+                Some(output_path.to_string_lossy().to_string()),
+            )
+        } else {
+            fs::write(&output_path, &converted)
+                .map_err(|e| AppError::file("write", &output_path, e))?;
+            (
+                FileOutcome::Written,
+                Some(output_path.to_string_lossy().to_string()),
+            )
+        }
+    } else if policy.on_larger == LargerOutputPolicy::KeepOriginal {
+        let input = Path::new(input_path);
+        let file_name = input
+            .file_name()
+            .ok_or_else(|| AppError::InvalidInput(format!("Invalid file name: {}", input_path)))?;
+        let target = output_path.with_file_name(file_name);
+        if target != input {
+            // 出力先にある別のファイルを元のファイルで上書きしない
+            if target.exists() {
+                return Err(AppError::file(
+                    "copy to",
+                    &target,
+                    io::Error::new(io::ErrorKind::AlreadyExists, "File already exists"),
+                ));
+            }
+            fs::copy(input, &target).map_err(|e| AppError::file("copy to", &target, e))?;
+        }
+        (
+            FileOutcome::KeptOriginal,
+            Some(target.to_string_lossy().to_string()),
+        )
+    } else {
+        (FileOutcome::Skipped, None)
+    };
+    eprintln!(
+        "File: {} -> {:?} ({} -> {} bytes, {:.1}%)",
+        input_path, outcome, input_size, converted_size, savings_percent
+    );
+
+    Ok(FileConvertResult {
+        input_path: input_path.to_string(),
+        output_path: written,
+        outcome,
+        input_size,
+        converted_size,
+        savings_percent,
+        chosen: result.chosen,
+    })
+}
+
+/// 自動選択の結果に合わせた保存先のパス
+/// 選ばれた形式と拡張子が食い違う場合 (WebP の保存先に AVIF が選ばれた場合など) は拡張子を置き換える
+fn output_path_for(
+    output_path: &Path,
+    input_path: &Path,
+    chosen: Option<AutoCandidate>,
+) -> PathBuf {
+    let extension = match chosen {
+        None => return output_path.to_path_buf(),
+        Some(candidate) => match candidate.format() {
+            Some(format) => format.extension().to_string(),
+            None => match input_path.extension() {
+                Some(extension) => extension.to_string_lossy().to_string(),
+                None => return output_path.to_path_buf(),
+            },
+        },
+    };
+    let matches = output_path
+        .extension()
+        .is_some_and(|current| current.to_string_lossy().eq_ignore_ascii_case(&extension));
+    if matches {
+        output_path.to_path_buf()
+    } else {
+        output_path.with_extension(extension)
+    }
+}
+
+/// 2 つの画像のバイト列をデコードして比較します。
+/// # 引数
+/// - `original`: 元の画像データのバイト列
+/// - `modified`: 比較対象の画像データのバイト列 (変換後の画像など)
+/// - `options`: 比較のオプション
+/// # 戻り値
+/// - 成功した場合は `CompareResult` を返します。
+/// - 失敗した場合は `AppError` を返します。
+/// # 注意
+/// - 埋め込みプロファイルの違いで差が出ないよう、両方とも sRGB に変換してから比較します。
+/// - EXIF の Orientation を適用し、表示される向きで比較します。
+/// - AVIF は libheif でデコードするため、`heif` feature を無効にしてビルドした場合は `AppError::UnsupportedFormat` を返します。
+pub fn compare_images(
+    original: &[u8],
+    modified: &[u8],
+    options: CompareOptions,
+) -> Result<CompareResult, AppError> {
+    let to_srgb = |data: &[u8]| -> Result<DynamicImage, AppError> {
+        eprintln!("Decoding...");
+        let img = apply_exif_orientation(decode(data)?, read_exif(data).as_deref());
+        let (img, _) = apply_color_management(
+            img,
+            read_color_profile(data),
+            &ColorOptions::default(),
+            false,
+        )?;
+        Ok(img)
+    };
+    compare(&to_srgb(original)?, &to_srgb(modified)?, &options)
+}
+
+/// 画像の一部 (または縮小したもの) をエンコードし、ファイルに書き込まずにサイズと結果を返します。
+/// # 引数
+/// - `data`: 変換対象の画像データのバイト列
+/// - `options`: エンコードオプション
+/// - `preview`: 切り抜く範囲と縮小のオプション
+/// # 戻り値
+/// - 成功した場合は、範囲をエンコードしたサイズ・全体の推定サイズ・エンコード時間と、デコードした RGBA の画素を返します。
+/// - 失敗した場合は `AppError` を返します。
+/// - 範囲が空の場合 (幅・高さが 0、または画像の外) は `AppError::InvalidInput` を返します。
+/// - 出力形式をデコードできない場合は `AppError::UnsupportedFeature` を返します。
+/// # 注意
+/// - 範囲は元の画像 (EXIF の向きと `operations` を適用した後、リサイズの前) の座標で指定します。
+/// - 範囲をリサイズの前に切り抜き、画像全体と同じ倍率でリサイズします。透かしは切り抜いた範囲を基準に配置されます。
+/// - `target_size` は画像全体のサイズに対する指定のため無視し、指定された品質でエンコードします。
+/// - 推定サイズは画素あたりのサイズが一様とみなした概算です。縮小した場合は実際より大きめになります。
+pub fn preview_encode(
+    data: &[u8],
+    options: EncodeOptions,
+    preview: &PreviewEncodeOptions,
+) -> Result<PreviewEncodeResult, AppError> {
+    // 結果の画素を返すため、出力形式をデコードできるかを先に確かめる (自動選択はエンコード後)
+    if options.auto.is_none()
+        && let Some(format) = options.format()
+    {
+        check_decodable(format)?;
+    }
+
+    eprintln!("Decoding...");
+    let exif = read_exif(data);
+    let img = apply_exif_orientation(decode(data)?, exif.as_deref());
+    let (img, icc_profile, (full_width, full_height)) =
+        prepare_image_region(img, read_color_profile(data), &options, preview.region)?;
+
+    // 指定サイズに収める
+    let transform = TransformOptions {
+        max_width: preview.max_width,
+        max_height: preview.max_height,
+        ..Default::default()
+    };
+    let img = apply_transform(img, &transform)?;
+
+    let metadata = Metadata { icc_profile, exif };
+    eprintln!("Encoding preview {}x{}...", img.width(), img.height());
+    let start = Instant::now();
+    let (encoded, quality) = if let Some(auto) = &options.auto {
+        let result = encode_auto(&img, None, &options, &metadata, auto)?;
+        if let Some(format) = result.chosen.and_then(|chosen| chosen.format()) {
+            check_decodable(format)?;
+        }
+        (result.data, result.quality)
+    } else if let Some(target) = &options.target_quality {
+        let result = encode_to_quality(&img, &options, &metadata, target)?;
+        (result.data, result.quality)
+    } else {
+        let quality = options.quality();
+        (encode(&img, options, &metadata)?, quality)
+    };
+    let encode_millis = start.elapsed().as_secs_f64() * 1000.0;
+
+    let pixels = |width: u32, height: u32| width as f64 * height as f64;
+    let estimated_size = (encoded.len() as f64 * pixels(full_width, full_height)
+        / pixels(img.width(), img.height()))
+    .round() as u64;
+    let rgba = decode(&encoded)?.to_rgba8();
+
+    Ok(PreviewEncodeResult {
+        size: encoded.len() as u64,
+        estimated_size,
+        encode_millis,
+        quality,
+        width: rgba.width(),
+        height: rgba.height(),
+        full_width,
+        full_height,
+        rgba: rgba.into_raw(),
+    })
+}
+
+/// デコード済みの画像にカラーマネジメント・変形・エンコードを順に適用し、変換結果の詳細を返します。
+/// # 引数
+/// - `img`: デコード済みの画像
+/// - `profile`: 入力画像に埋め込まれていた色情報
+/// - `exif`: 入力画像の EXIF (Orientation を画素に適用し、JPEG / PNG 出力で指定された場合に引き継ぐ)
+/// - `original`: 元のファイルのバイト列 (自動選択で元のファイルを候補にする場合のみ)
+/// # 注意
+/// - `auto` が指定されている場合は、候補を並列にエンコードして最も小さいものを選びます。
+/// - `target_size` が指定されている場合は、目標サイズに収まる品質を探索します。
+/// - `target_quality` が指定されている場合は、知覚品質の目標を満たす品質を探索します。
+pub fn encode_image_with_report(
+    img: DynamicImage,
+    profile: Option<ColorProfile>,
+    exif: Option<Vec<u8>>,
+    options: EncodeOptions,
+    original: Option<&[u8]>,
+) -> Result<ConvertResult, AppError> {
+    let img = apply_exif_orientation(img, exif.as_deref());
+    let (img, icc_profile) = prepare_image(img, profile, &options)?;
+    let metadata = Metadata { icc_profile, exif };
+    eprintln!("Encoding...");
+    if let Some(auto) = &options.auto {
+        return encode_auto(&img, original, &options, &metadata, auto);
+    }
+    if let Some(target) = &options.target_size {
+        return encode_to_size(&img, &options, &metadata, target);
+    }
+    if let Some(target) = &options.target_quality {
+        return encode_to_quality(&img, &options, &metadata, target);
+    }
+    // 画像エンコード
+    let data = encode(&img, options, &metadata)?;
+    Ok(ConvertResult {
+        width: img.width(),
+        height: img.height(),
+        size: data.len() as u64,
+        data,
+        quality: None,
+        attempts: 1,
+        score: None,
+        ..Default::default()
+    })
+}
+
+/// エンコード前の処理 (カラーマネジメント・変形・透かし・アルファ) を適用します。
+/// # 戻り値
+/// - 成功した場合は処理後の画像と、出力に埋め込むべき ICC プロファイルを返します。
+/// - 失敗した場合は `AppError` を返します。
+pub fn prepare_image(
+    img: DynamicImage,
+    profile: Option<ColorProfile>,
+    options: &EncodeOptions,
+) -> Result<(DynamicImage, Option<Vec<u8>>), AppError> {
+    let (img, icc_profile, _) = prepare_image_region(img, profile, options, None)?;
+    Ok((img, icc_profile))
+}
+
+/// 処理後の画像、出力に埋め込むべき ICC プロファイルと、画像全体の出力サイズ
+type PreparedRegion = (DynamicImage, Option<Vec<u8>>, (u32, u32));
+
+/// エンコード前の処理を、画像の一部の範囲だけに適用します (設定確認用のプレビュー)。
+/// # 戻り値
+/// - 成功した場合は処理後の画像、出力に埋め込むべき ICC プロファイルと、画像全体の出力サイズを返します。
+/// - 範囲が空の場合は `AppError::InvalidInput` を返します。
+/// # 注意
+/// - `region` が None の場合は `prepare_image` と同じく画像全体に適用します。
+fn prepare_image_region(
+    img: DynamicImage,
+    profile: Option<ColorProfile>,
+    options: &EncodeOptions,
+    region: Option<PreviewRegion>,
+) -> Result<PreparedRegion, AppError> {
+    // カラーマネジメント
+    // 自動選択の場合は ICC プロファイルを残し、埋め込めない候補ごとに sRGB へ変換する
+    let (img, icc_profile) = apply_color_management(
+        img,
+        profile,
+        &options.color.unwrap_or_default(),
+        options.auto.is_some() || options.supports_icc_profile(),
+    )?;
+    // 変形 (リサイズ)
+    // 範囲が指定された場合はリサイズの前に切り抜き、全体の出力サイズも求める
+    let transform = options.transform.clone().unwrap_or_default();
+    let (img, full_size) = match region {
+        Some(region) => apply_transform_region(
+            img,
+            &transform,
+            (region.x, region.y, region.width, region.height),
+        )?,
+        None => {
+            let img = match &options.transform {
+                Some(transform) => apply_transform(img, transform)?,
+                None => img,
+            };
+            let full_size = (img.width(), img.height());
+            (img, full_size)
+        }
+    };
+    // 透かし (リサイズ後の画像に合成する)
+    let img = match &options.watermark {
+        Some(watermark) => apply_watermark(img, watermark)?,
+        None => img,
+    };
+    // アルファ (不透明な画像の RGB 化・背景色への合成)
+    let mut alpha = options.alpha.unwrap_or_default();
+    if options.format() == Some(OutputFormat::Jpeg) && alpha.background.is_none() {
+        // JPEG はアルファを持てないため、背景色の指定が無ければ白に合成する
+        alpha.background = Some([255, 255, 255]);
+    }
+    let img = apply_alpha(img, &alpha);
+    Ok((img, icc_profile, full_size))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::codec::registry;
+    use crate::options::AutoOptions;
+    use image::{ImageFormat, RgbImage};
+    use std::io::Cursor;
+    use std::sync::Mutex;
+
+    /// 一括変換の中止はすべてのスレッドに効くため、`convert_file` のテストは並行して実行しない
+    static CONVERT_FILE: Mutex<()> = Mutex::new(());
+
+    fn png(width: u32, height: u32) -> Vec<u8> {
+        let img = RgbImage::from_fn(width, height, |x, y| {
+            image::Rgb([(x * 4) as u8, (y * 4) as u8, 96])
+        });
+        let mut data = Vec::new();
+        DynamicImage::ImageRgb8(img)
+            .write_to(&mut Cursor::new(&mut data), ImageFormat::Png)
+            .unwrap();
+        data
+    }
+
+    #[test]
+    fn output_extension_follows_auto_choice() {
+        let (input, output) = (Path::new("in/photo.png"), Path::new("out/photo.webp"));
+        let path = |chosen| output_path_for(output, input, chosen);
+        assert_eq!(path(None), PathBuf::from("out/photo.webp"));
+        assert_eq!(
+            path(Some(AutoCandidate::WebpLossy)),
+            PathBuf::from("out/photo.webp")
+        );
+        assert_eq!(
+            path(Some(AutoCandidate::Avif)),
+            PathBuf::from("out/photo.avif")
+        );
+        assert_eq!(
+            path(Some(AutoCandidate::Original)),
+            PathBuf::from("out/photo.png")
+        );
+        assert_eq!(
+            output_path_for(
+                Path::new("out/photo.WEBP"),
+                input,
+                Some(AutoCandidate::WebpLossless)
+            ),
+            PathBuf::from("out/photo.WEBP")
+        );
+    }
+
+    #[test]
+    fn write_anyway_is_the_default_policy() {
+        assert_eq!(
+            SizePolicyOptions::default().on_larger,
+            LargerOutputPolicy::WriteAnyway
+        );
+    }
+
+    #[test]
+    fn keep_original_does_not_overwrite() {
+        let _lock = CONVERT_FILE.lock().unwrap_or_else(|e| e.into_inner());
+        let dir = std::env::temp_dir().join(format!("keep-original-{}", std::process::id()));
+        let (input_dir, output_dir) = (dir.join("in"), dir.join("out"));
+        fs::create_dir_all(&input_dir).unwrap();
+        fs::create_dir_all(&output_dir).unwrap();
+        let input = input_dir.join("photo.png");
+        fs::write(&input, png(16, 16)).unwrap();
+        let existing = output_dir.join("photo.png");
+        fs::write(&existing, b"existing").unwrap();
+
+        let policy = SizePolicyOptions {
+            on_larger: LargerOutputPolicy::KeepOriginal,
+            min_savings_percent: 100.0,
+        };
+        let options = EncodeOptions {
+            png: Some(Default::default()),
+            ..Default::default()
+        };
+        let result = convert_file(
+            input.to_str().unwrap(),
+            output_dir.join("photo.webp").to_str().unwrap(),
+            options,
+            &policy,
+        );
+        let error = result.unwrap_err();
+        assert_eq!(error.code(), "file_exists");
+        assert_eq!(fs::read(&existing).unwrap(), b"existing");
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn in_place_output_is_written_unless_original_is_chosen() {
+        let _lock = CONVERT_FILE.lock().unwrap_or_else(|e| e.into_inner());
+        let dir = std::env::temp_dir().join(format!("in-place-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let input = dir.join("photo.png");
+        let path = input.to_str().unwrap();
+
+        // 同じパスへの再圧縮は書き込む
+        fs::write(&input, png(32, 32)).unwrap();
+        let options = EncodeOptions {
+            png: Some(Default::default()),
+            ..Default::default()
+        };
+        let result = convert_file(path, path, options, &SizePolicyOptions::default()).unwrap();
+        assert_eq!(result.outcome, FileOutcome::Written);
+        assert_eq!(
+            fs::read(&input).unwrap().len() as u64,
+            result.converted_size
+        );
+
+        // 元のファイルが選ばれた場合は書き込まずに残す
+        let original = png(32, 32);
+        fs::write(&input, &original).unwrap();
+        let options = EncodeOptions {
+            auto: Some(AutoOptions {
+                candidates: vec![AutoCandidate::Original],
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        let result = convert_file(path, path, options, &SizePolicyOptions::default()).unwrap();
+        assert_eq!(result.outcome, FileOutcome::KeptOriginal);
+        assert_eq!(result.output_path.as_deref(), Some(path));
+        assert_eq!(fs::read(&input).unwrap(), original);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn cancelled_batch_does_not_write() {
+        let _lock = CONVERT_FILE.lock().unwrap_or_else(|e| e.into_inner());
+        let dir = std::env::temp_dir().join(format!("cancel-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let input = dir.join("photo.png");
+        fs::write(&input, png(16, 16)).unwrap();
+        let output = dir.join("photo.out.png");
+        let convert = || {
+            let options = EncodeOptions {
+                png: Some(Default::default()),
+                ..Default::default()
+            };
+            convert_file(
+                input.to_str().unwrap(),
+                output.to_str().unwrap(),
+                options,
+                &SizePolicyOptions::default(),
+            )
+        };
+
+        crate::cancel::cancel_batch();
+        let error = convert().unwrap_err();
+        crate::cancel::start_batch();
+        assert!(matches!(error, AppError::Cancelled));
+        assert_eq!(error.code(), "cancelled");
+        assert!(!output.exists());
+
+        assert_eq!(convert().unwrap().outcome, FileOutcome::Written);
+        assert!(output.exists());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn compare_with_every_decodable_output() {
+        let original = png(48, 32);
+        for encoder in registry().encoders().filter(|encoder| encoder.enabled()) {
+            let format = encoder.format();
+            if !registry().can_decode(format) {
+                continue;
+            }
+            let mut options = EncodeOptions::default();
+            match format {
+                OutputFormat::Avif => options.avif = Some(Default::default()),
+                OutputFormat::Webp => options.webp = Some(Default::default()),
+                OutputFormat::Jpeg => options.jpeg = Some(Default::default()),
+                OutputFormat::Png => options.png = Some(Default::default()),
+            }
+            let converted = convert(&original, options).unwrap();
+            let result = compare_images(&original, &converted, CompareOptions::default())
+                .unwrap_or_else(|e| panic!("{:?}: {}", format, e));
+            assert_eq!((result.width, result.height), (48, 32));
+            assert!(result.ssim > 0.9, "{:?}: {}", format, result.ssim);
+        }
+    }
+
+    #[test]
+    fn preview_region_is_in_source_pixels() {
+        let data = png(40, 20);
+        let options = EncodeOptions {
+            png: Some(Default::default()),
+            transform: Some(TransformOptions {
+                width: Some(20),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        let region = |x, y, width, height| PreviewEncodeOptions {
+            region: Some(PreviewRegion {
+                x,
+                y,
+                width,
+                height,
+            }),
+            ..Default::default()
+        };
+
+        // 右半分 (元の座標) は、全体と同じ 1/2 の倍率で縮小される
+        let result = preview_encode(&data, options.clone(), &region(20, 0, 20, 20)).unwrap();
+        assert_eq!((result.full_width, result.full_height), (20, 10));
+        assert_eq!((result.width, result.height), (10, 10));
+        let red = result.rgba[0] as i32;
+        assert!((red - 82).abs() <= 8, "{}", red);
+
+        // はみ出した部分は除く
+        let result = preview_encode(&data, options.clone(), &region(30, 10, 100, 100)).unwrap();
+        assert_eq!((result.width, result.height), (5, 5));
+
+        // 空の範囲はエラー
+        for empty in [region(40, 0, 5, 5), region(0, 20, 5, 5), region(0, 0, 0, 5)] {
+            let error = preview_encode(&data, options.clone(), &empty).unwrap_err();
+            assert_eq!(error.code(), "invalid_input");
+        }
+    }
+}
@@ -0,0 +1,400 @@
+use crate::color::apply_color_management;
+use crate::decoder::{decode, probe, read_color_profile, read_thumbnail};
+use crate::encoder::{Metadata, encode};
+use crate::error::AppError;
+use crate::options::{
+    ColorOptions, EncodeOptions, OutputFormat, PreviewOptions, PreviewResult, TransformOptions,
+};
+use crate::transform::apply_transform;
+use image::metadata::Orientation;
+use std::fs::{self, Metadata as FileMetadata};
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::io;
+use std::path::Path;
+use std::time::UNIX_EPOCH;
+
+/// 埋め込みサムネイルと本体のアスペクト比の許容誤差 (黒帯付きのサムネイルを避ける)
+const THUMBNAIL_ASPECT_TOLERANCE: f64 = 0.02;
+
+/// 画像のバイト列から、ファイル一覧に表示するプレビュー (サムネイル) を生成します。
+/// # 引数
+/// - `data`: 画像データのバイト列
+/// - `options`: プレビューのオプション
+/// # 戻り値
+/// - 成功した場合はエンコードされたプレビューとそのサイズを返します。
+/// - 失敗した場合は `AppError` を返します。
+/// # 注意
+/// - 埋め込みサムネイルが枠に収めた後のサイズ以上で、アスペクト比が本体と一致する場合は、本体をデコードせずにそれを使います。
+/// - EXIF の Orientation を適用し、表示される向きに合わせます。
+/// - 本体をデコードした場合は、埋め込みプロファイルから sRGB に変換します。
+pub fn generate_preview(data: &[u8], options: &PreviewOptions) -> Result<PreviewResult, AppError> {
+    let info = probe(data)?;
+    let orientation = info
+        .orientation
+        .and_then(|value| Orientation::from_exif(value as u8));
+    // 90 度回転する Orientation では、枠の幅と高さを入れ替えて回転前の向きで扱う
+    let (box_width, box_height) = match info.orientation {
+        Some(5..=8) => (options.max_height, options.max_width),
+        _ => (options.max_width, options.max_height),
+    };
+    let (target_width, target_height) = fit_within(info.width, info.height, box_width, box_height);
+
+    let embedded = if options.use_embedded {
+        read_thumbnail(data).filter(|thumbnail| {
+            thumbnail.width() >= target_width
+                && thumbnail.height() >= target_height
+                && aspect_matches(
+                    (thumbnail.width(), thumbnail.height()),
+                    (info.width, info.height),
+                )
+        })
+    } else {
+        None
+    };
+    let img = match embedded {
+        Some(thumbnail) => {
+            eprintln!(
+                "Preview: Using embedded {}x{} thumbnail...",
+                thumbnail.width(),
+                thumbnail.height()
+            );
+            thumbnail
+        }
+        None => {
+            let img = decode(data)?;
+            let (img, _) = apply_color_management(
+                img,
+                read_color_profile(data),
+                &ColorOptions::default(),
+                false,
+            )?;
+            img
+        }
+    };
+
+    // プレビューは速度を優先し、ガンマ補正済みの縮小は行わない
+    let transform = TransformOptions {
+        max_width: Some(box_width),
+        max_height: Some(box_height),
+        linear: false,
+        ..Default::default()
+    };
+    let mut img = apply_transform(img, &transform)?;
+    if let Some(orientation) = orientation {
+        img.apply_orientation(orientation);
+    }
+
+    let encoded = encode(&img, preview_encode_options(options), &Metadata::default())?;
+    Ok(PreviewResult {
+        data: encoded,
+        mime_type: options.format.mime_type().to_string(),
+        width: img.width(),
+        height: img.height(),
+        cached: false,
+    })
+}
+
+/// 画像ファイルのプレビューを生成します。キャッシュのディレクトリを指定した場合は、生成結果をディスクに保存して再利用します。
+/// # 引数
+/// - `path`: 画像ファイルのパス
+/// - `options`: プレビューのオプション
+/// - `cache_dir`: キャッシュのディレクトリ (None の場合はキャッシュしない)
+/// # 戻り値
+/// - 成功した場合はエンコードされたプレビューとそのサイズを返します (キャッシュから返した場合は `cached` が true)。
+/// - 失敗した場合は `AppError` を返します。
+/// # 注意
+/// - キャッシュのキーはパス・更新日時・ファイルサイズとオプションです。ファイルが変更されると別のキーになります。
+/// - キャッシュの読み書きに失敗した場合は、キャッシュを使わずに生成します。古いキャッシュは削除しません。
+pub fn preview_file(
+    path: &str,
+    options: &PreviewOptions,
+    cache_dir: Option<&Path>,
+) -> Result<PreviewResult, AppError> {
+    let metadata = fs::metadata(path).map_err(|e| AppError::file("read", path, e))?;
+    let cache_path = cache_dir.map(|dir| dir.join(cache_file_name(path, &metadata, options)));
+
+    if let Some(cache_path) = &cache_path
+        && let Ok(data) = fs::read(cache_path)
+        && let Ok(info) = probe(&data)
+    {
+        return Ok(PreviewResult {
+            data,
+            mime_type: options.format.mime_type().to_string(),
+            width: info.width,
+            height: info.height,
+            cached: true,
+        });
+    }
+
+    let data = fs::read(path).map_err(|e| AppError::file("read", path, e))?;
+    let preview = generate_preview(&data, options)?;
+    if let Some(cache_path) = &cache_path
+        && let Err(e) = write_cache(cache_path, &preview.data)
+    {
+        eprintln!(
+            "Preview: Failed to write cache {}: {}",
+            cache_path.display(),
+            e
+        );
+    }
+    Ok(preview)
+}
+
+/// 元のサイズを枠に収めたサイズ (拡大はしない)
+fn fit_within(width: u32, height: u32, box_width: u32, box_height: u32) -> (u32, u32) {
+    let scale = (box_width as f64 / width as f64)
+        .min(box_height as f64 / height as f64)
+        .min(1.0);
+    (
+        ((width as f64 * scale).round() as u32).max(1),
+        ((height as f64 * scale).round() as u32).max(1),
+    )
+}
+
+/// 2 つのサイズのアスペクト比が許容誤差の範囲で一致するか
+fn aspect_matches(a: (u32, u32), b: (u32, u32)) -> bool {
+    let ratio = |(width, height): (u32, u32)| width as f64 / height.max(1) as f64;
+    (ratio(a) / ratio(b) - 1.0).abs() <= THUMBNAIL_ASPECT_TOLERANCE
+}
+
+/// プレビューの出力形式と品質から、エンコードオプションを組み立てる
+fn preview_encode_options(options: &PreviewOptions) -> EncodeOptions {
+    let mut encode_options = EncodeOptions::default();
+    match options.format {
+        OutputFormat::Avif => encode_options.avif = Some(Default::default()),
+        OutputFormat::Webp => encode_options.webp = Some(Default::default()),
+        OutputFormat::Jpeg => encode_options.jpeg = Some(Default::default()),
+        OutputFormat::Png => encode_options.png = Some(Default::default()),
+    }
+    encode_options.with_quality(options.quality)
+}
+
+/// キャッシュのファイル名 (パス・更新日時・サイズ・オプションのハッシュ)
+fn cache_file_name(path: &str, metadata: &FileMetadata, options: &PreviewOptions) -> String {
+    let mut hasher = DefaultHasher::new();
+    path.hash(&mut hasher);
+    metadata.len().hash(&mut hasher);
+    metadata
+        .modified()
+        .ok()
+        .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+        .map(|duration| duration.as_nanos())
+        .hash(&mut hasher);
+    options.max_width.hash(&mut hasher);
+    options.max_height.hash(&mut hasher);
+    options.format.hash(&mut hasher);
+    options.quality.to_bits().hash(&mut hasher);
+    options.use_embedded.hash(&mut hasher);
+    format!("{:016x}.{}", hasher.finish(), options.format.extension())
+}
+
+/// キャッシュを書き込む (書き込み途中のファイルを読まないよう、一時ファイルから置き換える)
+fn write_cache(cache_path: &Path, data: &[u8]) -> io::Result<()> {
+    if let Some(dir) = cache_path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let temp_path = cache_path.with_extension(format!("{}.tmp", std::process::id()));
+    fs::write(&temp_path, data)?;
+    fs::rename(&temp_path, cache_path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::codecs::jpeg::JpegEncoder;
+    use image::{DynamicImage, Rgb, RgbImage};
+    use std::fs::File;
+    use std::time::Duration;
+
+    /// 単色の JPEG を作る
+    fn jpeg(width: u32, height: u32, color: [u8; 3]) -> Vec<u8> {
+        let img = RgbImage::from_pixel(width, height, Rgb(color));
+        let mut data = Vec::new();
+        JpegEncoder::new_with_quality(&mut data, 90)
+            .encode_image(&DynamicImage::ImageRgb8(img))
+            .unwrap();
+        data
+    }
+
+    /// Orientation と IFD1 のサムネイル (JPEG) を持つ EXIF を、JPEG の APP1 として先頭に挿入する
+    fn with_exif(jpeg: &[u8], orientation: u16, thumbnail: Option<&[u8]>) -> Vec<u8> {
+        let entry = |tag: u16, value_type: u16, value: u32| {
+            let mut entry = Vec::new();
+            entry.extend_from_slice(&tag.to_le_bytes());
+            entry.extend_from_slice(&value_type.to_le_bytes());
+            entry.extend_from_slice(&1u32.to_le_bytes());
+            entry.extend_from_slice(&value.to_le_bytes());
+            entry
+        };
+        // IFD0 (8 + 2 + 12 + 4 = 26 から IFD1、IFD1 は 26 + 2 + 12 * 2 + 4 = 56 からサムネイル)
+        let mut tiff = b"II\x2a\x00\x08\x00\x00\x00".to_vec();
+        tiff.extend_from_slice(&1u16.to_le_bytes());
+        tiff.extend(entry(0x0112, 3, orientation as u32));
+        tiff.extend_from_slice(&(if thumbnail.is_some() { 26u32 } else { 0 }).to_le_bytes());
+        if let Some(thumbnail) = thumbnail {
+            tiff.extend_from_slice(&2u16.to_le_bytes());
+            tiff.extend(entry(0x0201, 4, 56));
+            tiff.extend(entry(0x0202, 4, thumbnail.len() as u32));
+            tiff.extend_from_slice(&0u32.to_le_bytes());
+            tiff.extend_from_slice(thumbnail);
+        }
+
+        let mut data = vec![0xFF, 0xD8, 0xFF, 0xE1];
+        data.extend_from_slice(&((tiff.len() + 8) as u16).to_be_bytes());
+        data.extend_from_slice(b"Exif\0\0");
+        data.extend(tiff);
+        data.extend_from_slice(&jpeg[2..]);
+        data
+    }
+
+    fn png_options(max_width: u32, max_height: u32) -> PreviewOptions {
+        PreviewOptions {
+            max_width,
+            max_height,
+            format: OutputFormat::Png,
+            ..Default::default()
+        }
+    }
+
+    /// プレビューの中央の画素が赤 (本体) か青 (サムネイル) か
+    fn center_is_blue(preview: &PreviewResult) -> bool {
+        let img = image::load_from_memory(&preview.data).unwrap().to_rgb8();
+        let Rgb([red, _, blue]) = *img.get_pixel(img.width() / 2, img.height() / 2);
+        blue > red
+    }
+
+    #[test]
+    fn fit_within_keeps_aspect_without_upscaling() {
+        assert_eq!(fit_within(4000, 3000, 256, 256), (256, 192));
+        assert_eq!(fit_within(3000, 4000, 256, 128), (96, 128));
+        assert_eq!(fit_within(100, 50, 256, 256), (100, 50));
+        // 極端に細長い画像でも 0 にはならない
+        assert_eq!(fit_within(10000, 1, 256, 256), (256, 1));
+    }
+
+    #[test]
+    fn aspect_matches_within_tolerance() {
+        assert!(aspect_matches((160, 120), (4000, 3000)));
+        assert!(aspect_matches((161, 120), (4000, 3000)));
+        assert!(!aspect_matches((160, 160), (4000, 3000)));
+        // 黒帯付きの 16:9 のサムネイルは 4:3 の本体と一致しない
+        assert!(!aspect_matches((160, 90), (4000, 3000)));
+        assert!(!aspect_matches((160, 0), (4000, 3000)));
+    }
+
+    #[test]
+    fn embedded_thumbnail_is_used_only_when_large_enough_and_same_aspect() {
+        let main = jpeg(400, 300, [255, 0, 0]);
+        let preview = |thumbnail: &[u8], options: &PreviewOptions| {
+            generate_preview(&with_exif(&main, 1, Some(thumbnail)), options).unwrap()
+        };
+        let options = png_options(128, 128);
+
+        let large = jpeg(200, 150, [0, 0, 255]);
+        let result = preview(&large, &options);
+        assert!(center_is_blue(&result));
+        assert_eq!((result.width, result.height), (128, 96));
+
+        let disabled = PreviewOptions {
+            use_embedded: false,
+            ..options.clone()
+        };
+        assert!(!center_is_blue(&preview(&large, &disabled)));
+        // 枠に収めたサイズより小さい
+        assert!(!center_is_blue(&preview(
+            &jpeg(100, 75, [0, 0, 255]),
+            &options
+        )));
+        // アスペクト比が異なる (黒帯付き)
+        assert!(!center_is_blue(&preview(
+            &jpeg(200, 200, [0, 0, 255]),
+            &options
+        )));
+    }
+
+    #[test]
+    fn rotated_orientations_swap_the_box() {
+        let main = jpeg(400, 200, [255, 0, 0]);
+        for orientation in 1..=8u16 {
+            let result =
+                generate_preview(&with_exif(&main, orientation, None), &png_options(100, 50))
+                    .unwrap();
+            let expected = if orientation >= 5 {
+                (25, 50)
+            } else {
+                (100, 50)
+            };
+            assert_eq!((result.width, result.height), expected, "{}", orientation);
+        }
+    }
+
+    #[test]
+    fn cache_file_name_changes_with_file_and_options() {
+        let path = std::env::temp_dir().join(format!("preview-key-{}.jpg", std::process::id()));
+        let path_str = path.to_str().unwrap();
+        let options = PreviewOptions::default();
+        fs::write(&path, b"1234").unwrap();
+        let name = |options: &PreviewOptions| {
+            cache_file_name(path_str, &fs::metadata(&path).unwrap(), options)
+        };
+
+        let original = name(&options);
+        assert!(original.ends_with(".webp"));
+        assert_eq!(name(&options), original);
+        assert_ne!(
+            cache_file_name("other.jpg", &fs::metadata(&path).unwrap(), &options),
+            original
+        );
+        assert_ne!(name(&png_options(256, 256)), original);
+
+        // 更新日時が変わる
+        let modified = fs::metadata(&path).unwrap().modified().unwrap();
+        let file = File::options().write(true).open(&path).unwrap();
+        file.set_modified(modified + Duration::from_secs(60))
+            .unwrap();
+        let touched = name(&options);
+        assert_ne!(touched, original);
+
+        // 同じ更新日時でサイズが変わる
+        fs::write(&path, b"12345").unwrap();
+        let file = File::options().write(true).open(&path).unwrap();
+        file.set_modified(modified + Duration::from_secs(60))
+            .unwrap();
+        assert_ne!(name(&options), touched);
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn preview_file_round_trips_through_the_cache() {
+        let dir = std::env::temp_dir().join(format!("preview-cache-{}", std::process::id()));
+        let cache_dir = dir.join("cache");
+        fs::create_dir_all(&dir).unwrap();
+        let input = dir.join("photo.jpg");
+        let path = input.to_str().unwrap();
+        fs::write(&input, jpeg(64, 32, [255, 0, 0])).unwrap();
+        let options = png_options(32, 32);
+
+        let first = preview_file(path, &options, Some(&cache_dir)).unwrap();
+        assert!(!first.cached);
+        assert_eq!((first.width, first.height), (32, 16));
+        assert_eq!(fs::read_dir(&cache_dir).unwrap().count(), 1);
+
+        let second = preview_file(path, &options, Some(&cache_dir)).unwrap();
+        assert!(second.cached);
+        assert_eq!(second.data, first.data);
+        assert_eq!((second.width, second.height), (32, 16));
+        assert_eq!(second.mime_type, "image/png");
+
+        // ファイルが変わると作り直す (古いキャッシュは残る)
+        fs::write(&input, jpeg(32, 64, [255, 0, 0])).unwrap();
+        let changed = preview_file(path, &options, Some(&cache_dir)).unwrap();
+        assert!(!changed.cached);
+        assert_eq!((changed.width, changed.height), (16, 32));
+        assert_eq!(fs::read_dir(&cache_dir).unwrap().count(), 2);
+
+        // キャッシュ無し
+        assert!(!preview_file(path, &options, None).unwrap().cached);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,446 @@
+use crate::metrics::ssimulacra2;
+use crate::options::PngQuantizeOptions;
+use image::{DynamicImage, RgbaImage};
+use std::collections::HashMap;
+
+/// ヒストグラムの最大色数 (これを超える場合は下位ビットを落として数える)
+const MAX_HISTOGRAM_COLORS: usize = 1 << 16;
+/// パレットを調整する k-means の繰り返し回数
+const KMEANS_ITERATIONS: usize = 3;
+/// 品質の範囲から色数を探索するときの最小の色数
+const MIN_SEARCH_COLORS: u16 = 16;
+
+/// パレット化した画像
+/// palette: パレット (RGBA、最大 256 色)
+/// indices: 各画素のパレットのインデックス
+pub struct IndexedImage {
+    pub palette: Vec<[u8; 4]>,
+    pub indices: Vec<u8>,
+}
+
+/// 画像を減色してパレット化する
+/// # 引数
+/// - `img`: 減色する画像
+/// - `options`: 減色のオプション
+/// # 戻り値
+/// - 成功した場合はパレット化した画像を返します。
+/// - 最大色数でも `min_quality` に届かない場合は `None` を返します (呼び出し側で可逆圧縮にする)。
+/// - 品質を計算できない場合 (小さすぎる画像など) も `None` を返します。
+/// # 注意
+/// - 元の色数が `max_colors` 以下の場合は、色を変えずにそのままパレット化します。
+/// - `max_quality` が 100 未満の場合は、16 色から倍々に色数を増やし、届いた時点の色数を使います。
+pub fn quantize(img: &DynamicImage, options: &PngQuantizeOptions) -> Option<IndexedImage> {
+    let rgba = img.to_rgba8();
+    let max_colors = options.max_colors.clamp(2, 256);
+    if let Some(indexed) = exact_palette(&rgba, max_colors as usize) {
+        eprintln!(
+            "Quantize: Image already has {} colors.",
+            indexed.palette.len()
+        );
+        return Some(indexed);
+    }
+
+    let needs_score = options.min_quality > 0.0 || options.max_quality < 100.0;
+    let counts: Vec<u16> = if options.max_quality < 100.0 {
+        std::iter::successors(Some(MIN_SEARCH_COLORS), |c| Some(c * 2))
+            .take_while(|&c| c < max_colors)
+            .chain(std::iter::once(max_colors))
+            .collect()
+    } else {
+        vec![max_colors]
+    };
+
+    let mut best = None;
+    for colors in counts {
+        let indexed = quantize_with_palette(&rgba, colors as usize, options.dithering);
+        if !needs_score {
+            eprintln!("Quantize: Reduced to {} colors.", colors);
+            return Some(indexed);
+        }
+        // SSIMULACRA2 は負の値になることがあるため、0-100 に収める
+        let score = match ssimulacra2(img, &DynamicImage::ImageRgba8(to_rgba(&indexed, &rgba))) {
+            Ok(score) => score.clamp(0.0, 100.0),
+            Err(e) => {
+                eprintln!(
+                    "Quantize: Failed to measure quality ({}), keeping lossless.",
+                    e
+                );
+                return None;
+            }
+        };
+        eprintln!("Quantize: {} colors -> SSIMULACRA2 {:.2}", colors, score);
+        if score >= options.max_quality as f64 {
+            return Some(indexed);
+        }
+        best = Some((indexed, score));
+    }
+
+    match best {
+        Some((indexed, score)) if score >= options.min_quality as f64 => Some(indexed),
+        _ => {
+            eprintln!("Quantize: Quality below the minimum, keeping lossless.");
+            None
+        }
+    }
+}
+
+/// 色数が `max_colors` 以下であれば、色を変えずにパレット化する
+fn exact_palette(rgba: &RgbaImage, max_colors: usize) -> Option<IndexedImage> {
+    let mut lookup: HashMap<[u8; 4], u8> = HashMap::new();
+    let mut palette = Vec::new();
+    let mut indices = Vec::with_capacity((rgba.width() * rgba.height()) as usize);
+    for pixel in rgba.pixels() {
+        let index = match lookup.get(&pixel.0) {
+            Some(&index) => index,
+            None => {
+                if palette.len() == max_colors {
+                    return None;
+                }
+                let index = palette.len() as u8;
+                lookup.insert(pixel.0, index);
+                palette.push(pixel.0);
+                index
+            }
+        };
+        indices.push(index);
+    }
+    Some(IndexedImage { palette, indices })
+}
+
+/// メディアンカットでパレットを作成し、k-means で調整した後、Floyd-Steinberg ディザリングで各画素を割り当てる
+fn quantize_with_palette(rgba: &RgbaImage, colors: usize, dithering: f32) -> IndexedImage {
+    let mut histogram = build_histogram(rgba);
+    let mut palette = median_cut(&mut histogram, colors);
+    refine_palette(&histogram, &mut palette);
+    let search = PaletteSearch::new(&palette);
+
+    let (width, height) = (rgba.width() as usize, rgba.height() as usize);
+    let strength = dithering.clamp(0.0, 1.0);
+    let mut indices = Vec::with_capacity(width * height);
+    // 現在の行と次の行に拡散する誤差 (RGBA)
+    let mut current = vec![[0f32; 4]; width + 2];
+    let mut next = vec![[0f32; 4]; width + 2];
+    for y in 0..height {
+        for x in 0..width {
+            let pixel = rgba.get_pixel(x as u32, y as u32).0;
+            let target: [u8; 4] = std::array::from_fn(|c| {
+                (pixel[c] as f32 + current[x + 1][c])
+                    .round()
+                    .clamp(0.0, 255.0) as u8
+            });
+            let index = search.nearest(target);
+            indices.push(index as u8);
+            if strength > 0.0 {
+                let chosen = palette[index];
+                for c in 0..4 {
+                    let error = (target[c] as f32 - chosen[c] as f32) * strength;
+                    current[x + 2][c] += error * 7.0 / 16.0;
+                    next[x][c] += error * 3.0 / 16.0;
+                    next[x + 1][c] += error * 5.0 / 16.0;
+                    next[x + 2][c] += error / 16.0;
+                }
+            }
+        }
+        std::mem::swap(&mut current, &mut next);
+        next.iter_mut().for_each(|e| *e = [0.0; 4]);
+    }
+    IndexedImage { palette, indices }
+}
+
+/// ヒストグラムの 1 色 (色と画素数)
+#[derive(Clone, Copy)]
+struct HistogramEntry {
+    color: [u8; 4],
+    count: u32,
+}
+
+/// 色のヒストグラムを作成する
+/// 色数が多すぎる場合は下位ビットを落として色数を減らす
+fn build_histogram(rgba: &RgbaImage) -> Vec<HistogramEntry> {
+    let mut shift = 0;
+    loop {
+        let mask = 0xFFu8 << shift;
+        let mut counts: HashMap<[u8; 4], u32> = HashMap::new();
+        for pixel in rgba.pixels() {
+            *counts.entry(pixel.0.map(|v| v & mask)).or_insert(0) += 1;
+            if counts.len() > MAX_HISTOGRAM_COLORS && shift < 4 {
+                break;
+            }
+        }
+        if counts.len() <= MAX_HISTOGRAM_COLORS || shift >= 4 {
+            // 落とした下位ビットの分だけ中央に寄せる
+            let offset = (1u8 << shift) / 2;
+            return counts
+                .into_iter()
+                .map(|(color, count)| HistogramEntry {
+                    color: color.map(|v| v | offset),
+                    count,
+                })
+                .collect();
+        }
+        shift += 1;
+    }
+}
+
+/// メディアンカットの箱 (ヒストグラムの範囲と、分割の優先度)
+struct ColorBox {
+    start: usize,
+    end: usize,
+    error: f64,
+    channel: usize,
+}
+
+impl ColorBox {
+    /// 範囲の重み付きの二乗誤差と、分散が最も大きいチャンネルを求める
+    fn new(entries: &[HistogramEntry], start: usize, end: usize) -> Self {
+        let items = &entries[start..end];
+        let total: f64 = items.iter().map(|e| e.count as f64).sum();
+        let mut variance = [0f64; 4];
+        for (c, v) in variance.iter_mut().enumerate() {
+            let mean = items
+                .iter()
+                .map(|e| e.color[c] as f64 * e.count as f64)
+                .sum::<f64>()
+                / total;
+            *v = items
+                .iter()
+                .map(|e| (e.color[c] as f64 - mean).powi(2) * e.count as f64)
+                .sum();
+        }
+        let channel = (0..4)
+            .max_by(|&a, &b| variance[a].total_cmp(&variance[b]))
+            .unwrap_or(0);
+        Self {
+            start,
+            end,
+            error: variance.iter().sum(),
+            channel,
+        }
+    }
+}
+
+/// メディアンカットで `colors` 色のパレットを作成する
+fn median_cut(entries: &mut [HistogramEntry], colors: usize) -> Vec<[u8; 4]> {
+    let mut boxes = vec![ColorBox::new(entries, 0, entries.len())];
+    while boxes.len() < colors {
+        // 誤差の最も大きい箱を、分散の最も大きいチャンネルの重み付き中央値で分割する
+        let Some(i) = boxes
+            .iter()
+            .enumerate()
+            .filter(|(_, b)| b.end - b.start > 1 && b.error > 0.0)
+            .max_by(|(_, a), (_, b)| a.error.total_cmp(&b.error))
+            .map(|(i, _)| i)
+        else {
+            break;
+        };
+        let ColorBox {
+            start,
+            end,
+            channel,
+            ..
+        } = boxes[i];
+        let items = &mut entries[start..end];
+        items.sort_unstable_by_key(|e| e.color[channel]);
+        let total: u64 = items.iter().map(|e| e.count as u64).sum();
+        let mut accumulated = 0;
+        let mut mid = items.len() / 2;
+        for (j, entry) in items.iter().enumerate() {
+            accumulated += entry.count as u64;
+            if accumulated * 2 >= total {
+                mid = (j + 1).clamp(1, items.len() - 1);
+                break;
+            }
+        }
+        boxes[i] = ColorBox::new(entries, start, start + mid);
+        boxes.push(ColorBox::new(entries, start + mid, end));
+    }
+    boxes
+        .iter()
+        .map(|b| weighted_mean(&entries[b.start..b.end]))
+        .collect()
+}
+
+/// k-means でパレットの各色を、割り当てられた色の重み付き平均に近づける
+fn refine_palette(entries: &[HistogramEntry], palette: &mut [[u8; 4]]) {
+    for _ in 0..KMEANS_ITERATIONS {
+        let search = PaletteSearch::new(palette);
+        let mut sums = vec![([0f64; 4], 0f64); palette.len()];
+        for entry in entries {
+            let (sum, count) = &mut sums[search.nearest(entry.color)];
+            for (s, &v) in sum.iter_mut().zip(&entry.color) {
+                *s += v as f64 * entry.count as f64;
+            }
+            *count += entry.count as f64;
+        }
+        for (color, (sum, count)) in palette.iter_mut().zip(sums) {
+            if count > 0.0 {
+                *color = sum.map(|s| (s / count).round() as u8);
+            }
+        }
+    }
+}
+
+/// 重み付きの平均の色
+fn weighted_mean(entries: &[HistogramEntry]) -> [u8; 4] {
+    let total: f64 = entries.iter().map(|e| e.count as f64).sum();
+    std::array::from_fn(|c| {
+        let sum: f64 = entries
+            .iter()
+            .map(|e| e.color[c] as f64 * e.count as f64)
+            .sum();
+        (sum / total).round() as u8
+    })
+}
+
+/// パレットから最も近い色を探す
+/// 緑の値で並べ替えておき、緑の差だけで最良の距離を超える範囲は探索しない
+struct PaletteSearch {
+    entries: Vec<([i32; 4], usize)>,
+}
+
+impl PaletteSearch {
+    fn new(palette: &[[u8; 4]]) -> Self {
+        let mut entries: Vec<([i32; 4], usize)> = palette
+            .iter()
+            .enumerate()
+            .map(|(i, color)| (color.map(i32::from), i))
+            .collect();
+        entries.sort_unstable_by_key(|(color, _)| color[1]);
+        Self { entries }
+    }
+
+    /// 最も近い色のパレットのインデックス
+    fn nearest(&self, color: [u8; 4]) -> usize {
+        let color = color.map(i32::from);
+        let distance =
+            |p: &[i32; 4]| -> i32 { p.iter().zip(&color).map(|(a, b)| (a - b).pow(2)).sum() };
+        let start = self.entries.partition_point(|(p, _)| p[1] < color[1]);
+        let mut best = (i32::MAX, 0);
+        // 上方向
+        for (p, i) in &self.entries[start..] {
+            if (p[1] - color[1]).pow(2) >= best.0 {
+                break;
+            }
+            let d = distance(p);
+            if d < best.0 {
+                best = (d, *i);
+            }
+        }
+        // 下方向
+        for (p, i) in self.entries[..start].iter().rev() {
+            if (p[1] - color[1]).pow(2) >= best.0 {
+                break;
+            }
+            let d = distance(p);
+            if d < best.0 {
+                best = (d, *i);
+            }
+        }
+        best.1
+    }
+}
+
+/// パレット化した画像を RGBA に戻す (品質の計算用)
+fn to_rgba(indexed: &IndexedImage, original: &RgbaImage) -> RgbaImage {
+    let raw = indexed
+        .indices
+        .iter()
+        .flat_map(|&i| indexed.palette[i as usize])
+        .collect();
+    RgbaImage::from_raw(original.width(), original.height(), raw).unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::Rgba;
+
+    fn entries(colors: &[([u8; 4], u32)]) -> Vec<HistogramEntry> {
+        colors
+            .iter()
+            .map(|&(color, count)| HistogramEntry { color, count })
+            .collect()
+    }
+
+    #[test]
+    fn exact_palette_keeps_colors() {
+        let colors = [[255, 0, 0, 255], [0, 255, 0, 255], [0, 0, 255, 128]];
+        let rgba = RgbaImage::from_fn(6, 2, |x, y| Rgba(colors[((x + y) % 3) as usize]));
+
+        let indexed = exact_palette(&rgba, 3).unwrap();
+        assert_eq!(indexed.palette.len(), 3);
+        assert_eq!(to_rgba(&indexed, &rgba), rgba);
+        assert!(exact_palette(&rgba, 2).is_none());
+    }
+
+    #[test]
+    fn median_cut_splits_clusters() {
+        // 2 つの離れた色の塊は、それぞれ 1 色にまとまる
+        let mut histogram = entries(&[
+            ([0, 0, 0, 255], 10),
+            ([4, 4, 4, 255], 10),
+            ([250, 250, 250, 255], 10),
+            ([254, 254, 254, 255], 10),
+        ]);
+        let mut palette = median_cut(&mut histogram, 2);
+        palette.sort();
+        assert_eq!(palette, vec![[2, 2, 2, 255], [252, 252, 252, 255]]);
+
+        // 色数より多くは分割しない
+        let mut histogram = entries(&[([10, 20, 30, 255], 5), ([40, 50, 60, 255], 5)]);
+        assert_eq!(median_cut(&mut histogram, 16).len(), 2);
+    }
+
+    #[test]
+    fn median_cut_uses_weighted_median() {
+        // 画素数の多い色は、少ない色とは別の箱になる
+        let mut histogram = entries(&[
+            ([0, 0, 0, 255], 1000),
+            ([100, 0, 0, 255], 1),
+            ([110, 0, 0, 255], 1),
+        ]);
+        let palette = median_cut(&mut histogram, 2);
+        assert!(palette.contains(&[0, 0, 0, 255]));
+        assert!(palette.contains(&[105, 0, 0, 255]));
+    }
+
+    #[test]
+    fn palette_search_matches_brute_force() {
+        let palette: Vec<[u8; 4]> = (0..64u32)
+            .map(|i| {
+                let v = i.wrapping_mul(2654435761);
+                [(v >> 24) as u8, (v >> 16) as u8, (v >> 8) as u8, v as u8]
+            })
+            .collect();
+        let search = PaletteSearch::new(&palette);
+        let distance = |a: &[u8; 4], b: &[u8; 4]| -> i32 {
+            a.iter()
+                .zip(b)
+                .map(|(&a, &b)| (a as i32 - b as i32).pow(2))
+                .sum()
+        };
+        for i in 0..500u32 {
+            let v = i.wrapping_mul(2246822519);
+            let color = [(v >> 24) as u8, (v >> 16) as u8, (v >> 8) as u8, v as u8];
+            let best = palette.iter().map(|p| distance(p, &color)).min().unwrap();
+            assert_eq!(distance(&palette[search.nearest(color)], &color), best);
+        }
+        // パレットにある色はそのまま選ばれる
+        assert_eq!(palette[search.nearest(palette[10])], palette[10]);
+    }
+
+    #[test]
+    fn tiny_image_is_quantized_without_error() {
+        let rgba = RgbaImage::from_fn(3, 3, |x, y| Rgba([(x * 80) as u8, (y * 80) as u8, 0, 255]));
+        let options = PngQuantizeOptions {
+            max_colors: 4,
+            min_quality: 10.0,
+            max_quality: 90.0,
+            ..Default::default()
+        };
+        if let Some(indexed) = quantize(&DynamicImage::ImageRgba8(rgba), &options) {
+            assert!(indexed.palette.len() <= 4);
+            assert_eq!(indexed.indices.len(), 9);
+        }
+    }
+}
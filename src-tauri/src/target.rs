@@ -0,0 +1,251 @@
+use crate::decoder::{check_decodable, decode};
+use crate::encoder::{Metadata, encode};
+use crate::error::AppError;
+use crate::metrics::score;
+use crate::options::{
+    ConvertResult, EncodeOptions, TargetQualityOptions, TargetSizeOptions, TransformOptions,
+};
+use crate::transform::apply_transform;
+use image::DynamicImage;
+
+/// 縮小を試みる最大回数
+const MAX_DOWNSCALE_STEPS: u32 = 6;
+/// これより小さくは縮小しない (px)
+const MIN_DOWNSCALE_WIDTH: u32 = 16;
+
+/// 出力が目標サイズに収まる品質を二分探索してエンコードする
+/// # 引数
+/// - `img`: エンコード前の処理を適用済みの画像
+/// - `options`: エンコードオプション (quality が探索の上限になる)
+/// - `metadata`: 出力に引き継ぐ ICC プロファイルや EXIF
+/// - `target`: 目標サイズのオプション
+/// # 戻り値
+/// - 成功した場合は目標サイズに収まる中で最も高い品質の結果を返します。
+/// - 失敗した場合は `AppError` を返します。
+/// # 注意
+/// - 品質は整数刻みで探索します。
+/// - `allow_downscale` が有効な場合、品質の下限でも収まらなければ縮小して再探索します。
+/// - `max_bytes` が 0 の場合は、エンコードせずに `AppError::InvalidInput` を返します。
+pub fn encode_to_size(
+    img: &DynamicImage,
+    options: &EncodeOptions,
+    metadata: &Metadata,
+    target: &TargetSizeOptions,
+) -> Result<ConvertResult, AppError> {
+    if target.max_bytes == 0 {
+        return Err(AppError::InvalidInput(
+            "Target size requires maxBytes greater than 0".to_string(),
+        ));
+    }
+    let max_quality = options.quality().ok_or(AppError::UnsupportedFeature(
+        "Target size requires a lossy WebP, AVIF or JPEG output".to_string(),
+    ))?;
+    let min_quality = target.min_quality.max(1.0).min(max_quality).round();
+    let max_quality = max_quality.round();
+    let fits = |data: &[u8]| data.len() as u64 <= target.max_bytes;
+
+    let mut attempts = 0;
+    let mut scaled: Option<DynamicImage> = None;
+    for step in 0..=MAX_DOWNSCALE_STEPS {
+        let current = scaled.as_ref().unwrap_or(img);
+        let mut try_encode = |quality: f32| {
+            attempts += 1;
+            encode(current, options.with_quality(quality), metadata)
+        };
+        eprintln!(
+            "Target size: Searching quality for {}x{} (step {})...",
+            current.width(),
+            current.height(),
+            step
+        );
+
+        // 上限の品質で収まればそのまま採用する
+        let data = try_encode(max_quality)?;
+        // Ok: 収まった結果、Err: 縮小した画像で再探索する
+        let best = if fits(&data) {
+            Ok((max_quality, data))
+        } else {
+            let data = try_encode(min_quality)?;
+            if fits(&data) {
+                // lo は収まる品質、hi は収まらない品質
+                let (mut lo, mut hi) = (min_quality as u32, max_quality as u32);
+                let mut best = (min_quality, data);
+                while hi - lo > 1 {
+                    let mid = (lo + hi) / 2;
+                    let data = try_encode(mid as f32)?;
+                    if fits(&data) {
+                        lo = mid;
+                        best = (mid as f32, data);
+                    } else {
+                        hi = mid;
+                    }
+                }
+                Ok(best)
+            } else {
+                // 縮小率は最低品質でのサイズとの比から見積もる (面積はサイズにほぼ比例する)
+                if !target.allow_downscale || step == MAX_DOWNSCALE_STEPS {
+                    break;
+                }
+                let ratio = (target.max_bytes as f64 / data.len() as f64).sqrt() * 0.95;
+                let width = (current.width() as f64 * ratio.clamp(0.25, 0.9)).round() as u32;
+                if width < MIN_DOWNSCALE_WIDTH {
+                    break;
+                }
+                eprintln!("Target size: Downscaling to width {}...", width);
+                Err(apply_transform(
+                    current.clone(),
+                    &TransformOptions {
+                        width: Some(width),
+                        ..Default::default()
+                    },
+                )?)
+            }
+        };
+
+        match best {
+            Ok((quality, data)) => {
+                eprintln!(
+                    "Target size: Chosen quality {} after {} attempts.",
+                    quality, attempts
+                );
+                return Ok(ConvertResult {
+                    width: current.width(),
+                    height: current.height(),
+                    size: data.len() as u64,
+                    data,
+                    quality: Some(quality),
+                    attempts,
+                    score: None,
+                    ..Default::default()
+                });
+            }
+            Err(next) => scaled = Some(next),
+        }
+    }
+
+    Err(AppError::Encode(format!(
+        "Could not fit output within {} bytes after {} attempts",
+        target.max_bytes, attempts
+    )))
+}
+
+/// 知覚品質の目標を満たす最も低い品質を二分探索してエンコードする
+/// # 引数
+/// - `img`: エンコード前の処理を適用済みの画像
+/// - `options`: エンコードオプション (quality が探索の上限になる)
+/// - `metadata`: 出力に引き継ぐ ICC プロファイルや EXIF
+/// - `target`: 知覚品質の目標のオプション
+/// # 戻り値
+/// - 成功した場合は目標を満たす中で最も低い品質の結果を返します。
+/// - 失敗した場合は `AppError` を返します。
+/// # 注意
+/// - 候補ごとにエンコード結果をデコードし、`img` と比較してスコアを求めます。
+/// - 上限の品質でも目標に届かない場合は、上限の品質の結果を返します。
+/// - 出力形式をデコードできない場合 (`heif` feature 無しの AVIF) は、エンコードせずに `AppError::UnsupportedFeature` を返します。
+pub fn encode_to_quality(
+    img: &DynamicImage,
+    options: &EncodeOptions,
+    metadata: &Metadata,
+    target: &TargetQualityOptions,
+) -> Result<ConvertResult, AppError> {
+    let max_quality = options
+        .quality()
+        .ok_or(AppError::UnsupportedFeature(
+            "Target quality requires a lossy WebP, AVIF or JPEG output".to_string(),
+        ))?
+        .round();
+    if let Some(format) = options.format() {
+        check_decodable(format)?;
+    }
+    let min_quality = target.min_quality.max(1.0).min(max_quality).round();
+
+    let mut attempts = 0;
+    let mut try_encode = |quality: f32| -> Result<(f32, Vec<u8>, f64), AppError> {
+        attempts += 1;
+        let data = encode(img, options.with_quality(quality), metadata)?;
+        let value = score(target.metric, img, &decode(&data)?)?;
+        eprintln!(
+            "Target quality: quality {} -> {:?} {:.4}",
+            quality, target.metric, value
+        );
+        Ok((quality, data, value))
+    };
+
+    // lo は目標に届かない品質、hi は目標を満たす品質
+    let mut best = try_encode(max_quality)?;
+    if target.metric.meets(best.2, target.score) {
+        let (mut lo, mut hi) = (min_quality as u32, max_quality as u32);
+        let candidate = try_encode(min_quality)?;
+        if target.metric.meets(candidate.2, target.score) {
+            best = candidate;
+        } else {
+            while hi - lo > 1 {
+                let mid = (lo + hi) / 2;
+                let candidate = try_encode(mid as f32)?;
+                if target.metric.meets(candidate.2, target.score) {
+                    hi = mid;
+                    best = candidate;
+                } else {
+                    lo = mid;
+                }
+            }
+        }
+    } else {
+        eprintln!("Target quality: Target not reachable, using the maximum quality.");
+    }
+
+    let (quality, data, score) = best;
+    eprintln!(
+        "Target quality: Chosen quality {} after {} attempts.",
+        quality, attempts
+    );
+    Ok(ConvertResult {
+        width: img.width(),
+        height: img.height(),
+        size: data.len() as u64,
+        data,
+        quality: Some(quality),
+        attempts,
+        score: Some(score),
+        ..Default::default()
+    })
+}
+
+#[cfg(all(test, feature = "webp"))]
+mod tests {
+    use super::*;
+    use crate::options::WebpOptions;
+    use image::RgbImage;
+
+    fn webp_options() -> EncodeOptions {
+        EncodeOptions {
+            webp: Some(WebpOptions {
+                quality: 90.0,
+                lossless: false,
+            }),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn zero_max_bytes_is_rejected() {
+        let img = DynamicImage::ImageRgb8(RgbImage::new(16, 16));
+        let target = TargetSizeOptions::default();
+        let result = encode_to_size(&img, &webp_options(), &Metadata::default(), &target);
+        assert!(matches!(result, Err(AppError::InvalidInput(_))));
+    }
+
+    #[test]
+    fn output_fits_max_bytes() {
+        let img = DynamicImage::ImageRgb8(RgbImage::from_fn(64, 64, |x, y| {
+            image::Rgb([(x * 4) as u8, (y * 4) as u8, ((x ^ y) * 4) as u8])
+        }));
+        let target = TargetSizeOptions {
+            max_bytes: 2000,
+            ..Default::default()
+        };
+        let result = encode_to_size(&img, &webp_options(), &Metadata::default(), &target).unwrap();
+        assert!(result.size <= 2000);
+        assert!(result.quality.is_some());
+    }
+}
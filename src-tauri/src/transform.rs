@@ -0,0 +1,666 @@
+use crate::error::AppError;
+use crate::header::exif_orientation;
+use crate::options::{FitMode, ResizeFilter, TransformOperation, TransformOptions};
+use image::metadata::Orientation;
+use image::{DynamicImage, RgbImage, RgbaImage};
+
+/// デコード後の画像に変形処理 (切り抜き・回転・反転・余白除去、リサイズ) を適用する
+/// # 引数
+/// - `img`: デコード済みの画像
+/// - `options`: 変形オプション
+/// # 戻り値
+/// - 成功した場合は変形後の `DynamicImage` を返します。
+/// - 失敗した場合は `AppError` を返します。
+/// # 注意
+/// - `operations` を指定順に適用してから、リサイズを行います。
+/// - サイズの指定が無い場合や、変形の必要が無い場合は入力画像をそのまま返します。
+/// - リサイズ後の画像は 8bit の RGB または RGBA になります。
+pub fn apply_transform(
+    img: DynamicImage,
+    options: &TransformOptions,
+) -> Result<DynamicImage, AppError> {
+    let mut img = img;
+    for operation in &options.operations {
+        img = apply_operation(img, operation)?;
+    }
+
+    let Some(plan) = plan_resize(img.width(), img.height(), options) else {
+        return Ok(img);
+    };
+
+    eprintln!(
+        "Transform: Resizing {}x{} -> {}x{}...",
+        img.width(),
+        img.height(),
+        plan.width,
+        plan.height
+    );
+    let resized = resample(
+        &img,
+        plan.width,
+        plan.height,
+        options.filter,
+        options.linear,
+        options.premultiply_alpha,
+    )?;
+
+    // Cover の場合は中央を切り抜いて指定サイズに合わせる
+    Ok(match plan.crop {
+        Some((width, height)) => {
+            let x = (plan.width - width) / 2;
+            let y = (plan.height - height) / 2;
+            resized.crop_imm(x, y, width, height)
+        }
+        None => resized,
+    })
+}
+
+/// 画像の一部の範囲だけに変形処理を適用する (設定確認用のプレビュー)
+/// # 引数
+/// - `img`: デコード済みの画像
+/// - `options`: 変形オプション
+/// - `region`: 切り抜く範囲 (x, y, 幅, 高さ)。リサイズ前 (`operations` を適用した後) の画像の座標で指定する
+/// # 戻り値
+/// - 成功した場合は、範囲を全体と同じ倍率でリサイズした画像と、画像全体の出力サイズを返します。
+/// - 範囲が空の場合 (幅・高さが 0、または出力に含まれない位置) は `AppError::InvalidInput` を返します。
+/// # 注意
+/// - 画像からはみ出した部分と、Cover で切り抜かれる部分は範囲から除きます。
+/// - リサイズする前に切り抜くため、全体をリサイズするより速く、範囲の端の画素はわずかに異なります。
+pub fn apply_transform_region(
+    img: DynamicImage,
+    options: &TransformOptions,
+    region: (u32, u32, u32, u32),
+) -> Result<(DynamicImage, (u32, u32)), AppError> {
+    let mut img = img;
+    for operation in &options.operations {
+        img = apply_operation(img, operation)?;
+    }
+    let (width, height) = (img.width(), img.height());
+    let plan = plan_resize(width, height, options);
+
+    // 出力に残る範囲 (リサイズ前の座標) と、リサイズの倍率
+    let (scale_x, scale_y, window, full_size) = match &plan {
+        Some(plan) => {
+            let (sx, sy) = (
+                plan.width as f64 / width as f64,
+                plan.height as f64 / height as f64,
+            );
+            let (cw, ch) = plan.crop.unwrap_or((plan.width, plan.height));
+            let (ox, oy) = ((plan.width - cw) / 2, (plan.height - ch) / 2);
+            let window = (
+                (ox as f64 / sx).floor() as u32,
+                (oy as f64 / sy).floor() as u32,
+                (((ox + cw) as f64 / sx).ceil() as u32).min(width),
+                (((oy + ch) as f64 / sy).ceil() as u32).min(height),
+            );
+            (sx, sy, window, (cw, ch))
+        }
+        None => (1.0, 1.0, (0, 0, width, height), (width, height)),
+    };
+
+    let (x, y, region_width, region_height) = region;
+    let left = x.max(window.0);
+    let top = y.max(window.1);
+    let right = x.saturating_add(region_width).min(window.2);
+    let bottom = y.saturating_add(region_height).min(window.3);
+    if left >= right || top >= bottom {
+        return Err(AppError::InvalidInput(format!(
+            "Region {}x{} at ({}, {}) is empty or outside the {}x{} image",
+            region_width, region_height, x, y, width, height
+        )));
+    }
+    let cropped = img.crop_imm(left, top, right - left, bottom - top);
+    if plan.is_none() {
+        return Ok((cropped, full_size));
+    }
+
+    let scaled = |v: u32, scale: f64| ((v as f64 * scale).round() as u32).max(1);
+    let (target_width, target_height) = (
+        scaled(cropped.width(), scale_x),
+        scaled(cropped.height(), scale_y),
+    );
+    eprintln!(
+        "Transform: Resizing region {}x{} -> {}x{}...",
+        cropped.width(),
+        cropped.height(),
+        target_width,
+        target_height
+    );
+    let resized = resample(
+        &cropped,
+        target_width,
+        target_height,
+        options.filter,
+        options.linear,
+        options.premultiply_alpha,
+    )?;
+    Ok((resized, full_size))
+}
+
+/// EXIF の Orientation を画素に適用し、表示される向きの画像にする
+/// 変形処理の座標は表示される向きで扱い、出力に引き継ぐ EXIF は Orientation を 1 に書き換える
+pub(crate) fn apply_exif_orientation(mut img: DynamicImage, exif: Option<&[u8]>) -> DynamicImage {
+    let orientation = exif
+        .and_then(exif_orientation)
+        .and_then(|value| Orientation::from_exif(value as u8));
+    if let Some(orientation) = orientation.filter(|o| *o != Orientation::NoTransforms) {
+        eprintln!("Transform: Applying EXIF orientation {:?}...", orientation);
+        img.apply_orientation(orientation);
+    }
+    img
+}
+
+/// 1つの変形処理を適用する
+/// 回転・反転は `imageops` の関数 (DynamicImage の各メソッド) をそのまま使用する
+fn apply_operation(
+    img: DynamicImage,
+    operation: &TransformOperation,
+) -> Result<DynamicImage, AppError> {
+    Ok(match *operation {
+        TransformOperation::Crop {
+            x,
+            y,
+            width,
+            height,
+        } => {
+            let in_bounds = width > 0
+                && height > 0
+                && x.checked_add(width).is_some_and(|r| r <= img.width())
+                && y.checked_add(height).is_some_and(|b| b <= img.height());
+            if !in_bounds {
+                return Err(AppError::Transform(format!(
+                    "Crop region {}x{}+{}+{} is outside of the {}x{} image",
+                    width,
+                    height,
+                    x,
+                    y,
+                    img.width(),
+                    img.height()
+                )));
+            }
+            eprintln!("Transform: Cropping {}x{}+{}+{}...", width, height, x, y);
+            img.crop_imm(x, y, width, height)
+        }
+        TransformOperation::Rotate90 => img.rotate90(),
+        TransformOperation::Rotate180 => img.rotate180(),
+        TransformOperation::Rotate270 => img.rotate270(),
+        TransformOperation::FlipHorizontal => img.fliph(),
+        TransformOperation::FlipVertical => img.flipv(),
+        TransformOperation::Trim { tolerance } => match trim_bounds(&img, tolerance) {
+            Some((x, y, width, height)) => {
+                eprintln!(
+                    "Transform: Trimming border to {}x{}+{}+{}...",
+                    width, height, x, y
+                );
+                img.crop_imm(x, y, width, height)
+            }
+            None => img,
+        },
+    })
+}
+
+/// 余白を除いた範囲 (x, y, 幅, 高さ) を求める
+/// 左上の画素が透明な場合は完全に透明な画素を、そうでない場合は左上と同じ色 (許容値以内) の画素を余白とみなす
+/// 余白が無い場合や、画像全体が余白の場合は None を返す
+fn trim_bounds(img: &DynamicImage, tolerance: u8) -> Option<(u32, u32, u32, u32)> {
+    let rgba = img.to_rgba8();
+    let (width, height) = rgba.dimensions();
+    let corner = rgba.get_pixel(0, 0).0;
+    let is_background = |p: [u8; 4]| {
+        if corner[3] == 0 {
+            p[3] <= tolerance
+        } else {
+            p.iter()
+                .zip(corner.iter())
+                .all(|(a, b)| a.abs_diff(*b) <= tolerance)
+        }
+    };
+
+    let (mut left, mut top, mut right, mut bottom) = (width, height, 0, 0);
+    for (x, y, p) in rgba.enumerate_pixels() {
+        if !is_background(p.0) {
+            left = left.min(x);
+            top = top.min(y);
+            right = right.max(x + 1);
+            bottom = bottom.max(y + 1);
+        }
+    }
+
+    if left >= right || top >= bottom {
+        return None;
+    }
+    if (left, top, right, bottom) == (0, 0, width, height) {
+        return None;
+    }
+    Some((left, top, right - left, bottom - top))
+}
+
+/// リサイズ後のサイズと切り抜きサイズ
+struct ResizePlan {
+    width: u32,
+    height: u32,
+    crop: Option<(u32, u32)>,
+}
+
+/// 元のサイズとオプションからリサイズ後のサイズを計算する
+/// リサイズが不要な場合は None を返す
+/// 極端なアスペクト比や 0 の指定でも、幅・高さ・切り抜きサイズは 1 以上になる
+fn plan_resize(src_width: u32, src_height: u32, options: &TransformOptions) -> Option<ResizePlan> {
+    let (sw, sh) = (src_width as f64, src_height as f64);
+    let scaled = |scale: f64| {
+        (
+            ((sw * scale).round() as u32).max(1),
+            ((sh * scale).round() as u32).max(1),
+        )
+    };
+
+    // 1. 指定サイズとフィットモードの適用
+    let (mut width, mut height, mut crop) = match (options.width, options.height) {
+        (None, None) => (src_width, src_height, None),
+        // 片方のみ指定された場合はアスペクト比を保って拡縮する
+        (Some(w), None) => {
+            let (w, h) = scaled(w as f64 / sw);
+            (w, h, None)
+        }
+        (None, Some(h)) => {
+            let (w, h) = scaled(h as f64 / sh);
+            (w, h, None)
+        }
+        (Some(w), Some(h)) => {
+            let (sx, sy) = (w as f64 / sw, h as f64 / sh);
+            match options.fit {
+                FitMode::Contain => {
+                    let (w, h) = scaled(sx.min(sy));
+                    (w, h, None)
+                }
+                FitMode::Inside => {
+                    let (w, h) = scaled(sx.min(sy).min(1.0));
+                    (w, h, None)
+                }
+                FitMode::Cover => {
+                    let (cw, ch) = scaled(sx.max(sy));
+                    (cw, ch, Some((w.clamp(1, cw), h.clamp(1, ch))))
+                }
+                FitMode::Fill => (w.max(1), h.max(1), None),
+            }
+        }
+    };
+
+    // 2. 最大サイズの制限 (縮小のみ、アスペクト比を維持)
+    let (out_width, out_height) = crop.unwrap_or((width, height));
+    let limit = [
+        options.max_width.map(|m| m as f64 / out_width as f64),
+        options.max_height.map(|m| m as f64 / out_height as f64),
+    ]
+    .into_iter()
+    .flatten()
+    .fold(1.0_f64, f64::min);
+    if limit < 1.0 {
+        let scale = |v: u32| ((v as f64 * limit).round() as u32).max(1);
+        crop = crop.map(|(w, h)| (scale(w), scale(h)));
+        width = scale(width);
+        height = scale(height);
+        // 丸め誤差で切り抜きサイズが上回らないようにする
+        crop = crop.map(|(w, h)| (w.min(width), h.min(height)));
+    }
+
+    if width == src_width && height == src_height && crop.is_none_or(|c| c == (width, height)) {
+        return None;
+    }
+    Some(ResizePlan {
+        width,
+        height,
+        crop,
+    })
+}
+
+/// リサンプリングフィルターのカーネル
+impl ResizeFilter {
+    /// カーネルの半径
+    fn support(self) -> f32 {
+        match self {
+            ResizeFilter::Lanczos3 => 3.0,
+            ResizeFilter::Mitchell | ResizeFilter::CatmullRom => 2.0,
+        }
+    }
+
+    fn weight(self, x: f32) -> f32 {
+        match self {
+            ResizeFilter::Lanczos3 => lanczos(x, 3.0),
+            ResizeFilter::Mitchell => cubic_bc(x, 1.0 / 3.0, 1.0 / 3.0),
+            ResizeFilter::CatmullRom => cubic_bc(x, 0.0, 0.5),
+        }
+    }
+}
+
+fn sinc(x: f32) -> f32 {
+    if x == 0.0 {
+        1.0
+    } else {
+        let a = x * std::f32::consts::PI;
+        a.sin() / a
+    }
+}
+
+fn lanczos(x: f32, a: f32) -> f32 {
+    if x.abs() < a {
+        sinc(x) * sinc(x / a)
+    } else {
+        0.0
+    }
+}
+
+/// Mitchell-Netravali の BC スプライン
+fn cubic_bc(x: f32, b: f32, c: f32) -> f32 {
+    let x = x.abs();
+    if x < 1.0 {
+        ((12.0 - 9.0 * b - 6.0 * c) * x.powi(3)
+            + (-18.0 + 12.0 * b + 6.0 * c) * x.powi(2)
+            + (6.0 - 2.0 * b))
+            / 6.0
+    } else if x < 2.0 {
+        ((-b - 6.0 * c) * x.powi(3)
+            + (6.0 * b + 30.0 * c) * x.powi(2)
+            + (-12.0 * b - 48.0 * c) * x
+            + (8.0 * b + 24.0 * c))
+            / 6.0
+    } else {
+        0.0
+    }
+}
+
+fn srgb_to_linear(v: f32) -> f32 {
+    if v <= 0.04045 {
+        v / 12.92
+    } else {
+        ((v + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+fn linear_to_srgb(v: f32) -> f32 {
+    if v <= 0.003_130_8 {
+        v * 12.92
+    } else {
+        1.055 * v.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+/// 出力の各画素に対する入力画素の範囲と重み
+struct Contribution {
+    start: usize,
+    weights: Vec<f32>,
+}
+
+/// 1次元方向の重みを計算する
+fn contributions(src_len: u32, dst_len: u32, filter: ResizeFilter) -> Vec<Contribution> {
+    let scale = src_len as f32 / dst_len as f32;
+    // 縮小時はカーネルを広げてエイリアシングを防ぐ
+    let filter_scale = scale.max(1.0);
+    let support = filter.support() * filter_scale;
+
+    (0..dst_len)
+        .map(|i| {
+            let center = (i as f32 + 0.5) * scale;
+            let start = (center - support).floor().max(0.0) as usize;
+            let end = ((center + support).ceil() as usize).min(src_len as usize);
+            let mut weights: Vec<f32> = (start..end)
+                .map(|j| filter.weight((j as f32 + 0.5 - center) / filter_scale))
+                .collect();
+            let sum: f32 = weights.iter().sum();
+            if sum != 0.0 {
+                weights.iter_mut().for_each(|w| *w /= sum);
+            }
+            Contribution { start, weights }
+        })
+        .collect()
+}
+
+/// 画像を指定サイズにリサンプリングする
+/// # 注意
+/// - `linear` が true の場合はリニアライトに変換してから処理します (ガンマ補正済み縮小)。
+/// - `premultiply` が true の場合は乗算済みアルファで処理し、透明部分の色の滲みを防ぎます。
+fn resample(
+    img: &DynamicImage,
+    width: u32,
+    height: u32,
+    filter: ResizeFilter,
+    linear: bool,
+    premultiply: bool,
+) -> Result<DynamicImage, AppError> {
+    let has_alpha = img.color().has_alpha();
+    let (src_width, src_height) = (img.width() as usize, img.height() as usize);
+    let mut src = img.to_rgba32f().into_raw();
+
+    for px in src.chunks_exact_mut(4) {
+        if linear {
+            px[..3].iter_mut().for_each(|v| *v = srgb_to_linear(*v));
+        }
+        if premultiply {
+            let a = px[3];
+            px[..3].iter_mut().for_each(|v| *v *= a);
+        }
+    }
+
+    // 水平方向
+    let horizontal = contributions(src_width as u32, width, filter);
+    let dst_width = width as usize;
+    let mut tmp = vec![0f32; dst_width * src_height * 4];
+    for y in 0..src_height {
+        let row = &src[y * src_width * 4..(y + 1) * src_width * 4];
+        for (x, c) in horizontal.iter().enumerate() {
+            let out = &mut tmp[(y * dst_width + x) * 4..(y * dst_width + x + 1) * 4];
+            for (k, w) in c.weights.iter().enumerate() {
+                let px = &row[(c.start + k) * 4..(c.start + k + 1) * 4];
+                for ch in 0..4 {
+                    out[ch] += px[ch] * w;
+                }
+            }
+        }
+    }
+
+    // 垂直方向
+    let vertical = contributions(src_height as u32, height, filter);
+    let mut dst = vec![0f32; dst_width * height as usize * 4];
+    for (y, c) in vertical.iter().enumerate() {
+        let out_row = &mut dst[y * dst_width * 4..(y + 1) * dst_width * 4];
+        for (k, w) in c.weights.iter().enumerate() {
+            let row = &tmp[(c.start + k) * dst_width * 4..(c.start + k + 1) * dst_width * 4];
+            for (o, v) in out_row.iter_mut().zip(row) {
+                *o += v * w;
+            }
+        }
+    }
+
+    let to_u8 = |v: f32| (v.clamp(0.0, 1.0) * 255.0).round() as u8;
+    let mut out = Vec::with_capacity(dst.len());
+    for px in dst.chunks_exact_mut(4) {
+        let a = px[3].clamp(0.0, 1.0);
+        if premultiply && a > 0.0 {
+            px[..3].iter_mut().for_each(|v| *v /= a);
+        }
+        if linear {
+            px[..3]
+                .iter_mut()
+                .for_each(|v| *v = linear_to_srgb(v.max(0.0)));
+        }
+        out.extend(px[..3].iter().map(|v| to_u8(*v)));
+        if has_alpha {
+            out.push(to_u8(a));
+        }
+    }
+
+    let resized = if has_alpha {
+        RgbaImage::from_raw(width, height, out).map(DynamicImage::ImageRgba8)
+    } else {
+        RgbImage::from_raw(width, height, out).map(DynamicImage::ImageRgb8)
+    };
+    resized.ok_or(AppError::Transform(
+        "Failed to create resized ImageBuffer".to_string(),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::Rgba;
+
+    /// (幅, 高さ, 切り抜きサイズ)
+    type Planned = (u32, u32, Option<(u32, u32)>);
+
+    fn plan(src: (u32, u32), size: (Option<u32>, Option<u32>), fit: FitMode) -> Option<Planned> {
+        let options = TransformOptions {
+            width: size.0,
+            height: size.1,
+            fit,
+            ..Default::default()
+        };
+        plan_resize(src.0, src.1, &options).map(|p| (p.width, p.height, p.crop))
+    }
+
+    #[test]
+    fn plan_resize_fit_modes() {
+        let size = (Some(100), Some(100));
+        assert_eq!(
+            plan((400, 200), size, FitMode::Contain),
+            Some((100, 50, None))
+        );
+        assert_eq!(
+            plan((400, 200), size, FitMode::Cover),
+            Some((200, 100, Some((100, 100))))
+        );
+        assert_eq!(
+            plan((400, 200), size, FitMode::Fill),
+            Some((100, 100, None))
+        );
+        assert_eq!(
+            plan((400, 200), size, FitMode::Inside),
+            Some((100, 50, None))
+        );
+        // Inside は拡大しない、Contain は拡大する
+        let size = (Some(1000), Some(1000));
+        assert_eq!(plan((400, 200), size, FitMode::Inside), None);
+        assert_eq!(
+            plan((400, 200), size, FitMode::Contain),
+            Some((1000, 500, None))
+        );
+    }
+
+    #[test]
+    fn plan_resize_single_dimension_and_max() {
+        assert_eq!(
+            plan((400, 200), (Some(200), None), FitMode::Contain),
+            Some((200, 100, None))
+        );
+        assert_eq!(
+            plan((400, 200), (None, Some(50)), FitMode::Contain),
+            Some((100, 50, None))
+        );
+        assert_eq!(plan((400, 200), (None, None), FitMode::Contain), None);
+
+        let options = TransformOptions {
+            max_width: Some(100),
+            max_height: Some(100),
+            ..Default::default()
+        };
+        let p = plan_resize(400, 200, &options).unwrap();
+        assert_eq!((p.width, p.height, p.crop), (100, 50, None));
+        // 上限より小さい場合は拡大しない
+        assert!(plan_resize(80, 40, &options).is_none());
+    }
+
+    #[test]
+    fn plan_resize_is_at_least_one_pixel() {
+        for fit in [
+            FitMode::Contain,
+            FitMode::Cover,
+            FitMode::Fill,
+            FitMode::Inside,
+        ] {
+            for (src, size) in [
+                ((10000, 1), (Some(10), Some(10))),
+                ((1, 10000), (Some(10), Some(10))),
+                ((100, 100), (Some(0), Some(10))),
+                ((100, 100), (Some(0), Some(0))),
+                ((10000, 1), (Some(1), None)),
+            ] {
+                let (width, height, crop) = plan(src, size, fit).unwrap();
+                assert!(width >= 1 && height >= 1, "{:?} {:?} {:?}", fit, src, size);
+                if let Some((cw, ch)) = crop {
+                    assert!(cw >= 1 && ch >= 1 && cw <= width && ch <= height);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn resample_keeps_constant_image() {
+        let img =
+            DynamicImage::ImageRgba8(RgbaImage::from_pixel(37, 23, Rgba([200, 100, 50, 160])));
+        for filter in [
+            ResizeFilter::Lanczos3,
+            ResizeFilter::Mitchell,
+            ResizeFilter::CatmullRom,
+        ] {
+            for (width, height) in [(10, 7), (80, 60), (1, 1)] {
+                for (linear, premultiply) in [(false, false), (true, true)] {
+                    let resized = resample(&img, width, height, filter, linear, premultiply)
+                        .unwrap()
+                        .to_rgba8();
+                    assert_eq!(resized.dimensions(), (width, height));
+                    for px in resized.pixels() {
+                        for (a, e) in px.0.iter().zip([200u8, 100, 50, 160]) {
+                            assert!(a.abs_diff(e) <= 1, "{:?} {:?}", filter, px);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn resample_premultiplied_alpha_has_no_halo() {
+        // 左半分は不透明な赤、右半分は色情報が緑の完全な透明
+        let img = DynamicImage::ImageRgba8(RgbaImage::from_fn(32, 32, |x, _| {
+            if x < 16 {
+                Rgba([255, 0, 0, 255])
+            } else {
+                Rgba([0, 255, 0, 0])
+            }
+        }));
+        for filter in [
+            ResizeFilter::Lanczos3,
+            ResizeFilter::Mitchell,
+            ResizeFilter::CatmullRom,
+        ] {
+            let resized = resample(&img, 13, 13, filter, true, true)
+                .unwrap()
+                .to_rgba8();
+            let edge = resized
+                .pixels()
+                .filter(|px| px[3] > 0 && px[3] < 255)
+                .count();
+            assert!(edge > 0);
+            for px in resized.pixels().filter(|px| px[3] > 0) {
+                assert!(px[0] >= 250 && px[1] <= 2, "{:?} {:?}", filter, px);
+            }
+        }
+    }
+
+    #[test]
+    fn region_excludes_cover_crop() {
+        let img = DynamicImage::ImageRgb8(RgbImage::new(40, 20));
+        let options = TransformOptions {
+            width: Some(10),
+            height: Some(10),
+            fit: FitMode::Cover,
+            ..Default::default()
+        };
+        // 縮小後は 20x10 で、中央の 10x10 (元の座標では x = 10..30) が残る
+        let (region, full) = apply_transform_region(img.clone(), &options, (0, 0, 40, 20)).unwrap();
+        assert_eq!(full, (10, 10));
+        assert_eq!((region.width(), region.height()), (10, 10));
+        let (region, _) = apply_transform_region(img.clone(), &options, (0, 0, 15, 20)).unwrap();
+        assert_eq!((region.width(), region.height()), (3, 10));
+        assert!(apply_transform_region(img, &options, (0, 0, 10, 20)).is_err());
+    }
+}
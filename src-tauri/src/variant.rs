@@ -0,0 +1,411 @@
+use crate::decoder::{decode, read_color_profile, read_exif};
+use crate::encoder::{Metadata, encode};
+use crate::error::AppError;
+use crate::options::{
+    EncodeOptions, Manifest, ManifestImage, ManifestOptions, ManifestSource, OutputFormat,
+    Placeholder, PlaceholderKind, TransformOptions, VariantPlan, WebpOptions,
+};
+use crate::pipeline::prepare_image;
+use crate::transform::{apply_exif_orientation, apply_transform};
+use base64::{Engine, engine::general_purpose::STANDARD as BASE64};
+use image::DynamicImage;
+use std::collections::{HashMap, HashSet};
+use std::path::{Component, Path};
+
+/// 生成された1つの出力
+pub struct Variant {
+    pub file_name: String,
+    pub width: u32,
+    pub height: u32,
+    pub format: OutputFormat,
+    pub data: Vec<u8>,
+}
+
+/// 生成結果 (画像の一覧とマニフェスト)
+pub struct VariantSet {
+    pub variants: Vec<Variant>,
+    pub manifest: Option<Manifest>,
+}
+
+/// 1つの入力画像から、生成計画に従って複数のサイズ・形式の画像を生成します。
+/// # 引数
+/// - `data`: 変換対象の画像データのバイト列
+/// - `name`: 出力ファイル名に使う元のファイル名 (拡張子無し)
+/// - `plan`: 生成計画
+/// # 戻り値
+/// - 成功した場合は生成された画像の一覧と、指定されていればマニフェストを返します。
+/// - 失敗した場合は `AppError` を返します。
+/// # 注意
+/// - デコードは一度だけ行い、各出力で使い回します。
+/// - 拡大が許可されていない場合、元画像より大きい幅は元画像の幅に揃え、重複する出力は1つにまとめます。
+/// - 幅と形式が同じでオプションが異なる出力や、ファイル名が重複する出力がある場合は `AppError::InvalidInput` を返します。
+/// - 展開後のファイル名がパスの区切り文字・`..`・絶対パスを含む場合は `AppError::InvalidInput` を返します。
+pub fn generate_variants(
+    data: &[u8],
+    name: &str,
+    plan: &VariantPlan,
+) -> Result<VariantSet, AppError> {
+    // デコード前にパターンを確かめる
+    variant_file_name(&plan.file_name_pattern, name, 1, OutputFormat::Png)?;
+
+    eprintln!("Decoding...");
+    let img = apply_exif_orientation(decode(data)?, read_exif(data).as_deref());
+    let profile = read_color_profile(data);
+
+    let mut seen = HashMap::new();
+    let mut file_names = HashSet::new();
+    let mut variants = Vec::with_capacity(plan.targets.len());
+    for target in &plan.targets {
+        let width = if plan.allow_upscale {
+            target.width
+        } else {
+            target.width.min(img.width())
+        };
+        // 同じ幅・形式の出力は、オプションも同じ場合のみ1つにまとめる
+        let target_options = serde_json::to_value(&target.options).ok();
+        if let Some(options) = seen.get(&(width, target.format)) {
+            if *options == target_options {
+                continue;
+            }
+            return Err(AppError::InvalidInput(format!(
+                "Variants for {}w {} have different options",
+                width,
+                target.format.extension()
+            )));
+        }
+        seen.insert((width, target.format), target_options);
+
+        let mut options = target.options.select(target.format).ok_or_else(|| {
+            AppError::Encode(format!(
+                "Missing {} options for {}w variant",
+                target.format.extension(),
+                target.width
+            ))
+        })?;
+        // 幅のみ指定し、その他の変形オプション (フィルターなど) は引き継ぐ
+        let mut transform = options.transform.take().unwrap_or_default();
+        transform.width = Some(width);
+        transform.height = None;
+        transform.max_width = None;
+        transform.max_height = None;
+        options.transform = Some(transform);
+
+        eprintln!(
+            "Variant: Generating {}w {}...",
+            width,
+            target.format.extension()
+        );
+        let (resized, icc_profile) = prepare_image(img.clone(), profile.clone(), &options)?;
+        let (out_width, out_height) = (resized.width(), resized.height());
+        let metadata = Metadata {
+            icc_profile,
+            exif: None,
+        };
+        let file_name = variant_file_name(&plan.file_name_pattern, name, out_width, target.format)?;
+        if !file_names.insert(file_name.clone()) {
+            return Err(AppError::InvalidInput(format!(
+                "Duplicate variant file name: {}",
+                file_name
+            )));
+        }
+        let encoded = encode(&resized, options, &metadata)?;
+
+        variants.push(Variant {
+            file_name,
+            width: out_width,
+            height: out_height,
+            format: target.format,
+            data: encoded,
+        });
+    }
+
+    let manifest = match &plan.manifest {
+        Some(manifest_options) => {
+            // プレースホルダーは sRGB に変換した画像から作成する
+            let placeholder = match manifest_options.placeholder {
+                PlaceholderKind::None => None,
+                kind => {
+                    let (srgb, _) = prepare_image(img, profile, &EncodeOptions::default())?;
+                    Some(create_placeholder(&srgb, kind)?)
+                }
+            };
+            build_manifest(name, &variants, placeholder, manifest_options)
+        }
+        None => None,
+    };
+
+    Ok(VariantSet { variants, manifest })
+}
+
+/// ファイル名のパターンを展開する
+/// 出力先のディレクトリの外に書き込まないよう、1つのファイル名以外 (区切り文字・`..`・絶対パス) は拒否する
+fn variant_file_name(
+    pattern: &str,
+    name: &str,
+    width: u32,
+    format: OutputFormat,
+) -> Result<String, AppError> {
+    let file_name = pattern
+        .replace("{name}", name)
+        .replace("{width}", &width.to_string())
+        .replace("{ext}", format.extension());
+    let mut components = Path::new(&file_name).components();
+    let single = matches!(
+        (components.next(), components.next()),
+        (Some(Component::Normal(_)), None)
+    );
+    if !single || file_name.contains(['/', '\\']) {
+        return Err(AppError::InvalidInput(format!(
+            "Invalid variant file name: {}",
+            file_name
+        )));
+    }
+    Ok(file_name)
+}
+
+/// 縮小した画像からプレースホルダーを作成する
+fn create_placeholder(img: &DynamicImage, kind: PlaceholderKind) -> Result<Placeholder, AppError> {
+    let shrink = |width: u32| {
+        apply_transform(
+            img.clone(),
+            &TransformOptions {
+                width: Some(width.min(img.width())),
+                ..Default::default()
+            },
+        )
+    };
+
+    match kind {
+        PlaceholderKind::Blurhash => {
+            let small = shrink(32)?.to_rgba8();
+            let hash = blurhash::encode(4, 3, small.width(), small.height(), small.as_raw())
+                .map_err(|e| AppError::encode("BlurHash", e))?;
+            Ok(Placeholder::Blurhash(hash))
+        }
+        _ => {
+            let small = shrink(24)?.blur(1.0);
+            let webp = encode(
+                &small,
+                EncodeOptions {
+                    webp: Some(WebpOptions {
+                        quality: 40.0,
+                        lossless: false,
+                    }),
+                    ..Default::default()
+                },
+                &Metadata::default(),
+            )?;
+            Ok(Placeholder::Lqip(format!(
+                "data:image/webp;base64,{}",
+                BASE64.encode(webp)
+            )))
+        }
+    }
+}
+
+/// 生成された画像の一覧からマニフェストと `<picture>` の HTML を作成する
+/// 画像が1つも無い場合は None を返す
+fn build_manifest(
+    name: &str,
+    variants: &[Variant],
+    placeholder: Option<Placeholder>,
+    options: &ManifestOptions,
+) -> Option<Manifest> {
+    let url = |variant: &Variant| format!("{}{}", options.url_prefix, variant.file_name);
+
+    // 新しい形式を先に並べ、ブラウザが対応する最初の形式を選べるようにする
+    let sources: Vec<ManifestSource> = [OutputFormat::Avif, OutputFormat::Webp]
+        .into_iter()
+        .filter_map(|format| {
+            let mut items: Vec<&Variant> = variants.iter().filter(|v| v.format == format).collect();
+            if items.is_empty() {
+                return None;
+            }
+            items.sort_by_key(|v| v.width);
+            let srcset = items
+                .iter()
+                .map(|v| format!("{} {}w", url(v), v.width))
+                .collect::<Vec<_>>()
+                .join(", ");
+            Some(ManifestSource {
+                mime_type: format.mime_type().to_string(),
+                srcset,
+                sizes: options.sizes.clone(),
+            })
+        })
+        .collect();
+
+    // フォールバックは互換性の高い JPEG / PNG / WebP の最大幅 (無ければ全形式の最大幅)
+    let largest = |format: Option<OutputFormat>| {
+        variants
+            .iter()
+            .filter(|v| format.is_none_or(|f| v.format == f))
+            .max_by_key(|v| v.width)
+    };
+    let fallback = largest(Some(OutputFormat::Jpeg))
+        .or_else(|| largest(Some(OutputFormat::Png)))
+        .or_else(|| largest(Some(OutputFormat::Webp)))
+        .or_else(|| largest(None))?;
+    let fallback = ManifestImage {
+        src: url(fallback),
+        width: fallback.width,
+        height: fallback.height,
+    };
+
+    let html = render_picture_html(&sources, &fallback, placeholder.as_ref(), &options.alt);
+    Some(Manifest {
+        name: name.to_string(),
+        sources,
+        fallback,
+        placeholder,
+        html,
+    })
+}
+
+/// `<picture>` の HTML スニペットを作成する
+fn render_picture_html(
+    sources: &[ManifestSource],
+    fallback: &ManifestImage,
+    placeholder: Option<&Placeholder>,
+    alt: &str,
+) -> String {
+    let mut html = String::from("<picture>\n");
+    for source in sources {
+        html.push_str(&format!(
+            "  <source type=\"{}\" srcset=\"{}\" sizes=\"{}\">\n",
+            source.mime_type,
+            escape_html(&source.srcset),
+            escape_html(&source.sizes)
+        ));
+    }
+    let placeholder_attr = match placeholder {
+        Some(Placeholder::Lqip(uri)) => format!(
+            " style=\"background-size:cover;background-image:url({})\"",
+            escape_html(uri)
+        ),
+        Some(Placeholder::Blurhash(hash)) => format!(" data-blurhash=\"{}\"", escape_html(hash)),
+        None => String::new(),
+    };
+    html.push_str(&format!(
+        "  <img src=\"{}\" width=\"{}\" height=\"{}\" alt=\"{}\" loading=\"lazy\" decoding=\"async\"{}>\n",
+        escape_html(&fallback.src),
+        fallback.width,
+        fallback.height,
+        escape_html(alt),
+        placeholder_attr
+    ));
+    html.push_str("</picture>\n");
+    html
+}
+
+/// HTML の属性値として安全な文字列にエスケープする
+fn escape_html(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('"', "&quot;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::options::{PngOptions, VariantTarget};
+    use image::{ImageFormat, RgbImage};
+    use std::io::Cursor;
+
+    fn png(width: u32, height: u32) -> Vec<u8> {
+        let mut data = Vec::new();
+        DynamicImage::ImageRgb8(RgbImage::new(width, height))
+            .write_to(&mut Cursor::new(&mut data), ImageFormat::Png)
+            .unwrap();
+        data
+    }
+
+    fn target(width: u32, level: u8) -> VariantTarget {
+        VariantTarget {
+            width,
+            format: OutputFormat::Png,
+            options: EncodeOptions {
+                png: Some(PngOptions {
+                    level,
+                    ..Default::default()
+                }),
+                ..Default::default()
+            },
+        }
+    }
+
+    fn plan(targets: Vec<VariantTarget>, pattern: &str) -> VariantPlan {
+        VariantPlan {
+            targets,
+            file_name_pattern: pattern.to_string(),
+            allow_upscale: false,
+            manifest: None,
+        }
+    }
+
+    #[test]
+    fn file_name_pattern_is_expanded() {
+        assert_eq!(
+            variant_file_name("{name}-{width}w.{ext}", "photo", 640, OutputFormat::Webp).unwrap(),
+            "photo-640w.webp"
+        );
+    }
+
+    #[test]
+    fn file_name_must_stay_in_output_dir() {
+        for pattern in [
+            "../{name}.{ext}",
+            "sub/{name}.{ext}",
+            "sub\\{name}.{ext}",
+            "/tmp/{name}.{ext}",
+            "..",
+            ".",
+            "",
+        ] {
+            assert!(
+                matches!(
+                    variant_file_name(pattern, "photo", 640, OutputFormat::Png),
+                    Err(AppError::InvalidInput(_))
+                ),
+                "{pattern}"
+            );
+        }
+    }
+
+    #[test]
+    fn clamped_duplicates_are_merged() {
+        let set = generate_variants(
+            &png(8, 4),
+            "photo",
+            &plan(
+                vec![target(4, 2), target(16, 2), target(32, 2)],
+                "{name}-{width}w.{ext}",
+            ),
+        )
+        .unwrap();
+        let widths: Vec<u32> = set.variants.iter().map(|v| v.width).collect();
+        assert_eq!(widths, vec![4, 8]);
+    }
+
+    #[test]
+    fn duplicates_with_different_options_are_rejected() {
+        let result = generate_variants(
+            &png(8, 4),
+            "photo",
+            &plan(vec![target(16, 2), target(32, 4)], "{name}-{width}w.{ext}"),
+        );
+        assert!(matches!(result, Err(AppError::InvalidInput(_))));
+    }
+
+    #[test]
+    fn duplicate_file_names_are_rejected() {
+        let result = generate_variants(
+            &png(8, 4),
+            "photo",
+            &plan(vec![target(4, 2), target(8, 2)], "{name}.{ext}"),
+        );
+        assert!(matches!(result, Err(AppError::InvalidInput(_))));
+    }
+}
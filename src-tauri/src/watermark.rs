@@ -0,0 +1,363 @@
+use crate::decoder::decode;
+use crate::error::AppError;
+use crate::options::{Anchor, TransformOptions, WatermarkOptions};
+use crate::transform::apply_transform;
+use ab_glyph::{Font, FontVec, PxScale, ScaleFont, point};
+use image::{DynamicImage, RgbaImage};
+use std::fs;
+
+/// フォントが指定されていない場合に使う同梱のフォント (DejaVu Sans、ライセンスは fonts/LICENSE)
+/// OS によって結果が変わらないよう、システムフォントは探さない
+const BUNDLED_FONT: &[u8] = include_bytes!("../fonts/DejaVuSans.ttf");
+
+/// 画像に透かし (ロゴ画像またはテキスト) を合成する
+/// # 引数
+/// - `img`: 合成先の画像
+/// - `options`: 透かしのオプション
+/// # 戻り値
+/// - 成功した場合は合成後の `DynamicImage` を返します。
+/// - 失敗した場合は `AppError` を返します。
+/// # 注意
+/// - `image_path` と `text` の両方が指定された場合は画像を優先します。
+/// - 透かしの幅は `scale` × 画像の幅になるように拡縮します。
+/// - フォントが指定されていない場合は、同梱のフォント (DejaVu Sans) を使用します。
+pub fn apply_watermark(
+    img: DynamicImage,
+    options: &WatermarkOptions,
+) -> Result<DynamicImage, AppError> {
+    let target_width = ((img.width() as f32 * options.scale).round() as u32).max(1);
+
+    let overlay = if let Some(path) = &options.image_path {
+        eprintln!("Watermark: Compositing image {}...", path);
+        let data = fs::read(path).map_err(|e| AppError::file("read", path, e))?;
+        apply_transform(
+            decode(&data)?,
+            &TransformOptions {
+                width: Some(target_width),
+                ..Default::default()
+            },
+        )?
+        .into_rgba8()
+    } else if let Some(text) = options.text.as_deref().filter(|t| !t.is_empty()) {
+        eprintln!("Watermark: Rendering text...");
+        let font = load_font(options.font_path.as_deref())?;
+        render_text(&font, text, target_width, options.color)
+    } else {
+        return Ok(img);
+    };
+
+    let has_alpha = img.color().has_alpha();
+    let mut base = img.into_rgba8();
+    let opacity = options.opacity.clamp(0.0, 1.0);
+    let (width, height) = base.dimensions();
+    let (ow, oh) = overlay.dimensions();
+    let margin = options.margin;
+
+    if options.tile {
+        // 余白を間隔として画像全体に敷き詰める
+        let mut y = margin;
+        while y < height {
+            let mut x = margin;
+            while x < width {
+                blend(&mut base, &overlay, x, y, opacity);
+                x += ow + margin.max(1);
+            }
+            y += oh + margin.max(1);
+        }
+    } else {
+        let place = |size: u32, len: u32, start: bool, center: bool| {
+            if center {
+                size.saturating_sub(len) / 2
+            } else if start {
+                margin
+            } else {
+                size.saturating_sub(len + margin)
+            }
+        };
+        let (left, center_x, top, center_y) = options.anchor.alignment();
+        let x = place(width, ow, left, center_x);
+        let y = place(height, oh, top, center_y);
+        blend(&mut base, &overlay, x, y, opacity);
+    }
+
+    Ok(if has_alpha {
+        DynamicImage::ImageRgba8(base)
+    } else {
+        DynamicImage::ImageRgb8(DynamicImage::ImageRgba8(base).into_rgb8())
+    })
+}
+
+impl Anchor {
+    /// (左寄せか, 水平中央か, 上寄せか, 垂直中央か)
+    fn alignment(self) -> (bool, bool, bool, bool) {
+        match self {
+            Anchor::TopLeft => (true, false, true, false),
+            Anchor::Top => (false, true, true, false),
+            Anchor::TopRight => (false, false, true, false),
+            Anchor::Left => (true, false, false, true),
+            Anchor::Center => (false, true, false, true),
+            Anchor::Right => (false, false, false, true),
+            Anchor::BottomLeft => (true, false, false, false),
+            Anchor::Bottom => (false, true, false, false),
+            Anchor::BottomRight => (false, false, false, false),
+        }
+    }
+}
+
+/// フォントを読み込む (未指定の場合は同梱のフォントを使う)
+fn load_font(path: Option<&str>) -> Result<FontVec, AppError> {
+    let data = match path {
+        Some(path) => fs::read(path).map_err(|e| AppError::file("read", path, e))?,
+        None => BUNDLED_FONT.to_vec(),
+    };
+    FontVec::try_from_vec(data).map_err(|e| AppError::transform("watermark font", e))
+}
+
+/// テキストを指定幅に収まる大きさで描画する
+fn render_text(font: &FontVec, text: &str, target_width: u32, color: [u8; 3]) -> RgbaImage {
+    // 文字列の幅はフォントサイズに比例するため、基準サイズで測ってから拡縮する
+    let base_size = 100.0;
+    let base_width = layout_width(font, text, base_size).max(1.0);
+    let size = base_size * target_width as f32 / base_width;
+
+    let scaled = font.as_scaled(PxScale::from(size));
+    let width = layout_width(font, text, size).ceil() as u32;
+    let height = (scaled.ascent() - scaled.descent()).ceil() as u32;
+    let mut canvas = RgbaImage::new(width.max(1), height.max(1));
+
+    let mut caret = 0.0;
+    let mut previous = None;
+    for c in text.chars() {
+        let id = scaled.glyph_id(c);
+        if let Some(prev) = previous {
+            caret += scaled.kern(prev, id);
+        }
+        let glyph = id.with_scale_and_position(size, point(caret, scaled.ascent()));
+        caret += scaled.h_advance(id);
+        previous = Some(id);
+
+        let Some(outlined) = font.outline_glyph(glyph) else {
+            continue;
+        };
+        let bounds = outlined.px_bounds();
+        outlined.draw(|x, y, coverage| {
+            let px = x as i32 + bounds.min.x as i32;
+            let py = y as i32 + bounds.min.y as i32;
+            if px < 0 || py < 0 || px as u32 >= canvas.width() || py as u32 >= canvas.height() {
+                return;
+            }
+            let pixel = canvas.get_pixel_mut(px as u32, py as u32);
+            let alpha = (coverage.clamp(0.0, 1.0) * 255.0).round() as u8;
+            *pixel = image::Rgba([color[0], color[1], color[2], pixel[3].max(alpha)]);
+        });
+    }
+
+    canvas
+}
+
+/// 指定サイズでのテキストの幅
+fn layout_width(font: &FontVec, text: &str, size: f32) -> f32 {
+    let scaled = font.as_scaled(PxScale::from(size));
+    let mut width = 0.0;
+    let mut previous = None;
+    for c in text.chars() {
+        let id = scaled.glyph_id(c);
+        if let Some(prev) = previous {
+            width += scaled.kern(prev, id);
+        }
+        width += scaled.h_advance(id);
+        previous = Some(id);
+    }
+    width
+}
+
+/// 透かしを不透明度を掛けてアルファ合成する
+fn blend(base: &mut RgbaImage, overlay: &RgbaImage, x: u32, y: u32, opacity: f32) {
+    for (ox, oy, src) in overlay.enumerate_pixels() {
+        let (bx, by) = (x + ox, y + oy);
+        if bx >= base.width() || by >= base.height() {
+            continue;
+        }
+        let a = src[3] as f32 / 255.0 * opacity;
+        if a <= 0.0 {
+            continue;
+        }
+        let dst = base.get_pixel_mut(bx, by);
+        let dst_a = dst[3] as f32 / 255.0;
+        let out_a = a + dst_a * (1.0 - a);
+        for c in 0..3 {
+            let v = (src[c] as f32 * a + dst[c] as f32 * dst_a * (1.0 - a)) / out_a;
+            dst[c] = v.round().clamp(0.0, 255.0) as u8;
+        }
+        dst[3] = (out_a * 255.0).round() as u8;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::Rgba;
+
+    #[test]
+    fn text_watermark_uses_bundled_font() {
+        let img = DynamicImage::ImageRgba8(RgbaImage::new(200, 100));
+        let options = WatermarkOptions {
+            text: Some("Sample".to_string()),
+            color: [255, 255, 255],
+            opacity: 1.0,
+            ..Default::default()
+        };
+        let result = apply_watermark(img, &options).unwrap().to_rgba8();
+        assert!(result.pixels().any(|px| px[3] > 0));
+    }
+
+    /// 10x5 の不透明な赤いロゴを一時ファイルに書き出す
+    fn logo_path(name: &str) -> String {
+        let path =
+            std::env::temp_dir().join(format!("watermark-{}-{}.png", name, std::process::id()));
+        RgbaImage::from_pixel(10, 5, Rgba([255, 0, 0, 255]))
+            .save(&path)
+            .unwrap();
+        path.to_string_lossy().into_owned()
+    }
+
+    /// 100x50 の白い画像にロゴを合成する (ロゴの幅は画像の 1/10 なので拡縮しない)
+    fn watermark(name: &str, options: WatermarkOptions) -> RgbaImage {
+        let path = logo_path(name);
+        let img = DynamicImage::ImageRgba8(RgbaImage::from_pixel(100, 50, Rgba([255; 4])));
+        let options = WatermarkOptions {
+            image_path: Some(path.clone()),
+            scale: 0.1,
+            opacity: 1.0,
+            ..options
+        };
+        let result = apply_watermark(img, &options).unwrap().to_rgba8();
+        fs::remove_file(path).unwrap();
+        result
+    }
+
+    /// 赤く塗られた画素を囲む範囲 (左, 上, 右, 下)
+    fn red_bounds(img: &RgbaImage) -> (u32, u32, u32, u32) {
+        let red: Vec<_> = img
+            .enumerate_pixels()
+            .filter(|(_, _, px)| px[1] < 255)
+            .map(|(x, y, _)| (x, y))
+            .collect();
+        (
+            red.iter().map(|p| p.0).min().unwrap(),
+            red.iter().map(|p| p.1).min().unwrap(),
+            red.iter().map(|p| p.0).max().unwrap() + 1,
+            red.iter().map(|p| p.1).max().unwrap() + 1,
+        )
+    }
+
+    #[test]
+    fn anchor_and_margin_place_the_overlay() {
+        for (anchor, left, top) in [
+            (Anchor::TopLeft, 8, 8),
+            (Anchor::Top, 45, 8),
+            (Anchor::Center, 45, 22),
+            (Anchor::Right, 82, 22),
+            (Anchor::BottomLeft, 8, 37),
+            (Anchor::BottomRight, 82, 37),
+        ] {
+            let options = WatermarkOptions {
+                anchor,
+                margin: 8,
+                ..Default::default()
+            };
+            let result = watermark(&format!("{:?}", anchor), options);
+            assert_eq!(
+                red_bounds(&result),
+                (left, top, left + 10, top + 5),
+                "{:?}",
+                anchor
+            );
+            assert_eq!(result.get_pixel(left, top).0, [255, 0, 0, 255]);
+        }
+    }
+
+    #[test]
+    fn opacity_blends_with_the_base() {
+        let options = WatermarkOptions {
+            anchor: Anchor::TopLeft,
+            margin: 0,
+            opacity: 0.5,
+            ..Default::default()
+        };
+        let path = logo_path("opacity");
+        let img =
+            DynamicImage::ImageRgb8(image::RgbImage::from_pixel(100, 50, image::Rgb([255; 3])));
+        let options = WatermarkOptions {
+            image_path: Some(path.clone()),
+            scale: 0.1,
+            ..options
+        };
+        let result = apply_watermark(img, &options).unwrap();
+        fs::remove_file(path).unwrap();
+        // 不透明な画像はアルファを持たないまま返す
+        let result = result.as_rgb8().unwrap();
+        assert_eq!(result.get_pixel(0, 0).0, [255, 128, 128]);
+        assert_eq!(result.get_pixel(10, 0).0, [255, 255, 255]);
+    }
+
+    #[test]
+    fn tile_repeats_with_margin_as_spacing() {
+        let options = WatermarkOptions {
+            tile: true,
+            margin: 5,
+            ..Default::default()
+        };
+        let result = watermark("tile", options);
+        // x = 5, 20, 35, ... / y = 5, 15, 25, ... にロゴが並ぶ
+        for (x, y, is_red) in [
+            (5, 5, true),
+            (14, 9, true),
+            (15, 5, false),
+            (20, 5, true),
+            (5, 10, false),
+            (5, 15, true),
+            (95, 45, true),
+            (4, 4, false),
+        ] {
+            assert_eq!(result.get_pixel(x, y)[1] < 255, is_red, "({}, {})", x, y);
+        }
+        let tiles = result
+            .enumerate_pixels()
+            .filter(|(_, _, px)| px[1] < 255)
+            .count();
+        // 横 7 枚 (最後は 5 px だけ) × 縦 5 枚
+        assert_eq!(tiles, (6 * 10 + 5) * 5 * 5);
+    }
+
+    #[test]
+    fn missing_image_reports_the_path() {
+        let options = WatermarkOptions {
+            image_path: Some("/no/such/logo.png".to_string()),
+            ..Default::default()
+        };
+        let img = DynamicImage::ImageRgba8(RgbaImage::new(10, 10));
+        let error = apply_watermark(img, &options).unwrap_err();
+        assert_eq!(error.code(), "file_not_found");
+        assert_eq!(
+            error.path(),
+            Some(std::path::Path::new("/no/such/logo.png"))
+        );
+    }
+
+    #[test]
+    fn invalid_font_keeps_the_cause() {
+        let path = std::env::temp_dir().join(format!("watermark-font-{}.ttf", std::process::id()));
+        fs::write(&path, b"not a font").unwrap();
+        let options = WatermarkOptions {
+            text: Some("Sample".to_string()),
+            font_path: Some(path.to_string_lossy().into_owned()),
+            ..Default::default()
+        };
+        let img = DynamicImage::ImageRgba8(RgbaImage::new(10, 10));
+        let error = apply_watermark(img, &options).unwrap_err();
+        fs::remove_file(path).unwrap();
+        assert_eq!(error.code(), "transform_failed");
+        assert!(error.details().unwrap()["sources"][0].is_string());
+    }
+}
@@ -1,5 +1,6 @@
+import type { AppError } from '@/interfaces/AppError';
 import { useGlobalStore, useSettingsStore } from '@/store';
-import { ref, type Ref, nextTick } from 'vue';
+import { ref, type Ref, nextTick, watch } from 'vue';
 import type { ComposerTranslation } from 'vue-i18n';
 
 import { listen } from '@tauri-apps/api/event';
@@ -16,6 +17,38 @@ import { useFileSystem } from './useFileSystem';
 import { useImageConverter } from './useImageConverter'; // 汎用コンバーターをインポート
 import { usePaste } from './usePaste';
 
+/**
+ * 例外やコマンドのエラー（`AppError`）から表示用のメッセージを取り出す
+ * @param e 捕捉した値
+ * @returns メッセージ
+ */
+const errorMessage = (e: unknown): string => {
+  if (e instanceof Error) {
+    return e.message;
+  }
+  if (typeof e === 'object' && e !== null && 'code' in e && 'message' in e) {
+    return (e as AppError).message;
+  }
+  return String(e);
+};
+
+/**
+ * 分離モードの子プロセスがクラッシュ・タイムアウトしたエラーか（そのファイルだけを失敗として次に進める）
+ * @param e 捕捉した値
+ */
+const isWorkerFailure = (e: unknown): boolean =>
+  typeof e === 'object' &&
+  e !== null &&
+  'code' in e &&
+  ['worker_crashed', 'worker_timeout'].includes((e as AppError).code);
+
+/**
+ * 一括変換の中止によるエラーか
+ * @param e 捕捉した値
+ */
+const isCancelled = (e: unknown): boolean =>
+  typeof e === 'object' && e !== null && 'code' in e && (e as AppError).code === 'cancelled';
+
 export function useImageConversionController(t: ComposerTranslation) {
   const globalStore = useGlobalStore();
   const fileSystem = useFileSystem();
@@ -24,7 +57,7 @@ export function useImageConversionController(t: ComposerTranslation) {
   const { play: playCompleteSound } = useSound(completeSound);
   const { play: playErrorSound } = useSound(errorSound);
 
-  const { convert, compress } = useImageConverter(); // コアロジックを取得
+  const { convert, compress, startBatch, cancelBatch } = useImageConverter(); // コアロジックを取得
 
   // --- UIの状態管理 ---
   const dialog = ref(false); // 進捗ダイアログ表示制御
@@ -32,13 +65,28 @@ export function useImageConversionController(t: ComposerTranslation) {
   const inProgress = ref(false); // 処理中フラグ
   const progress: Ref<number> = ref(0); // 進捗
 
+  // 進捗ダイアログのキャンセルで処理中フラグが下ろされたら、変換中のファイルも含めて中止する
+  watch(inProgress, value => {
+    if (!value && dialog.value) {
+      cancelBatch().catch(console.error);
+    }
+  });
+
   // 変換処理
   const processFiles = async (files: string[]) => {
     dialog.value = true;
     inProgress.value = true;
     progress.value = 0;
+    const failedFiles: string[] = [];
+    let cancelled = false;
+    await startBatch();
 
     for (let i = 0; i < files.length; i++) {
+      if (!inProgress.value) {
+        // キャンセルされた場合は残りのファイルを変換しない
+        cancelled = true;
+        break;
+      }
       const file = files[i];
       if (!file || !settingsStore.extensionPattern.test(file)) {
         // 拡張子がマッチしない場合はスキップ
@@ -52,34 +100,52 @@ export function useImageConversionController(t: ComposerTranslation) {
       currentFile.value = file;
       try {
         // 汎用コンバーターを呼び出す
-        await convert(
+        const result = await convert(
           file,
           settingsStore.commonOptions.sameDirectory
             ? undefined
             : settingsStore.commonOptions.outputPath
         );
-        if (settingsStore.commonOptions.deleteOriginal) {
-          // 元ファイル削除オプションが有効な場合、元ファイルを削除
+        console.info(
+          `${file}: ${result.outcome} (${result.inputSize} -> ${result.convertedSize} bytes, ${result.savingsPercent.toFixed(1)}%)`
+        );
+        if (settingsStore.commonOptions.deleteOriginal && result.outcome === 'Written') {
+          // 元ファイル削除オプションが有効で、変換結果を書き込んだ場合のみ元ファイルを削除
           await fileSystem.del(file);
           console.info(`Deleted original file: ${file}`);
         }
       } catch (e: unknown) {
         console.error(e);
-        if (e instanceof Error) {
-          globalStore.setMessage(e.message);
+        if (isCancelled(e)) {
+          cancelled = true;
+          break;
+        }
+        if (isWorkerFailure(e)) {
+          // 子プロセスで変換していれば、アプリは落ちていないので残りのファイルを続ける
+          failedFiles.push(file);
         } else {
-          globalStore.setMessage(String(e));
+          globalStore.setMessage(errorMessage(e));
+          playErrorSound();
+          dialog.value = false;
+          inProgress.value = false;
+          return;
         }
-        playErrorSound();
-        dialog.value = false;
-        inProgress.value = false;
-        return;
       }
       progress.value = Math.floor(((i + 1) / files.length) * 100);
     }
 
     dialog.value = false;
     inProgress.value = false;
+    if (cancelled) {
+      globalStore.setMessage(t('cancelled'));
+      return;
+    }
+    if (failedFiles.length > 0) {
+      console.warn('Failed files:', failedFiles);
+      playErrorSound();
+      globalStore.setMessage(t('completed_with_failures', { count: failedFiles.length }));
+      return;
+    }
     playCompleteSound();
     globalStore.setMessage(t('completed'));
   };
@@ -185,11 +251,7 @@ export function useImageConversionController(t: ComposerTranslation) {
       await processFiles(files);
     } catch (e) {
       console.error(e);
-      if (e instanceof Error) {
-        globalStore.setMessage(e.message);
-      } else {
-        globalStore.setMessage(String(e));
-      }
+      globalStore.setMessage(errorMessage(e));
       playErrorSound();
       return;
     }
@@ -230,11 +292,7 @@ export function useImageConversionController(t: ComposerTranslation) {
       await processFiles(files);
     } catch (e) {
       console.error(e);
-      if (e instanceof Error) {
-        globalStore.setMessage(e.message);
-      } else {
-        globalStore.setMessage(String(e));
-      }
+      globalStore.setMessage(errorMessage(e));
       dialog.value = false;
       progress.value = 0;
       inProgress.value = false;
@@ -1,3 +1,6 @@
+import type { EncodeOptions } from '@/interfaces/EncodeOptions';
+import type { IsolationOptions } from '@/interfaces/IsolationOptions';
+import type { FileConvertResult, SizePolicyOptions } from '@/interfaces/SizePolicyOptions';
 import { useSettingsStore } from '@/store';
 import { toRaw } from 'vue';
 
@@ -13,13 +16,12 @@ export function useImageConverter() {
   /**
    * 単一ファイルの変換処理
    * @param input 入力ファイルのパス
-   * @param options 変換パラメータ
+   * @param output 出力先ディレクトリ（省略時は入力ファイルと同じディレクトリ）
+   * @returns 変換結果（書き込んだか、サイズ、削減率）
    */
-  const convert = async (input: string, output?: string) => {
+  const convert = async (input: string, output?: string): Promise<FileConvertResult> => {
     // 入力ファイル名
     const fileName = await fileSystem.getFileName(input);
-    // 変換
-    const buffer = await compress(await fileSystem.read(input));
     // 出力ファイル名を生成
     const outputFileName = `${fileName.split('.').slice(0, -1).join('.')}.${settingsStore.commonOptions.format}`;
     // 保存先
@@ -27,10 +29,38 @@ export function useImageConverter() {
       ? await join(output, outputFileName) // 出力先を指定して保存
       : await join(await fileSystem.getDir(input), outputFileName); // 入力パスと同じディレクトリに保存
 
-    // 保存処理
-    await fileSystem.save(savePath, buffer);
+    // 変換と保存処理（サイズが十分に小さくならない場合はポリシーに従う）
+    const policy: SizePolicyOptions = {
+      onLarger: settingsStore.commonOptions.largerOutputPolicy,
+      minSavingsPercent: settingsStore.commonOptions.minSavingsPercent
+    };
+    return await invoke<FileConvertResult>('convert_file', {
+      inputPath: input,
+      outputPath: savePath,
+      options: encodeOptions(),
+      policy,
+      isolation: isolationOptions()
+    });
   };
 
+  /**
+   * 設定から分離モードのオプションを生成
+   * @returns 分離モードのオプション（無効の場合は null）
+   */
+  const isolationOptions = (): IsolationOptions | null =>
+    settingsStore.commonOptions.isolation
+      ? { timeoutSeconds: settingsStore.commonOptions.isolationTimeout }
+      : null;
+
+  /**
+   * 設定から圧縮オプションを生成
+   * @returns 圧縮オプション
+   */
+  const encodeOptions = (): EncodeOptions =>
+    settingsStore.commonOptions.format === 'avif'
+      ? { avif: toRaw(settingsStore.avifOptions) }
+      : { webp: toRaw(settingsStore.webpOptions) };
+
   /**
    * 圧縮処理
    * @param data 元バイナリデータ
@@ -38,18 +68,27 @@ export function useImageConverter() {
    */
   const compress = async (data: Uint8Array): Promise<Uint8Array> => {
     // 圧縮オプション
-    const options =
-      settingsStore.commonOptions.format === 'avif'
-        ? { avif: toRaw(settingsStore.avifOptions) }
-        : { webp: toRaw(settingsStore.webpOptions) };
+    const options = encodeOptions();
     try {
       // rust側のVec<8>はnumber[]型になるのでUint8Arrayに変換する
-      return new Uint8Array(await invoke<number[]>('convert', { data, options }));
+      return new Uint8Array(
+        await invoke<number[]>('convert', { data, options, isolation: isolationOptions() })
+      );
     } catch (e) {
       console.error(e);
       throw e;
     }
   };
 
-  return { convert, compress };
+  /**
+   * 一括変換の開始（前回の中止を取り消す）
+   */
+  const startBatch = async (): Promise<void> => await invoke('start_batch');
+
+  /**
+   * 一括変換の中止（変換中のファイルは書き込まずに `cancelled` のエラーになる）
+   */
+  const cancelBatch = async (): Promise<void> => await invoke('cancel_batch');
+
+  return { convert, compress, startBatch, cancelBatch };
 }
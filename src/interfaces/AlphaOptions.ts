@@ -0,0 +1,11 @@
+/**
+ * Rustの `AlphaOptions` 構造体に対応
+ */
+export interface AlphaOptions {
+  /** 全ての画素が不透明な場合はアルファを取り除く */
+  dropOpaque?: boolean;
+  /** 背景色 [R, G, B]（指定した場合は合成して透明部分を無くす） */
+  background?: [number, number, number];
+  /** 完全に透明な画素の色を揃える（透明部分の色情報が失われるため、既定では無効） */
+  cleanTransparent?: boolean;
+}
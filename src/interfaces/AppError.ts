@@ -0,0 +1,35 @@
+/**
+ * Rustの `AppError::code` に対応（エラーの種類を表す安定したコード）
+ */
+export type AppErrorCode =
+  | 'decode_failed'
+  | 'encode_failed'
+  | 'transform_failed'
+  | 'compare_failed'
+  | 'file_not_found'
+  | 'permission_denied'
+  | 'file_exists'
+  | 'disk_full'
+  | 'io_error'
+  | 'unsupported_format'
+  | 'unsupported_feature'
+  | 'limit_exceeded'
+  | 'cancelled'
+  | 'invalid_input'
+  | 'internal'
+  | 'worker_crashed'
+  | 'worker_timeout';
+
+/**
+ * Rustの `AppError` に対応（コマンドが失敗した場合に reject される値）
+ */
+export interface AppError {
+  /** エラーの種類 */
+  code: AppErrorCode;
+  /** 英語のメッセージ */
+  message: string;
+  /** 種類ごとの詳細（`format`, `limit` など）と原因のエラーのメッセージ（`sources`） */
+  details: Record<string, unknown> | null;
+  /** 対象のファイルのパス（分かる場合のみ） */
+  path: string | null;
+}
@@ -0,0 +1,32 @@
+/**
+ * Rustの `AutoCandidate` 列挙型に対応
+ */
+export type AutoCandidate = 'WebpLossy' | 'WebpLossless' | 'Avif' | 'Original';
+
+/**
+ * Rustの `AutoOptions` 構造体に対応
+ */
+export interface AutoOptions {
+  /** 比較する候補 */
+  candidates?: AutoCandidate[];
+  /** 非可逆圧縮の候補の品質 */
+  quality?: number;
+  /** 非可逆圧縮の候補に求めるSSIMULACRA2のスコア */
+  minScore?: number;
+}
+
+/**
+ * Rustの `CandidateReport` 構造体に対応
+ */
+export interface CandidateReport {
+  /** 候補 */
+  candidate: AutoCandidate;
+  /** 出力サイズ（バイト、失敗した場合はnull） */
+  size: number | null;
+  /** SSIMULACRA2のスコア */
+  score: number | null;
+  /** 選択の対象になったか */
+  accepted: boolean;
+  /** エンコードに失敗した場合のエラーメッセージ */
+  error: string | null;
+}
@@ -0,0 +1,58 @@
+import type { OutputFormat } from './VariantPlan';
+
+/**
+ * Rustの `OptionKind` 列挙型に対応（`type` で種類を判別する）
+ */
+export type OptionKind =
+  | { type: 'number'; min: number; max: number; step: number }
+  | { type: 'boolean' }
+  | { type: 'choice'; values: string[] }
+  | { type: 'group'; fields: OptionField[] };
+
+/**
+ * Rustの `OptionField` 構造体に対応
+ */
+export type OptionField = OptionKind & {
+  /** オプションのキー（例: `webp.quality` の `quality`） */
+  key: string;
+  /** 既定値（省略可能な項目は null） */
+  default: unknown;
+};
+
+/**
+ * Rustの `DecoderInfo` 構造体に対応
+ */
+export interface DecoderInfo {
+  /** 形式の名前（`jpeg`, `heic` など） */
+  name: string;
+  /** 拡張子の一覧（ドット無し） */
+  extensions: string[];
+  /** デコードできるか（feature で無効化されている場合は false） */
+  enabled: boolean;
+}
+
+/**
+ * Rustの `EncoderInfo` 構造体に対応
+ */
+export interface EncoderInfo {
+  /** 出力形式（`EncodeOptions` のキーと同じ） */
+  format: OutputFormat;
+  /** 出力ファイルの拡張子 */
+  extension: string;
+  /** MIME タイプ */
+  mimeType: string;
+  /** オプションの一覧 */
+  options: OptionField[];
+  /** エンコードできるか（feature で無効化されている場合は false） */
+  enabled: boolean;
+}
+
+/**
+ * Rustの `CodecList` 構造体に対応（`list_codecs` コマンドの戻り値）
+ */
+export interface CodecList {
+  /** 入力形式の一覧 */
+  decoders: DecoderInfo[];
+  /** 出力形式の一覧（優先順） */
+  encoders: EncoderInfo[];
+}
@@ -0,0 +1,9 @@
+/**
+ * Rustの `ColorOptions` 構造体に対応
+ */
+export interface ColorOptions {
+  /** 変換方式（sRGBに変換 / プロファイルを保持） */
+  mode: 'Srgb' | 'Preserve';
+  /** レンダリングインテント */
+  intent: 'Perceptual' | 'RelativeColorimetric' | 'Saturation' | 'AbsoluteColorimetric';
+}
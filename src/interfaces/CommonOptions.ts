@@ -1,3 +1,5 @@
+import type { LargerOutputPolicy } from './SizePolicyOptions';
+
 export interface CommonOptions {
   /** Output image format */
   format: 'avif' | 'webp';
@@ -13,4 +15,12 @@ export interface CommonOptions {
   ignoreJpeg: boolean;
   /** Output directory */
   outputPath: string;
+  /** What to do when the converted file is not small enough */
+  largerOutputPolicy: LargerOutputPolicy;
+  /** Minimum savings percentage required to write the converted file */
+  minSavingsPercent: number;
+  /** Convert in a separate process so that a crashing file does not stop the batch */
+  isolation: boolean;
+  /** Time limit for each file in isolation mode (seconds) */
+  isolationTimeout: number;
 }
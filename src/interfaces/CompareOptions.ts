@@ -0,0 +1,31 @@
+/**
+ * Rustの `CompareOptions` 構造体に対応
+ */
+export interface CompareOptions {
+  /** 差分のヒートマップ画像を生成する */
+  heatmap?: boolean;
+  /** ヒートマップの差分の増幅率 */
+  amplify?: number;
+}
+
+/**
+ * Rustの `CompareResult` 構造体に対応
+ */
+export interface CompareResult {
+  /** 比較した幅（元画像の幅） */
+  width: number;
+  /** 比較した高さ（元画像の高さ） */
+  height: number;
+  /** 比較対象を元画像のサイズにリサイズしたか */
+  resized: boolean;
+  /** PSNR（dB、同一の場合は null） */
+  psnr: number | null;
+  /** SSIM（輝度、1.0が同一） */
+  ssim: number;
+  /** SSIMULACRA2（100が同一） */
+  ssimulacra2: number;
+  /** チャンネルごとの最大絶対誤差 [R, G, B] */
+  maxAbsError: [number, number, number];
+  /** 差分を増幅したヒートマップ（PNGのdata URI） */
+  heatmap: string | null;
+}
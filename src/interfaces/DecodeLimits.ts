@@ -0,0 +1,16 @@
+/**
+ * Rustの `DecodeLimits` 構造体に対応（`set_decode_limits` / `get_decode_limits` コマンドで使用）
+ * 省略した項目は既定値、null を指定した項目は制限しない
+ */
+export interface DecodeLimits {
+  /** 幅の上限（px、既定値 65535） */
+  maxWidth?: number | null;
+  /** 高さの上限（px、既定値 65535） */
+  maxHeight?: number | null;
+  /** 画素数の上限（既定値 268435456） */
+  maxPixels?: number | null;
+  /** デコード結果のメモリ量の上限（バイト、既定値 512 MiB） */
+  maxAlloc?: number | null;
+  /** フレーム数またはページ数の上限（既定値 10000） */
+  maxFrames?: number | null;
+}
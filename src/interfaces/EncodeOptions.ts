@@ -1,4 +1,12 @@
+import type { AlphaOptions } from './AlphaOptions';
+import type { AutoOptions } from './AutoOptions';
 import type { AvifOptions } from './AvifOptions';
+import type { ColorOptions } from './ColorOptions';
+import type { JpegOptions } from './JpegOptions';
+import type { PngOptions } from './PngOptions';
+import type { TargetQualityOptions, TargetSizeOptions } from './TargetSizeOptions';
+import type { TransformOptions } from './TransformOptions';
+import type { WatermarkOptions } from './WatermarkOptions';
 import type { WebpOptions } from './WebpOptions';
 
 /**
@@ -7,4 +15,13 @@ import type { WebpOptions } from './WebpOptions';
 export interface EncodeOptions {
   avif?: AvifOptions;
   webp?: WebpOptions;
+  jpeg?: JpegOptions;
+  png?: PngOptions;
+  color?: ColorOptions;
+  transform?: TransformOptions;
+  watermark?: WatermarkOptions;
+  alpha?: AlphaOptions;
+  targetSize?: TargetSizeOptions;
+  targetQuality?: TargetQualityOptions;
+  auto?: AutoOptions;
 }
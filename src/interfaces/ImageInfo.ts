@@ -0,0 +1,60 @@
+/**
+ * Rustの `ColorType` 列挙型に対応
+ */
+export type ColorType = 'gray' | 'grayAlpha' | 'rgb' | 'rgba' | 'cmyk';
+
+/**
+ * Rustの `ExifSummary` 構造体に対応（記録されていない項目は null）
+ */
+export interface ExifSummary {
+  /** カメラのメーカー */
+  make: string | null;
+  /** カメラの機種 */
+  model: string | null;
+  /** レンズ */
+  lensModel: string | null;
+  /** 編集ソフト */
+  software: string | null;
+  /** 撮影日時（`YYYY:MM:DD HH:MM:SS`） */
+  dateTimeOriginal: string | null;
+  /** 露出時間（`1/125` など） */
+  exposureTime: string | null;
+  /** F 値 */
+  fNumber: number | null;
+  /** ISO 感度 */
+  iso: number | null;
+  /** 焦点距離（mm） */
+  focalLength: number | null;
+  /** 撮影位置の緯度（10 進の度、南緯は負の値） */
+  gpsLatitude: number | null;
+  /** 撮影位置の経度（10 進の度、西経は負の値） */
+  gpsLongitude: number | null;
+}
+
+/**
+ * Rustの `ImageInfo` 構造体に対応（`probe_image` コマンドの戻り値）
+ */
+export interface ImageInfo {
+  /** 入力形式（`jpeg`, `png`, `heic`, `jpeg2000` など） */
+  format: string;
+  /** 幅（px、EXIF の回転は適用しない） */
+  width: number;
+  /** 高さ（px、EXIF の回転は適用しない） */
+  height: number;
+  /** ファイルのサイズ（バイト） */
+  fileSize: number;
+  /** 1 チャンネルあたりのビット数 */
+  bitDepth: number;
+  /** 色の種類 */
+  colorType: ColorType;
+  /** アルファチャンネルを持つか */
+  hasAlpha: boolean;
+  /** フレーム数（アニメーション）またはページ数（TIFF）、静止画は 1 */
+  frameCount: number;
+  /** 埋め込まれた ICC プロファイルの説明 */
+  iccDescription: string | null;
+  /** EXIF の Orientation（1-8） */
+  orientation: number | null;
+  /** EXIF の主な項目 */
+  exif: ExifSummary | null;
+}
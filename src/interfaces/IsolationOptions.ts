@@ -0,0 +1,8 @@
+/**
+ * Rustの `IsolationOptions` 構造体に対応（変換コマンドの `isolation` 引数）
+ * 指定した場合、デコードとエンコードを子プロセスで行う
+ */
+export interface IsolationOptions {
+  /** 1 つの変換の制限時間（秒、既定値 120、null の場合は制限しない） */
+  timeoutSeconds?: number | null;
+}
@@ -0,0 +1,22 @@
+/** クロマサブサンプリング */
+export type ChromaSubsampling = 'Yuv444' | 'Yuv420';
+
+/**
+ * Rustの `JpegOptions` 構造体に対応
+ */
+export interface JpegOptions {
+  /** 品質（1~100） */
+  quality: number;
+  /** プログレッシブ JPEG にするか */
+  progressive: boolean;
+  /** クロマサブサンプリング */
+  subsampling: ChromaSubsampling;
+  /** ハフマンテーブルを最適化するか */
+  optimizeCoding: boolean;
+  /** トレリス量子化を使うか */
+  trellis: boolean;
+  /** ベースライン（非プログレッシブ）にするか */
+  baseline: boolean;
+  /** EXIF と ICC プロファイルを引き継ぐか */
+  keepMetadata: boolean;
+}
@@ -0,0 +1,27 @@
+/**
+ * Rustの `PngQuantizeOptions` 構造体に対応
+ */
+export interface PngQuantizeOptions {
+  /** パレットの最大色数（2~256） */
+  maxColors: number;
+  /** 品質の下限（0~100）。届かない場合は減色しない */
+  minQuality: number;
+  /** 品質の上限（0~100）。届く最も少ない色数を選ぶ */
+  maxQuality: number;
+  /** ディザリングの強さ（0.0~1.0） */
+  dithering: number;
+}
+
+/**
+ * Rustの `PngOptions` 構造体に対応
+ */
+export interface PngOptions {
+  /** 最適化レベル（0~6） */
+  level: number;
+  /** Zopfli で圧縮するか */
+  zopfli: boolean;
+  /** 表示に影響しないメタデータを削除するか */
+  stripMetadata: boolean;
+  /** 減色のオプション（未指定の場合は可逆圧縮） */
+  quantize?: PngQuantizeOptions;
+}
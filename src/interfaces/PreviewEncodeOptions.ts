@@ -0,0 +1,45 @@
+/**
+ * Rustの `PreviewRegion` 構造体に対応（リサイズする前の画像の座標）
+ */
+export interface PreviewRegion {
+  x: number;
+  y: number;
+  width: number;
+  height: number;
+}
+
+/**
+ * Rustの `PreviewEncodeOptions` 構造体に対応（省略した項目は既定値）
+ */
+export interface PreviewEncodeOptions {
+  /** 切り抜く範囲（null の場合は全体、はみ出した部分は除く。空の範囲はエラー） */
+  region?: PreviewRegion | null;
+  /** 切り抜いた範囲を収める最大幅（null の場合は縮小しない） */
+  maxWidth?: number | null;
+  /** 切り抜いた範囲を収める最大高さ（null の場合は縮小しない） */
+  maxHeight?: number | null;
+}
+
+/**
+ * Rustの `PreviewEncodeResult` 構造体に対応（`preview_encode` コマンドの戻り値）
+ */
+export interface PreviewEncodeResult {
+  /** 範囲をエンコードしたサイズ（バイト） */
+  size: number;
+  /** 画像全体をエンコードした場合の推定サイズ（バイト） */
+  estimatedSize: number;
+  /** エンコードにかかった時間（ms） */
+  encodeMillis: number;
+  /** 使用した品質（品質を持たない形式やロスレスの場合は null） */
+  quality: number | null;
+  /** エンコードした範囲の幅 */
+  width: number;
+  /** エンコードした範囲の高さ */
+  height: number;
+  /** 画像全体の出力幅 */
+  fullWidth: number;
+  /** 画像全体の出力高さ */
+  fullHeight: number;
+  /** エンコード結果をデコードした RGBA の画素（`new ImageData(new Uint8ClampedArray(rgba), width, height)` で表示できる） */
+  rgba: number[];
+}
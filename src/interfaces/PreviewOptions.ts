@@ -0,0 +1,33 @@
+import type { OutputFormat } from './VariantPlan';
+
+/**
+ * Rustの `PreviewOptions` 構造体に対応（省略した項目は既定値）
+ */
+export interface PreviewOptions {
+  /** 収める枠の幅（px、既定値 256） */
+  maxWidth?: number;
+  /** 収める枠の高さ（px、既定値 256） */
+  maxHeight?: number;
+  /** 出力形式（既定値 `webp`） */
+  format?: OutputFormat;
+  /** 非可逆圧縮の品質（0-100、既定値 75） */
+  quality?: number;
+  /** 埋め込みサムネイルが十分な大きさの場合はそれを使う（既定値 true） */
+  useEmbedded?: boolean;
+}
+
+/**
+ * Rustの `PreviewResult` 構造体に対応（`generate_preview` コマンドの戻り値）
+ */
+export interface PreviewResult {
+  /** エンコードされたバイト列 */
+  data: number[];
+  /** 出力形式の MIME タイプ */
+  mimeType: string;
+  /** プレビューの幅（EXIF の回転を適用した後） */
+  width: number;
+  /** プレビューの高さ（EXIF の回転を適用した後） */
+  height: number;
+  /** ディスクのキャッシュから返したか */
+  cached: boolean;
+}
@@ -0,0 +1,36 @@
+import type { AutoCandidate } from './AutoOptions';
+
+/**
+ * Rustの `LargerOutputPolicy` 列挙型に対応
+ */
+export type LargerOutputPolicy = 'Skip' | 'KeepOriginal' | 'WriteAnyway';
+
+/**
+ * Rustの `SizePolicyOptions` 構造体に対応
+ */
+export interface SizePolicyOptions {
+  /** 削減率が足りない場合の扱い */
+  onLarger?: LargerOutputPolicy;
+  /** 必要な最小の削減率（%） */
+  minSavingsPercent?: number;
+}
+
+/**
+ * Rustの `FileConvertResult` 構造体に対応
+ */
+export interface FileConvertResult {
+  /** 入力ファイルのパス */
+  inputPath: string;
+  /** 書き込んだファイルのパス（書き込まなかった場合はnull） */
+  outputPath: string | null;
+  /** 結果（KeptOriginal は変換結果を書き込まずに元のファイルを残した場合） */
+  outcome: 'Written' | 'Skipped' | 'KeptOriginal';
+  /** 元のファイルのサイズ（バイト） */
+  inputSize: number;
+  /** 変換結果のサイズ（バイト） */
+  convertedSize: number;
+  /** 削減率（%、大きくなった場合は負の値） */
+  savingsPercent: number;
+  /** 自動選択で選ばれた候補（outputPathの拡張子は候補の形式に合わせる） */
+  chosen?: AutoCandidate | null;
+}
@@ -0,0 +1,49 @@
+import type { AutoCandidate, CandidateReport } from './AutoOptions';
+
+/**
+ * Rustの `TargetSizeOptions` 構造体に対応
+ */
+export interface TargetSizeOptions {
+  /** 出力の最大サイズ（バイト、1 以上） */
+  maxBytes: number;
+  /** 探索する品質の下限 */
+  minQuality?: number;
+  /** 品質の下限でも収まらない場合に縮小を許可する */
+  allowDownscale?: boolean;
+}
+
+/**
+ * Rustの `ConvertResult` 構造体に対応
+ */
+export interface ConvertResult {
+  /** エンコードされたバイト列 */
+  data: number[];
+  /** 幅 */
+  width: number;
+  /** 高さ */
+  height: number;
+  /** 出力サイズ（バイト） */
+  size: number;
+  /** 最終的に使用した品質（品質を探索した場合のみ） */
+  quality?: number;
+  /** エンコードを試行した回数 */
+  attempts: number;
+  /** 知覚品質のスコア（知覚品質の目標を指定した場合のみ） */
+  score?: number;
+  /** 自動選択で選ばれた候補 */
+  chosen?: AutoCandidate;
+  /** 自動選択の各候補の結果 */
+  candidates: CandidateReport[];
+}
+
+/**
+ * Rustの `TargetQualityOptions` 構造体に対応
+ */
+export interface TargetQualityOptions {
+  /** 評価指標（Ssimulacra2: 高いほど高画質 / Dssim: 低いほど高画質） */
+  metric?: 'Ssimulacra2' | 'Dssim';
+  /** 目標スコア */
+  score: number;
+  /** 探索する品質の下限 */
+  minQuality?: number;
+}
@@ -0,0 +1,35 @@
+/**
+ * Rustの `TransformOperation` 列挙型に対応
+ */
+export type TransformOperation =
+  | { type: 'crop'; x: number; y: number; width: number; height: number }
+  | { type: 'rotate90' }
+  | { type: 'rotate180' }
+  | { type: 'rotate270' }
+  | { type: 'flipHorizontal' }
+  | { type: 'flipVertical' }
+  | { type: 'trim'; tolerance?: number };
+
+/**
+ * Rustの `TransformOptions` 構造体に対応
+ */
+export interface TransformOptions {
+  /** リサイズ前に指定順で適用する変形処理 */
+  operations?: TransformOperation[];
+  /** 最大幅（超える場合のみ縮小） */
+  maxWidth?: number;
+  /** 最大高さ（超える場合のみ縮小） */
+  maxHeight?: number;
+  /** 出力幅 */
+  width?: number;
+  /** 出力高さ */
+  height?: number;
+  /** 幅と高さを両方指定した場合の合わせ方 */
+  fit?: 'Contain' | 'Cover' | 'Fill' | 'Inside';
+  /** リサンプリングフィルター */
+  filter?: 'Lanczos3' | 'Mitchell' | 'CatmullRom';
+  /** リニアライトで縮小する */
+  linear?: boolean;
+  /** 乗算済みアルファで縮小する */
+  premultiplyAlpha?: boolean;
+}
@@ -0,0 +1,88 @@
+import type { EncodeOptions } from './EncodeOptions';
+
+/** 出力形式 */
+export type OutputFormat = 'avif' | 'webp' | 'jpeg' | 'png';
+
+/**
+ * Rustの `VariantTarget` 構造体に対応
+ */
+export interface VariantTarget {
+  /** 出力幅 */
+  width: number;
+  /** 出力形式 */
+  format: OutputFormat;
+  /** エンコードオプション */
+  options: EncodeOptions;
+}
+
+/**
+ * Rustの `VariantPlan` 構造体に対応
+ */
+export interface VariantPlan {
+  /** 出力対象の一覧 */
+  targets: VariantTarget[];
+  /** 出力ファイル名のパターン（{name}, {width}, {ext}） */
+  fileNamePattern?: string;
+  /** 元画像より大きい幅への拡大を許可するか */
+  allowUpscale?: boolean;
+  /** マニフェストとHTMLの生成オプション */
+  manifest?: ManifestOptions;
+}
+
+/**
+ * Rustの `ManifestOptions` 構造体に対応
+ */
+export interface ManifestOptions {
+  /** `<source>` の sizes 属性 */
+  sizes?: string;
+  /** `<img>` の alt 属性 */
+  alt?: string;
+  /** srcset / src の前に付けるURL */
+  urlPrefix?: string;
+  /** プレースホルダーの種類 */
+  placeholder?: 'None' | 'Lqip' | 'Blurhash';
+  /** HTMLスニペットをファイルに書き出すか */
+  html?: boolean;
+}
+
+/**
+ * Rustの `Manifest` 構造体に対応
+ */
+export interface Manifest {
+  /** 元のファイル名（拡張子無し） */
+  name: string;
+  /** 形式ごとの `<source>` */
+  sources: { type: string; srcset: string; sizes: string }[];
+  /** フォールバックの `<img>` */
+  fallback: { src: string; width: number; height: number };
+  /** プレースホルダー */
+  placeholder?: { kind: 'lqip' | 'blurhash'; value: string };
+  /** `<picture>` のHTMLスニペット */
+  html: string;
+}
+
+/**
+ * Rustの `VariantResult` 構造体に対応
+ */
+export interface VariantResult {
+  /** 生成されたファイルの一覧 */
+  outputs: VariantOutput[];
+  /** マニフェスト */
+  manifest?: Manifest;
+}
+
+/**
+ * Rustの `VariantOutput` 構造体に対応
+ */
+export interface VariantOutput {
+  /** 出力ファイルのパス */
+  path: string;
+  /** 幅 */
+  width: number;
+  /** 高さ */
+  height: number;
+  /** 出力形式 */
+  format: OutputFormat;
+  /** ファイルサイズ（バイト） */
+  size: number;
+}
@@ -0,0 +1,37 @@
+/**
+ * Rustの `Anchor` 列挙型に対応
+ */
+export type Anchor =
+  | 'topLeft'
+  | 'top'
+  | 'topRight'
+  | 'left'
+  | 'center'
+  | 'right'
+  | 'bottomLeft'
+  | 'bottom'
+  | 'bottomRight';
+
+/**
+ * Rustの `WatermarkOptions` 構造体に対応
+ */
+export interface WatermarkOptions {
+  /** 重ねる画像のパス（textより優先） */
+  imagePath?: string;
+  /** 重ねる文字列 */
+  text?: string;
+  /** フォントファイルのパス（未指定の場合は同梱の DejaVu Sans） */
+  fontPath?: string;
+  /** テキストの色 [R, G, B] */
+  color?: [number, number, number];
+  /** 配置位置 */
+  anchor?: Anchor;
+  /** 端からの余白（px）、タイル表示の場合は間隔 */
+  margin?: number;
+  /** 透かしの幅（画像の幅に対する割合） */
+  scale?: number;
+  /** 不透明度（0.0〜1.0） */
+  opacity?: number;
+  /** 画像全体に敷き詰める */
+  tile?: boolean;
+}
@@ -32,7 +32,11 @@ const defaultCommonOptions: CommonOptions = {
   recursive: false,
   sameDirectory: true,
   ignoreJpeg: false,
-  outputPath: await documentDir()
+  outputPath: await documentDir(),
+  largerOutputPolicy: 'WriteAnyway',
+  minSavingsPercent: 0,
+  isolation: false,
+  isolationTimeout: 120
 } as const;
 
 /** Global Store */